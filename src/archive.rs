@@ -0,0 +1,353 @@
+//! Copies of the messages of a course, kept on disk before its channels are deleted.
+//!
+//! Each archive is written twice to `data/archives/<guild_id>/`: as json, which the `archive`
+//! binary reads back, and as a standalone html page that any browser can open.
+
+use crate::{log, storage::write_atomically, util::is_not_found};
+use anyhow::Context as _;
+use chrono::{DateTime, Utc};
+use futures::stream::TryStreamExt;
+use serde::{Deserialize, Serialize};
+use serenity::{
+    model::{
+        channel::{Channel, ChannelType, GuildChannel, Message},
+        id::{ChannelId, GuildId},
+    },
+    prelude::Context,
+};
+use std::{
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+};
+
+const ARCHIVES: &str = "data/archives";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Archive {
+    pub course: String,
+    pub guild: GuildId,
+    /// When the archive was made, in RFC 3339.
+    pub archived_at: String,
+    pub channels: Vec<ArchivedChannel>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ArchivedChannel {
+    pub id: ChannelId,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub topic: Option<String>,
+    /// Oldest first.
+    pub messages: Vec<ArchivedMessage>,
+    /// The threads of the channel, which for a forum are its posts.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub threads: Vec<ArchivedChannel>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ArchivedMessage {
+    pub author: String,
+    pub author_id: u64,
+    /// When the message was sent, in RFC 3339.
+    pub timestamp: String,
+    pub content: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<ArchivedAttachment>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub embeds: Vec<ArchivedEmbed>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ArchivedAttachment {
+    pub filename: String,
+    pub url: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct ArchivedEmbed {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+}
+
+impl From<&Message> for ArchivedMessage {
+    fn from(m: &Message) -> Self {
+        ArchivedMessage {
+            author: m.author.name.clone(),
+            author_id: m.author.id.get(),
+            timestamp: m.timestamp.to_string(),
+            content: m.content.clone(),
+            attachments: m
+                .attachments
+                .iter()
+                .map(|a| ArchivedAttachment {
+                    filename: a.filename.clone(),
+                    url: a.url.clone(),
+                })
+                .collect(),
+            embeds: m
+                .embeds
+                .iter()
+                .map(|e| ArchivedEmbed {
+                    title: e.title.clone(),
+                    description: e.description.clone(),
+                    url: e.url.clone(),
+                    image: e.image.as_ref().map(|i| i.url.clone()),
+                })
+                .collect(),
+        }
+    }
+}
+
+pub fn archive_dir(guild: GuildId) -> PathBuf {
+    Path::new(ARCHIVES).join(guild.to_string())
+}
+
+async fn messages(ctx: &Context, channel: ChannelId) -> serenity::Result<Vec<ArchivedMessage>> {
+    let mut messages = channel
+        .messages_iter(&ctx.http)
+        .map_ok(|m| ArchivedMessage::from(&m))
+        .try_collect::<Vec<_>>()
+        .await?;
+    messages.reverse();
+    Ok(messages)
+}
+
+async fn archive_channel(
+    ctx: &Context,
+    channel: &GuildChannel,
+    threads: &[GuildChannel],
+) -> serenity::Result<ArchivedChannel> {
+    let mut archived = ArchivedChannel {
+        id: channel.id,
+        name: channel.name.clone(),
+        topic: channel.topic.clone(),
+        messages: Vec::new(),
+        threads: Vec::new(),
+    };
+    // A forum only has messages in its posts, and voice channels have no threads.
+    if channel.kind != ChannelType::Forum {
+        archived.messages = messages(ctx, channel.id).await?;
+    }
+    if channel.kind == ChannelType::Voice {
+        return Ok(archived);
+    }
+    let old_threads = channel
+        .id
+        .get_archived_public_threads(&ctx.http, None, None)
+        .await?;
+    if old_threads.has_more {
+        log!(
+            "Only the newest archived threads of {} could be archived",
+            channel.name
+        );
+    }
+    for thread in threads
+        .iter()
+        .filter(|t| t.parent_id == Some(channel.id))
+        .chain(&old_threads.threads)
+    {
+        archived.threads.push(ArchivedChannel {
+            id: thread.id,
+            name: thread.name.clone(),
+            topic: None,
+            messages: messages(ctx, thread.id).await?,
+            threads: Vec::new(),
+        });
+    }
+    Ok(archived)
+}
+
+impl Archive {
+    /// Fetches every message of the channels of a course, including their threads. Channels that
+    /// no longer exist are left out.
+    pub async fn fetch(
+        ctx: &Context,
+        guild: GuildId,
+        course: &str,
+        channels: &[ChannelId],
+    ) -> serenity::Result<Self> {
+        let threads = guild.get_active_threads(&ctx.http).await?.threads;
+        let mut archive = Archive {
+            course: course.to_string(),
+            guild,
+            archived_at: Utc::now().to_rfc3339(),
+            channels: Vec::new(),
+        };
+        for channel in channels {
+            let channel = match channel.to_channel(&ctx.http).await {
+                Ok(channel) => channel,
+                // Channels deleted by hand have nothing left to archive.
+                Err(e) if is_not_found(&e) => continue,
+                Err(e) => return Err(e),
+            };
+            // Categories have no messages.
+            if let Channel::Guild(c) = channel
+                && c.kind != ChannelType::Category
+            {
+                archive
+                    .channels
+                    .push(archive_channel(ctx, &c, &threads).await?);
+            }
+        }
+        Ok(archive)
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let file =
+            fs::read(path).with_context(|| format!("{} could not be read", path.display()))?;
+        serde_json::from_slice(&file)
+            .with_context(|| format!("{} is not an archive", path.display()))
+    }
+
+    /// Writes the json and html versions of the archive to the archives of its guild, returning
+    /// the path of the json one.
+    pub fn save(&self) -> anyhow::Result<PathBuf> {
+        self.save_in(&archive_dir(self.guild))
+    }
+
+    /// Writes the json and html versions of the archive to `dir`, returning the path of the json
+    /// one.
+    pub fn save_in(&self, dir: &Path) -> anyhow::Result<PathBuf> {
+        fs::create_dir_all(dir)?;
+        let time = DateTime::parse_from_rfc3339(&self.archived_at)
+            .map(|t| t.format("%Y-%m-%d-%H%M%S").to_string())
+            .unwrap_or_else(|_| self.archived_at.replace(':', ""));
+        let base = format!("{}-{}", self.course, time);
+        // Archives of the same course made in the same second are told apart by a counter.
+        let stem = (1..)
+            .map(|n| match n {
+                1 => base.clone(),
+                n => format!("{}-{}", base, n),
+            })
+            .find(|stem| !dir.join(format!("{}.json", stem)).exists())
+            .expect("some counter is free");
+        let json = dir.join(format!("{}.json", stem));
+        let html = dir.join(format!("{}.html", stem));
+        write_atomically(&json, &serde_json::to_vec_pretty(self)?)
+            .with_context(|| format!("{} could not be written", json.display()))?;
+        write_atomically(&html, self.to_html().as_bytes())
+            .with_context(|| format!("{} could not be written", html.display()))?;
+        Ok(json)
+    }
+
+    pub fn message_count(&self) -> usize {
+        fn count(c: &ArchivedChannel) -> usize {
+            c.messages.len() + c.threads.iter().map(count).sum::<usize>()
+        }
+        self.channels.iter().map(count).sum()
+    }
+
+    /// The archive as plain text, for reading in a terminal.
+    pub fn to_text(&self) -> String {
+        fn channel(s: &mut String, c: &ArchivedChannel, depth: usize) {
+            let indent = "  ".repeat(depth);
+            let _ = writeln!(s, "{}#{}", indent, c.name);
+            if let Some(topic) = &c.topic {
+                let _ = writeln!(s, "{}  {}", indent, topic);
+            }
+            for m in &c.messages {
+                let _ = writeln!(s, "{}[{}] {}: {}", indent, m.timestamp, m.author, m.content);
+                for a in &m.attachments {
+                    let _ = writeln!(s, "{}    anexo {}: {}", indent, a.filename, a.url);
+                }
+                for e in &m.embeds {
+                    let text = [&e.title, &e.description, &e.url, &e.image]
+                        .into_iter()
+                        .flatten()
+                        .map(String::as_str)
+                        .collect::<Vec<_>>();
+                    let _ = writeln!(s, "{}    embed: {}", indent, text.join(" | "));
+                }
+            }
+            for t in &c.threads {
+                channel(s, t, depth + 1);
+            }
+        }
+        let mut s = format!("{} (arquivada em {})\n\n", self.course, self.archived_at);
+        for c in &self.channels {
+            channel(&mut s, c, 0);
+            s.push('\n');
+        }
+        s
+    }
+
+    /// The archive as a standalone html page.
+    pub fn to_html(&self) -> String {
+        fn channel(s: &mut String, c: &ArchivedChannel) {
+            let _ = writeln!(s, "<section><h2>#{}</h2>", escape(&c.name));
+            if let Some(topic) = &c.topic {
+                let _ = writeln!(s, "<p class=\"topic\">{}</p>", escape(topic));
+            }
+            for m in &c.messages {
+                let _ = write!(
+                    s,
+                    "<div class=\"message\"><span class=\"author\">{}</span> \
+                    <time>{}</time><p>{}</p>",
+                    escape(&m.author),
+                    escape(&m.timestamp),
+                    escape(&m.content).replace('\n', "<br>")
+                );
+                for a in &m.attachments {
+                    let _ = write!(
+                        s,
+                        "<a class=\"attachment\" href=\"{}\">{}</a>",
+                        escape(&a.url),
+                        escape(&a.filename)
+                    );
+                }
+                for e in &m.embeds {
+                    s.push_str("<blockquote class=\"embed\">");
+                    if let Some(title) = &e.title {
+                        let _ = write!(s, "<b>{}</b>", escape(title));
+                    }
+                    if let Some(description) = &e.description {
+                        let _ = write!(s, "<p>{}</p>", escape(description));
+                    }
+                    if let Some(url) = &e.url {
+                        let _ = write!(s, "<a href=\"{0}\">{0}</a>", escape(url));
+                    }
+                    if let Some(image) = &e.image {
+                        let _ = write!(s, "<img src=\"{}\">", escape(image));
+                    }
+                    s.push_str("</blockquote>");
+                }
+                s.push_str("</div>\n");
+            }
+            for t in &c.threads {
+                channel(s, t);
+            }
+            s.push_str("</section>\n");
+        }
+        let mut s = format!(
+            "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>{0}</title>\n<style>\
+            body{{font-family:sans-serif;max-width:60em;margin:auto}}\
+            section section{{margin-left:2em}}\
+            .message{{margin:.5em 0}}.author{{font-weight:bold}}time{{color:gray}}\
+            .embed{{border-left:3px solid gray;padding-left:.5em}}\
+            .attachment{{display:block}}\
+            </style></head><body>\n<h1>{0}</h1><p>Arquivada em {1}</p>\n",
+            escape(&self.course),
+            escape(&self.archived_at)
+        );
+        for c in &self.channels {
+            channel(&mut s, c);
+        }
+        s.push_str("</body></html>\n");
+        s
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,63 @@
+//! Reads the archives of removed courses without connecting to discord.
+//!
+//! Without arguments lists the archives in `data/archives`, otherwise prints the archive in the
+//! given json file, optionally only the channels whose name contains the second argument.
+
+use rusteze::archive::Archive;
+use std::{env, fs, path::Path, process};
+
+fn list() -> anyhow::Result<()> {
+    let root = Path::new("data/archives");
+    if !root.exists() {
+        println!("Não há arquivos em {}", root.display());
+        return Ok(());
+    }
+    for guild in fs::read_dir(root)? {
+        let mut paths = fs::read_dir(guild?.path())?
+            .map(|e| e.map(|e| e.path()))
+            .collect::<Result<Vec<_>, _>>()?;
+        paths.sort();
+        for path in paths
+            .iter()
+            .filter(|p| p.extension() == Some("json".as_ref()))
+        {
+            match Archive::load(path) {
+                Ok(a) => println!(
+                    "{}: {} ({} mensagens, arquivada em {})",
+                    path.display(),
+                    a.course,
+                    a.message_count(),
+                    a.archived_at
+                ),
+                Err(e) => eprintln!("{:#}", e),
+            }
+        }
+    }
+    Ok(())
+}
+
+fn show(path: &Path, channel: Option<&str>) -> anyhow::Result<()> {
+    let mut archive = Archive::load(path)?;
+    if let Some(channel) = channel {
+        archive.channels.retain(|c| c.name.contains(channel));
+    }
+    print!("{}", archive.to_text());
+    Ok(())
+}
+
+fn main() {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    let result = match args.as_slice() {
+        [] => list(),
+        [path] => show(path.as_ref(), None),
+        [path, channel] => show(path.as_ref(), Some(channel)),
+        _ => {
+            eprintln!("usage: archive [ARCHIVE.json [CHANNEL]]");
+            process::exit(2);
+        }
+    };
+    if let Err(e) = result {
+        eprintln!("{:#}", e);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,206 @@
+//! A declarative description of the courses of a guild, written by hand in TOML or CSV.
+
+use crate::{channels::MiEI, config::CourseStyle};
+use anyhow::anyhow;
+use serde::{Deserialize, Deserializer, Serialize};
+use serenity::model::id::{ChannelId, RoleId};
+use std::collections::{HashMap, HashSet};
+
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq, Eq)]
+pub struct Catalogue {
+    #[serde(rename = "course", default)]
+    pub courses: Vec<Entry>,
+    /// Only filled in by exports, imports ignore it.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub deprecated: Vec<Deprecated>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq, Eq)]
+pub struct Entry {
+    #[serde(deserialize_with = "string_or_number")]
+    pub year: String,
+    #[serde(deserialize_with = "string_or_number")]
+    pub semester: String,
+    /// The short name students use with `$study`.
+    pub code: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Extra topics of the course, each one gets the channels of the templates of the course.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub channels: Vec<String>,
+    /// Only filled in by exports, imports ignore it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub role: Option<RoleId>,
+    /// Only filled in by exports, imports ignore it.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub channel_ids: Vec<ChannelId>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq, Eq)]
+pub struct Deprecated {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub role: RoleId,
+    pub channel_ids: Vec<ChannelId>,
+}
+
+/// Spreadsheets export numbers and people write `year = 1` in TOML, so both are accepted.
+fn string_or_number<'de, D: Deserializer<'de>>(d: D) -> Result<String, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum StringOrNumber {
+        String(String),
+        Number(u64),
+    }
+    Ok(match StringOrNumber::deserialize(d)? {
+        StringOrNumber::String(s) => s,
+        StringOrNumber::Number(n) => n.to_string(),
+    })
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Toml,
+    Csv,
+}
+
+impl Format {
+    /// Guesses the format from the extension of a file name.
+    pub fn from_file_name(name: &str) -> Option<Self> {
+        match name.rsplit_once('.')?.1.to_lowercase().as_str() {
+            "toml" => Some(Format::Toml),
+            "csv" => Some(Format::Csv),
+            _ => None,
+        }
+    }
+}
+
+/// A row of a CSV catalogue, where the extra channels are separated by `;`.
+#[derive(Deserialize)]
+struct CsvEntry {
+    year: String,
+    semester: String,
+    code: String,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    channels: String,
+}
+
+impl Catalogue {
+    pub fn parse(s: &str, format: Format) -> anyhow::Result<Self> {
+        let mut catalogue: Catalogue = match format {
+            Format::Toml => toml::from_str(s)?,
+            Format::Csv => Catalogue {
+                courses: csv::ReaderBuilder::new()
+                    .trim(csv::Trim::All)
+                    .from_reader(s.as_bytes())
+                    .deserialize::<CsvEntry>()
+                    .map(|e| {
+                        let e = e?;
+                        Ok(Entry {
+                            year: e.year,
+                            semester: e.semester,
+                            code: e.code,
+                            name: Some(e.name).filter(|n| !n.is_empty()),
+                            channels: e
+                                .channels
+                                .split(';')
+                                .map(str::trim)
+                                .filter(|c| !c.is_empty())
+                                .map(str::to_string)
+                                .collect(),
+                            ..Default::default()
+                        })
+                    })
+                    .collect::<anyhow::Result<_>>()?,
+                ..Default::default()
+            },
+        };
+        let mut codes = HashSet::new();
+        for entry in &mut catalogue.courses {
+            entry.code = entry.code.to_uppercase();
+            if entry.code.is_empty() || entry.code.contains(char::is_whitespace) {
+                return Err(anyhow!("Invalid course code: '{}'", entry.code));
+            }
+            if !codes.insert(entry.code.clone()) {
+                return Err(anyhow!("Duplicate course code: '{}'", entry.code));
+            }
+        }
+        Ok(catalogue)
+    }
+
+    /// Describes the current courses of a guild.
+    ///
+    /// The names of the guild's channels and roles are used to recover the extra channels of
+    /// each course, by the templates of the course, and the names of deprecated courses.
+    pub fn from_courses(
+        miei: &MiEI,
+        style: &CourseStyle,
+        channel_names: &HashMap<ChannelId, String>,
+        role_names: &HashMap<RoleId, String>,
+    ) -> Self {
+        let mut courses = miei
+            .courses()
+            .map(|(year, semester, code, course)| {
+                // What goes before and after the topic in the names of the channels, lowercase
+                // like Discord keeps them.
+                let patterns = style
+                    .templates_for(year, code)
+                    .iter()
+                    .filter_map(|t| {
+                        let (before, after) = t.name.split_once("{name}")?;
+                        Some((before.to_lowercase(), after.to_lowercase()))
+                    })
+                    .collect::<Vec<_>>();
+                let own_topic = code.to_lowercase();
+                let mut channels = Vec::<String>::new();
+                for name in course.channels.iter().filter_map(|c| channel_names.get(c)) {
+                    let name = name.to_lowercase();
+                    let topic = patterns.iter().find_map(|(before, after)| {
+                        name.strip_prefix(before.as_str())?
+                            .strip_suffix(after.as_str())
+                            .filter(|t| !t.is_empty())
+                    });
+                    // Each topic has a channel for every template.
+                    if let Some(topic) = topic.filter(|t| *t != own_topic)
+                        && !channels.iter().any(|c| c == topic)
+                    {
+                        channels.push(topic.to_string());
+                    }
+                }
+                Entry {
+                    year: year.to_string(),
+                    semester: semester.to_string(),
+                    code: code.to_string(),
+                    name: course.info.full_name.clone(),
+                    channels,
+                    role: Some(course.role),
+                    channel_ids: course.channels.clone(),
+                }
+            })
+            .collect::<Vec<_>>();
+        courses
+            .sort_by(|a, b| (&a.year, &a.semester, &a.code).cmp(&(&b.year, &b.semester, &b.code)));
+        let deprecated = miei
+            .deprecated_courses()
+            .iter()
+            .map(|c| Deprecated {
+                code: role_names.get(&c.role).cloned(),
+                name: c.info.full_name.clone(),
+                role: c.role,
+                channel_ids: c.channels.clone(),
+            })
+            .collect();
+        Catalogue {
+            courses,
+            deprecated,
+        }
+    }
+
+    pub fn to_toml(&self) -> anyhow::Result<String> {
+        Ok(toml::to_string_pretty(self)?)
+    }
+}
@@ -1,86 +1,428 @@
-use crate::util::SendSyncError;
-use anyhow::anyhow;
+use crate::{
+    archive::{self, Archive},
+    config::{ChannelTemplate, Config, CourseStyle},
+    guild::{self, GuildData, GuildMap},
+    storage::{Storage, migrations},
+    util::is_not_found,
+};
+use anyhow::{Context as _, anyhow};
+use chrono::{Datelike, NaiveDate};
+use journal::{Change, Failed, Journal, failed};
 use serde::{Deserialize, Serialize};
 use serenity::{
-    all::{CreateChannel, EditChannel, EditRole},
+    all::{CreateChannel, CreateForumTag, EditChannel, EditRole},
     model::{
         channel::{
-            Channel as SerenityChannel, ChannelType, PermissionOverwrite,
-            PermissionOverwriteType::{Member, Role},
+            Channel as SerenityChannel, ChannelType, GuildChannel, PermissionOverwrite,
+            PermissionOverwriteType::{self, Member, Role},
         },
+        guild::Role as SerenityRole,
         id::{ChannelId, GuildId, RoleId},
         permissions::Permissions,
     },
-    prelude::{Context, RwLock, TypeMapKey},
+    prelude::{Context, TypeMapKey},
 };
-use std::{collections::HashMap, fs::File, io, sync::Arc};
+use std::collections::HashMap;
+
+mod journal;
 
-const COURSES: &str = "data/courses.json";
-const DEPRECATED_CATEGORY: ChannelId = ChannelId::new(618553779192856577);
+pub use journal::CourseError;
 
 #[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq, Eq)]
 pub struct MiEI {
-    #[serde(flatten)]
+    #[serde(skip)]
+    guild: GuildId,
+    #[serde(default, rename = "years")]
     courses: HashMap<String, Year>,
     #[serde(default)]
     deprecated_courses: Vec<Course>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    expiry: Vec<Expiry>,
+    /// Set by [`MiEI::dry_run`]: the steps of the course changes, which are never made.
+    #[serde(skip)]
+    plan: Option<Vec<String>>,
+}
+
+/// How similar a name has to be to a course for it to be suggested, from 0 to 1.
+const SIMILARITY: f64 = 0.7;
+
+/// How similar a name is to the name or alias of a course, or to one of its words, from 0 to 1.
+/// Names that start a longer one, like ALGEBRA for ALGEBRA-LINEAR, count as very similar.
+fn similarity(name: &str, key: &str) -> f64 {
+    if name.len() >= 3 && key.starts_with(name) {
+        return 0.9;
+    }
+    let words = key.split('-').filter(|w| w.len() > 2);
+    std::iter::once(key)
+        .chain(words)
+        .map(|k| strsim::normalized_damerau_levenshtein(name, k))
+        .fold(0.0, f64::max)
 }
 
+/// Creates the channels of `templates` for `name` in a course category.
 async fn create_channels(
     ctx: &Context,
     guild: GuildId,
+    templates: &[ChannelTemplate],
     name: &str,
+    role: RoleId,
     category_id: ChannelId,
-) -> serenity::Result<(ChannelId, ChannelId)> {
-    let duvidas = guild
-        .create_channel(
-            &ctx,
-            CreateChannel::new(format!("duvidas-{}", name))
-                .kind(ChannelType::Text)
-                .category(category_id),
-        )
-        .await?;
-    let anexos = guild
-        .create_channel(
-            &ctx,
-            CreateChannel::new(format!("anexos-{}", name))
-                .kind(ChannelType::Text)
-                .category(category_id),
-        )
-        .await?;
-    Ok((duvidas.id, anexos.id))
+    journal: &mut Journal,
+) -> Result<Vec<ChannelId>, Failed> {
+    let mut channels = Vec::with_capacity(templates.len());
+    for template in templates {
+        let channel_name = template.name_for(name);
+        let kind = ChannelType::from(template.kind);
+        journal.plan(format!("+ {} channel {}", kind.name(), channel_name));
+        let mut channel = CreateChannel::new(&channel_name)
+            .kind(kind)
+            .category(category_id);
+        if let Some(topic) = template.topic_for(name) {
+            journal.plan(format!("    topic: {}", topic));
+            channel = channel.topic(topic);
+        }
+        if !template.tags.is_empty() {
+            journal.plan(format!("    tags: {}", template.tags.join(", ")));
+        }
+        if !template.overwrites.is_empty() {
+            // Channels with their own overwrites no longer follow the category, so they
+            // start from its overwrites.
+            let mut perms = course_overwrites(ctx, guild, role);
+            for overwrite in &template.overwrites {
+                let overwrite = overwrite.to_overwrite(guild, role);
+                perms.retain(|o| o.kind != overwrite.kind);
+                perms.push(overwrite);
+            }
+            journal.plan_overwrites(guild, &perms);
+            channel = channel.permissions(perms);
+        }
+        if journal.is_dry_run() {
+            channels.push(journal.placeholder(&channel_name));
+            continue;
+        }
+        let channel = guild
+            .create_channel(&ctx, channel)
+            .await
+            .map_err(failed("create a channel"))?;
+        journal.record(Change::CreatedChannel(channel.id));
+        if !template.tags.is_empty() {
+            // Tags can only be given to a forum that already exists.
+            channel
+                .id
+                .edit(
+                    &ctx.http,
+                    EditChannel::new()
+                        .available_tags(template.tags.iter().map(CreateForumTag::new)),
+                )
+                .await
+                .map_err(failed("tag a forum"))?;
+        }
+        channels.push(channel.id);
+    }
+    Ok(channels)
 }
 
-fn add_category_emoji<'a>(year: &'a str, s: &'a str) -> String {
-    format!(
-        "{} {}",
-        match year {
-            "1" => "📚",
-            "2" => "📗",
-            "3" => "📘",
-            "4" => "📙",
-            "5" => "📓",
-            _ => "",
+fn previous_overwrite(
+    channel: &GuildChannel,
+    kind: PermissionOverwriteType,
+) -> Option<PermissionOverwrite> {
+    channel
+        .permission_overwrites
+        .iter()
+        .find(|o| o.kind == kind)
+        .cloned()
+}
+
+/// Creates the role, category and channels of a new course.
+async fn create_course(
+    ctx: &Context,
+    guild: GuildId,
+    style: &CourseStyle,
+    year: &str,
+    name: &str,
+    journal: &mut Journal,
+) -> Result<Course, Failed> {
+    journal.plan(format!(
+        "+ role {} (#{:06x})",
+        name,
+        style.role_colour(year)
+    ));
+    let role = if journal.is_dry_run() {
+        journal.placeholder(name)
+    } else {
+        let role = guild
+            .create_role(&ctx.http, course_role(style, name, year))
+            .await
+            .map_err(failed("create the role"))?;
+        journal.record(Change::CreatedRole(role.id));
+        role.id
+    };
+    let perms = course_overwrites(ctx, guild, role);
+    let category_name = style.category_name(year, name);
+    journal.plan(format!("+ category {}", category_name));
+    journal.plan_overwrites(guild, &perms);
+    let category = if journal.is_dry_run() {
+        journal.placeholder(&category_name)
+    } else {
+        let category = guild
+            .create_channel(
+                &ctx,
+                CreateChannel::new(category_name)
+                    .kind(ChannelType::Category)
+                    .permissions(perms),
+            )
+            .await
+            .map_err(failed("create the category"))?;
+        journal.record(Change::CreatedChannel(category.id));
+        category.id
+    };
+    let templates = style.templates_for(year, name);
+    let mut channels = vec![category];
+    channels.extend(create_channels(ctx, guild, &templates, name, role, category, journal).await?);
+    Ok(Course {
+        role,
+        channels,
+        ..Default::default()
+    })
+}
+
+/// The role of a course in its year.
+pub(crate) fn course_role<'a>(style: &CourseStyle, name: &str, year: &str) -> EditRole<'a> {
+    EditRole::new()
+        .hoist(false)
+        .mentionable(true)
+        .name(name)
+        .colour(style.role_colour(year))
+}
+
+pub(crate) async fn course_style(ctx: &Context, guild: GuildId) -> CourseStyle {
+    guild::data::<Config>(ctx, guild)
+        .await
+        .read()
+        .await
+        .course_style()
+        .clone()
+}
+
+/// Moves the set of channels chosen for a course to its `new` name, or forgets it when the
+/// course was removed.
+async fn update_course_templates(ctx: &Context, guild: GuildId, old: &str, new: Option<&str>) {
+    let config = guild::data::<Config>(ctx, guild).await;
+    let mut config = config.write().await;
+    let updated = match new {
+        Some(new) => config.rename_course_templates(old, new),
+        None => config.forget_course_templates(old),
+    };
+    if let Err(e) = updated {
+        crate::log!("Could not update the templates chosen for {}: {}", old, e);
+    }
+}
+
+/// Archives the messages of the channels of a course, which [`MiEI::remove_role`] needs before
+/// deleting them. Archiving can take minutes, so it's done without holding the courses.
+pub async fn archive_course(
+    ctx: &Context,
+    guild: GuildId,
+    name: &str,
+    channels: &[ChannelId],
+) -> anyhow::Result<()> {
+    let archive = Archive::fetch(ctx, guild, name, channels)
+        .await
+        .context("Could not fetch the messages to archive")?;
+    let path = archive
+        .save()
+        .context("Could not save the archive of the messages")?;
+    crate::log!(
+        "Archived {} messages of {} in {}",
+        archive.message_count(),
+        name,
+        path.display()
+    );
+    Ok(())
+}
+
+/// The overwrites of a course category: only the course role and the bot can see it.
+pub(crate) fn course_overwrites(
+    ctx: &Context,
+    guild: GuildId,
+    role: RoleId,
+) -> Vec<PermissionOverwrite> {
+    vec![
+        PermissionOverwrite {
+            allow: Permissions::empty(),
+            deny: Permissions::VIEW_CHANNEL,
+            kind: Role(guild.get().into()),
+        },
+        PermissionOverwrite {
+            allow: Permissions::VIEW_CHANNEL,
+            deny: Permissions::empty(),
+            kind: Role(role),
         },
-        s
-    )
-    .trim()
-    .to_string()
+        PermissionOverwrite {
+            allow: Permissions::VIEW_CHANNEL,
+            deny: Permissions::empty(),
+            kind: Member(ctx.cache.current_user().id),
+        },
+    ]
+}
+
+/// A role of the guild. Dry runs only look in the cache, so they make no requests.
+async fn fetch_role(
+    ctx: &Context,
+    guild: GuildId,
+    role: RoleId,
+    journal: &Journal,
+) -> Result<SerenityRole, Failed> {
+    let role = if journal.is_dry_run() {
+        ctx.cache
+            .guild(guild)
+            .and_then(|g| g.roles.get(&role).cloned())
+    } else {
+        guild
+            .roles(&ctx.http)
+            .await
+            .map_err(failed("fetch the roles"))?
+            .remove(&role)
+    };
+    role.ok_or_else(|| Failed::new("find the course role", anyhow!("No such role")))
+}
+
+/// A channel of the guild, or nothing if it's some other kind of channel. Dry runs only look in
+/// the cache, so they make no requests.
+async fn fetch_channel(
+    ctx: &Context,
+    channel: ChannelId,
+    journal: &Journal,
+) -> Result<Option<GuildChannel>, Failed> {
+    if journal.is_dry_run() {
+        return match ctx.cache.channel(channel) {
+            Some(c) => Ok(Some(GuildChannel::clone(&c))),
+            None => Err(Failed::new(
+                "find a channel",
+                anyhow!("Channel {} is not in the cache", channel),
+            )),
+        };
+    }
+    match channel
+        .to_channel(&ctx.http)
+        .await
+        .map_err(failed("fetch a channel"))?
+    {
+        SerenityChannel::Guild(c) => Ok(Some(c)),
+        _ => Ok(None),
+    }
 }
 
 impl MiEI {
-    fn write_courses(&self) -> Result<(), io::Error> {
-        serde_json::to_writer(File::create(COURSES)?, &self)?;
-        Ok(())
+    fn write_courses(&self) -> anyhow::Result<()> {
+        if self.plan.is_some() {
+            return Ok(());
+        }
+        self.write()
     }
 
-    pub fn role_by_name<'a>(&'a self, role_name: &'a str) -> Option<RoleId> {
-        let role_name_ = role_name.to_uppercase();
-        self.courses
-            .values()
-            .filter_map(move |x| x.get_role(&role_name_))
+    /// A copy of the courses whose changes only describe what they would do, without touching
+    /// the guild or saving anything. The steps are collected by [`MiEI::take_plan`].
+    pub fn dry_run(&self) -> MiEI {
+        MiEI {
+            plan: Some(Vec::new()),
+            ..self.clone()
+        }
+    }
+
+    pub fn take_plan(&mut self) -> Vec<String> {
+        self.plan.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    fn journal(&self) -> Journal {
+        if self.plan.is_some() {
+            Journal::dry_run()
+        } else {
+            Journal::default()
+        }
+    }
+
+    /// Keeps the steps of a dry run once an operation is done.
+    fn note(&mut self, mut journal: Journal) {
+        if let Some(plan) = &mut self.plan {
+            plan.extend(journal.take_plan());
+        }
+    }
+
+    /// The role of the course with this name or alias.
+    pub fn role_by_name(&self, role_name: &str) -> Option<RoleId> {
+        self.resolve(role_name)
+            .and_then(|name| self.course(name))
             .map(|x| x.role)
-            .next()
+    }
+
+    /// The name of the course that has this name or alias.
+    pub fn resolve(&self, name: &str) -> Option<&str> {
+        let upper = name.to_uppercase();
+        self.courses()
+            .find(|(_, _, n, c)| *n == upper || c.aliases.contains(&upper))
+            .map(|(_, _, n, _)| n)
+    }
+
+    /// The courses whose name or an alias looks like `name`, most similar first.
+    pub fn similar_courses(&self, name: &str) -> Vec<&str> {
+        let upper = name.to_uppercase();
+        let mut similar = self
+            .courses()
+            .filter_map(|(_, _, n, c)| {
+                let score = std::iter::once(n)
+                    .chain(c.aliases.iter().map(String::as_str))
+                    .map(|key| similarity(&upper, key))
+                    .fold(0.0, f64::max);
+                (score >= SIMILARITY).then_some((score, n))
+            })
+            .collect::<Vec<_>>();
+        similar.sort_by(|a, b| b.0.total_cmp(&a.0).then(a.1.cmp(b.1)));
+        similar.into_iter().map(|(_, n)| n).collect()
+    }
+
+    /// Whether a name is already the name or an alias of a course, ignoring case. The aliases
+    /// of deprecated courses count too, since restoring a course brings them back.
+    pub fn name_taken(&self, name: &str) -> bool {
+        let upper = name.to_uppercase();
+        self.resolve(&upper).is_some()
+            || self
+                .deprecated_courses
+                .iter()
+                .any(|c| c.aliases.contains(&upper))
+    }
+
+    pub fn add_alias(&mut self, course: &str, alias: &str) -> anyhow::Result<()> {
+        let alias = alias.to_uppercase();
+        if alias.contains(['*', ',']) || alias.chars().all(|c| c.is_ascii_digit()) {
+            return Err(anyhow!("Invalid alias: {}", alias));
+        }
+        if let Some(other) = self.resolve(&alias) {
+            return Err(anyhow!("{} is already {}", alias, other));
+        }
+        if self.name_taken(&alias) {
+            return Err(anyhow!("{} is already a deprecated course", alias));
+        }
+        let course = self
+            .course_mut(course)
+            .ok_or_else(|| anyhow!("No such course: {}", course))?;
+        course.aliases.push(alias);
+        self.write_courses()
+    }
+
+    /// Removes an alias, returning the course that had it.
+    pub fn remove_alias(&mut self, alias: &str) -> anyhow::Result<String> {
+        let alias = alias.to_uppercase();
+        let name = self
+            .courses()
+            .find(|(_, _, _, c)| c.aliases.contains(&alias))
+            .map(|(_, _, n, _)| n.to_string())
+            .ok_or_else(|| anyhow!("No such alias: {}", alias))?;
+        if let Some(course) = self.course_mut(&name) {
+            course.aliases.retain(|a| *a != alias);
+        }
+        self.write_courses()?;
+        Ok(name)
     }
 
     pub fn wildcard_roles<'s>(
@@ -111,17 +453,6 @@ impl MiEI {
             .and_then(|y| y.roles_by_semester(semester))
     }
 
-    fn role_color(year: &str) -> u64 {
-        match year {
-            "1" => 0x843da4,
-            "2" => 0x2ecc71,
-            "3" => 0x498db,
-            "4" => 0xf1c40f,
-            "5" => 0x1e1e1e,
-            _ => 0xffffff,
-        }
-    }
-
     pub async fn create_role<'a>(
         &mut self,
         ctx: &Context,
@@ -129,58 +460,34 @@ impl MiEI {
         semester: &str,
         course: &'a str,
         guild: GuildId,
-    ) -> Result<Option<&'a str>, SendSyncError> {
+    ) -> Result<Option<&'a str>, CourseError> {
         let upper_course = course.to_uppercase();
         if self.role_exists(&upper_course) {
-            Ok(None)
-        } else {
-            let role = guild
-                .create_role(
-                    &ctx.http,
-                    EditRole::new()
-                        .hoist(false)
-                        .mentionable(true)
-                        .name(&upper_course)
-                        .colour(MiEI::role_color(year)),
-                )
-                .await
-                .unwrap();
-            let perms = vec![
-                PermissionOverwrite {
-                    allow: Permissions::empty(),
-                    deny: Permissions::VIEW_CHANNEL,
-                    kind: Role(guild.get().into()),
-                },
-                PermissionOverwrite {
-                    allow: Permissions::VIEW_CHANNEL,
-                    deny: Permissions::empty(),
-                    kind: Role(role.id),
-                },
-                PermissionOverwrite {
-                    allow: Permissions::VIEW_CHANNEL,
-                    deny: Permissions::empty(),
-                    kind: Member(ctx.http.get_current_user().await?.id),
-                },
-            ];
-            let category = guild
-                .create_channel(
-                    &ctx,
-                    CreateChannel::new(add_category_emoji(year, &upper_course))
-                        .kind(ChannelType::Category)
-                        .permissions(perms),
-                )
-                .await
-                .unwrap();
-            let (duvidas_id, anexos_id) =
-                create_channels(ctx, guild, &upper_course, category.id).await?;
-            let courses = Course {
-                role: role.id,
-                channels: vec![category.id, anexos_id, duvidas_id],
-            };
-            self.add_role(&upper_course, courses, semester, year);
-            self.write_courses()?;
-            Ok(Some(course))
+            return Ok(None);
         }
+        if self.name_taken(&upper_course) {
+            return Err(CourseError::AlreadyExists(upper_course));
+        }
+        let style = course_style(ctx, guild).await;
+        let mut journal = self.journal();
+        let failure =
+            match create_course(ctx, guild, &style, year, &upper_course, &mut journal).await {
+                Ok(c) => {
+                    self.add_role(&upper_course, c, semester, year);
+                    match self.write_courses() {
+                        Ok(()) => {
+                            self.note(journal);
+                            return Ok(Some(course));
+                        }
+                        Err(e) => {
+                            self.take_course(&upper_course);
+                            Failed::new("save the courses", e)
+                        }
+                    }
+                }
+                Err(e) => e,
+            };
+        Err(journal.undo(ctx, guild, failure).await)
     }
 
     fn add_role(&mut self, role_name: &str, course: Course, semester: &str, year: &str) {
@@ -192,23 +499,51 @@ impl MiEI {
             .add_role(role_name, course, semester);
     }
 
+    /// Deletes the channels and the role of a course. Its messages must have been archived with
+    /// [`archive_course`] first, from the `archived` channels, which must still be all of the
+    /// channels of the course. Dry runs don't need an archive.
     pub async fn remove_role<'a>(
         &mut self,
         role_name: &'a str,
+        archived: &[ChannelId],
         ctx: &Context,
         guild: GuildId,
-    ) -> serenity::Result<&'a str> {
-        if let Some(x) = self
-            .courses
-            .values_mut()
-            .find_map(|x| x.pop_role(role_name))
-        {
-            x.remove(ctx, guild).await?;
-            self.write_courses()?;
-            Ok(role_name)
-        } else {
-            Err(serenity::Error::Other("No such role"))
+    ) -> anyhow::Result<&'a str> {
+        let upper_course = role_name.to_uppercase();
+        let Some((year, semester)) = self.get_year_semester_names(&upper_course) else {
+            return Err(anyhow!("No such role"));
+        };
+        let course = self
+            .take_course(&upper_course)
+            .ok_or_else(|| anyhow!("No such role"))?;
+        if self.plan.is_none() && course.channels != archived {
+            self.add_role(&upper_course, course, &semester, &year);
+            return Err(anyhow!(
+                "The channels of {} changed while they were archived, try again",
+                upper_course
+            ));
+        }
+        let mut journal = self.journal();
+        let leftovers = match course.remove(ctx, guild, &mut journal).await {
+            Ok(leftovers) => leftovers,
+            Err(e) => {
+                self.add_role(&upper_course, course, &semester, &year);
+                return Err(e);
+            }
+        };
+        self.write_courses()?;
+        if self.plan.is_none() {
+            update_course_templates(ctx, guild, &upper_course, None).await;
+        }
+        self.note(journal);
+        if !leftovers.is_empty() {
+            return Err(anyhow!(
+                "{} was removed, but these could not be deleted: {}",
+                upper_course,
+                leftovers.join(", ")
+            ));
         }
+        Ok(role_name)
     }
 
     pub async fn move_course(
@@ -219,30 +554,54 @@ impl MiEI {
         new_name: Option<&str>,
         ctx: &Context,
         guild: GuildId,
-    ) -> anyhow::Result<String> {
+    ) -> Result<String, CourseError> {
+        let upper_course = course.to_uppercase();
         let upper_new_name = new_name.map(|n| n.to_uppercase());
-        if let Some(n) = upper_new_name.as_ref().filter(|r| self.role_exists(r)) {
-            Err(anyhow!("Course already exists: {}", n))
-        } else if let Some(old_course) = self.courses.values_mut().find_map(|x| x.pop_role(course))
-        {
-            guild
-                .edit_role(
-                    &ctx.http,
-                    old_course.role,
-                    EditRole::new().colour(MiEI::role_color(new_year)),
-                )
-                .await?;
-            if let Some(n) = upper_new_name {
-                old_course.rename(&n, new_year, ctx, guild).await?;
-                self.add_role(&n, old_course, new_semester, new_year);
-            } else {
-                self.add_role(&course.to_uppercase(), old_course, new_semester, new_year);
-            }
-            self.write_courses()?;
-            Ok(new_name.unwrap_or(course).to_string())
-        } else {
-            Err(anyhow!("No such course: {}", course))
+        if let Some(n) = upper_new_name.as_ref().filter(|r| self.name_taken(r)) {
+            return Err(CourseError::AlreadyExists(n.clone()));
         }
+        let Some((year, semester)) = self.get_year_semester_names(&upper_course) else {
+            return Err(CourseError::NotFound(course.to_string()));
+        };
+        let old_course = self
+            .take_course(&upper_course)
+            .ok_or_else(|| CourseError::NotFound(course.to_string()))?;
+        let style = course_style(ctx, guild).await;
+        let mut journal = self.journal();
+        let moved = old_course
+            .move_to(
+                &style,
+                new_year,
+                upper_new_name
+                    .as_deref()
+                    .map(|n| (upper_course.as_str(), n)),
+                ctx,
+                guild,
+                &mut journal,
+            )
+            .await;
+        let name = upper_new_name.unwrap_or_else(|| upper_course.clone());
+        let (old_course, failure) = match moved {
+            Ok(()) => {
+                self.add_role(&name, old_course, new_semester, new_year);
+                match self.write_courses() {
+                    Ok(()) => {
+                        if self.plan.is_none() && name != upper_course {
+                            update_course_templates(ctx, guild, &upper_course, Some(&name)).await;
+                        }
+                        self.note(journal);
+                        return Ok(new_name.unwrap_or(course).to_string());
+                    }
+                    Err(e) => {
+                        let old_course = self.take_course(&name).expect("course was just added");
+                        (old_course, Failed::new("save the courses", e))
+                    }
+                }
+            }
+            Err(e) => (old_course, e),
+        };
+        self.add_role(&upper_course, old_course, &semester, &year);
+        Err(journal.undo(ctx, guild, failure).await)
     }
 
     pub async fn rename_course(
@@ -251,12 +610,12 @@ impl MiEI {
         new_name: &str,
         ctx: &Context,
         guild: GuildId,
-    ) -> anyhow::Result<String> {
+    ) -> Result<String, CourseError> {
         if let Some((year, semester)) = self.get_year_semester_names(course) {
             self.move_course(course, &year, &semester, Some(new_name), ctx, guild)
                 .await
         } else {
-            Err(anyhow!("No such course: {}", course))
+            Err(CourseError::NotFound(course.to_string()))
         }
     }
 
@@ -265,18 +624,266 @@ impl MiEI {
         course: &str,
         ctx: &Context,
         guild: GuildId,
-    ) -> anyhow::Result<String> {
-        if let Some(mut c) = self.courses.values_mut().find_map(|x| x.pop_role(course)) {
-            c.deprecate(ctx, guild).await?;
-            self.deprecated_courses.push(c);
-            self.write_courses()?;
-            Ok(course.to_string())
-        } else {
-            Err(anyhow!("No such course: {}", course))
+    ) -> Result<String, CourseError> {
+        let Some((year, semester)) = self.get_year_semester_names(course) else {
+            return Err(CourseError::NotFound(course.to_string()));
+        };
+        let c = self
+            .take_course(course)
+            .ok_or_else(|| CourseError::NotFound(course.to_string()))?;
+        let style = course_style(ctx, guild).await;
+        let mut journal = self.journal();
+        let failure = match c.deprecate(&style, ctx, guild, &mut journal).await {
+            Ok(deprecation) => {
+                self.deprecated_courses.push(deprecation.course.clone());
+                match self.write_courses() {
+                    Ok(()) => {
+                        if !journal.is_dry_run() {
+                            deprecation.finish(ctx, guild).await;
+                        }
+                        self.note(journal);
+                        return Ok(course.to_string());
+                    }
+                    Err(e) => {
+                        self.deprecated_courses.pop();
+                        Failed::new("save the courses", e)
+                    }
+                }
+            }
+            Err(e) => e,
+        };
+        self.add_role(&course.to_uppercase(), c, &semester, &year);
+        Err(journal.undo(ctx, guild, failure).await)
+    }
+
+    /// Brings back the most recently deprecated course with the given name.
+    pub async fn restore_course(
+        &mut self,
+        course: &str,
+        year: &str,
+        semester: &str,
+        ctx: &Context,
+        guild: GuildId,
+    ) -> Result<String, CourseError> {
+        let upper_course = course.to_uppercase();
+        if self.name_taken(&upper_course) {
+            return Err(CourseError::AlreadyExists(upper_course));
+        }
+        let roles = guild
+            .roles(&ctx.http)
+            .await
+            .map_err(|e| CourseError::Failed {
+                step: "fetch the roles",
+                source: e.into(),
+                not_undone: Vec::new(),
+            })?;
+        let index = self
+            .deprecated_courses
+            .iter()
+            .rposition(|c| roles.get(&c.role).is_some_and(|r| r.name == upper_course))
+            .ok_or_else(|| CourseError::NotFound(course.to_string()))?;
+        let deprecated = self.deprecated_courses.remove(index);
+        let style = course_style(ctx, guild).await;
+        let mut journal = self.journal();
+        let failure = match deprecated
+            .restore(
+                &style,
+                &roles[&deprecated.role],
+                year,
+                ctx,
+                guild,
+                &mut journal,
+            )
+            .await
+        {
+            Ok(c) => {
+                self.add_role(&upper_course, c, semester, year);
+                match self.write_courses() {
+                    Ok(()) => {
+                        self.note(journal);
+                        return Ok(upper_course);
+                    }
+                    Err(e) => {
+                        self.take_course(&upper_course);
+                        Failed::new("save the courses", e)
+                    }
+                }
+            }
+            Err(e) => e,
+        };
+        self.deprecated_courses.insert(index, deprecated);
+        Err(journal.undo(ctx, guild, failure).await)
+    }
+
+    /// Recolours the roles and renames the categories of the courses of a year to match the
+    /// style, returning what failed.
+    pub async fn restyle_year(
+        &self,
+        style: &CourseStyle,
+        year: &str,
+        ctx: &Context,
+        guild: GuildId,
+    ) -> Vec<String> {
+        let mut failed = Vec::new();
+        for (_, _, name, course) in self.courses().filter(|(y, ..)| *y == year) {
+            if let Err(e) = guild
+                .edit_role(
+                    &ctx.http,
+                    course.role,
+                    EditRole::new().colour(style.role_colour(year)),
+                )
+                .await
+            {
+                failed.push(format!("{}: {}", name, e));
+            }
+            if let Some(category) = course.channels.first()
+                && let Err(e) = category
+                    .edit(
+                        &ctx.http,
+                        EditChannel::new().name(style.category_name(year, name)),
+                    )
+                    .await
+            {
+                failed.push(format!("{}: {}", name, e));
+            }
+        }
+        failed
+    }
+
+    fn take_course(&mut self, role_name: &str) -> Option<Course> {
+        self.courses
+            .values_mut()
+            .find_map(|x| x.pop_role(role_name))
+    }
+
+    pub(crate) fn course(&self, role_name: &str) -> Option<&Course> {
+        let upper_role_name = role_name.to_uppercase();
+        self.courses
+            .values()
+            .find_map(|x| x.get_role(&upper_role_name))
+    }
+
+    pub(crate) fn course_mut(&mut self, role_name: &str) -> Option<&mut Course> {
+        let upper_role_name = role_name.to_uppercase();
+        self.courses
+            .values_mut()
+            .flat_map(|y| y.courses.values_mut())
+            .find_map(|s| s.courses.get_mut(&upper_role_name))
+    }
+
+    pub fn info(&self, role_name: &str) -> Option<&CourseInfo> {
+        self.course(self.resolve(role_name)?).map(|c| &c.info)
+    }
+
+    pub fn aliases(&self, role_name: &str) -> &[String] {
+        self.course(role_name).map_or(&[], |c| &c.aliases)
+    }
+
+    pub fn restrictions(&self, role_name: &str) -> Option<&Restrictions> {
+        self.resolve(role_name)
+            .and_then(|name| self.course(name))
+            .map(|c| &c.restrictions)
+    }
+
+    pub fn edit_restrictions(
+        &mut self,
+        role_name: &str,
+        edit: impl FnOnce(&mut Restrictions),
+    ) -> anyhow::Result<()> {
+        let name = self.resolve(role_name).unwrap_or(role_name).to_string();
+        let course = self
+            .course_mut(&name)
+            .ok_or_else(|| anyhow!("No such course: {}", role_name))?;
+        edit(&mut course.restrictions);
+        self.write_courses()
+    }
+
+    /// How many of the courses have one of these roles.
+    pub fn course_count(&self, roles: &[RoleId]) -> usize {
+        self.courses()
+            .filter(|(_, _, _, c)| roles.contains(&c.role))
+            .count()
+    }
+
+    pub fn expiry(&self) -> &[Expiry] {
+        &self.expiry
+    }
+
+    /// Makes the course roles of a year, or of one of its semesters, expire every year on
+    /// `date`, written as MM-DD, or stop expiring when there is no date. A new date only counts
+    /// from `today` on, so roles never expire just because a date was set.
+    pub fn set_expiry(
+        &mut self,
+        year: &str,
+        semester: Option<&str>,
+        date: Option<&str>,
+        today: NaiveDate,
+    ) -> anyhow::Result<()> {
+        let exists = self
+            .courses
+            .get(year)
+            .is_some_and(|y| semester.is_none_or(|s| y.courses.contains_key(s)));
+        if !exists {
+            return Err(anyhow!(
+                "No such year or semester: {}ano{}",
+                year,
+                semester.map(|s| format!("{}sem", s)).unwrap_or_default()
+            ));
+        }
+        self.expiry
+            .retain(|e| e.year != year || e.semester.as_deref() != semester);
+        if let Some(date) = date {
+            let expiry = Expiry {
+                year: year.to_string(),
+                semester: semester.map(str::to_string),
+                date: date.to_string(),
+                last_run: Some(today.to_string()),
+            };
+            expiry
+                .month_day()
+                .ok_or_else(|| anyhow!("Invalid date, expected MM-DD: {}", date))?;
+            self.expiry.push(expiry);
         }
+        self.write_courses()
     }
 
-    fn get_year_semester_names(&self, role_name: &str) -> Option<(String, String)> {
+    /// Records that the roles of a year or semester expired on `today`.
+    pub fn expired(
+        &mut self,
+        year: &str,
+        semester: Option<&str>,
+        today: NaiveDate,
+    ) -> anyhow::Result<()> {
+        for e in &mut self.expiry {
+            if e.year == year && e.semester.as_deref() == semester {
+                e.last_run = Some(today.to_string());
+            }
+        }
+        self.write_courses()
+    }
+
+    /// The roles of the courses of a year, or of one of its semesters.
+    pub fn roles_of(&self, year: &str, semester: Option<&str>) -> Vec<RoleId> {
+        self.courses()
+            .filter(|(y, s, _, _)| *y == year && semester.is_none_or(|sem| sem == *s))
+            .map(|(_, _, _, c)| c.role)
+            .collect()
+    }
+
+    pub fn edit_info(
+        &mut self,
+        role_name: &str,
+        edit: impl FnOnce(&mut CourseInfo),
+    ) -> anyhow::Result<()> {
+        let name = self.resolve(role_name).unwrap_or(role_name).to_string();
+        let course = self
+            .course_mut(&name)
+            .ok_or_else(|| anyhow!("No such course: {}", role_name))?;
+        edit(&mut course.info);
+        self.write_courses()
+    }
+
+    pub(crate) fn get_year_semester_names(&self, role_name: &str) -> Option<(String, String)> {
         let upper_role_name = role_name.to_uppercase();
         self.courses.iter().find_map(|(key, x)| {
             x.get_semester_name(&upper_role_name)
@@ -284,7 +891,7 @@ impl MiEI {
         })
     }
 
-    fn role_exists(&self, role_name: &str) -> bool {
+    pub(crate) fn role_exists(&self, role_name: &str) -> bool {
         self.courses.values().any(|x| x.role_exists(role_name))
     }
 
@@ -300,34 +907,144 @@ impl MiEI {
         })
     }
 
+    /// Every year and semester, including the ones with no courses.
+    pub(crate) fn semesters(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.courses.iter().flat_map(|(year, sems)| {
+            sems.courses
+                .keys()
+                .map(move |semester| (year.as_str(), semester.as_str()))
+        })
+    }
+
+    /// Every course that isn't deprecated, along with its year and semester.
+    pub(crate) fn courses(&self) -> impl Iterator<Item = (&str, &str, &str, &Course)> {
+        self.courses.iter().flat_map(|(year, sems)| {
+            sems.courses.iter().flat_map(move |(semester, courses)| {
+                courses
+                    .courses
+                    .iter()
+                    .map(move |(name, c)| (year.as_str(), semester.as_str(), name.as_str(), c))
+            })
+        })
+    }
+
+    pub(crate) fn deprecated_courses(&self) -> &[Course] {
+        &self.deprecated_courses
+    }
+
+    pub(crate) fn deprecated_courses_mut(&mut self) -> &mut [Course] {
+        &mut self.deprecated_courses
+    }
+
+    pub(crate) fn from_parts(
+        guild: GuildId,
+        semesters: impl IntoIterator<Item = (String, String)>,
+        courses: impl IntoIterator<Item = (String, String, String, Course)>,
+        deprecated_courses: Vec<Course>,
+        expiry: Vec<Expiry>,
+    ) -> Self {
+        let mut miei = MiEI {
+            guild,
+            courses: HashMap::new(),
+            deprecated_courses,
+            expiry,
+            plan: None,
+        };
+        for (year, semester) in semesters {
+            miei.courses
+                .entry(year)
+                .or_default()
+                .courses
+                .entry(semester)
+                .or_default();
+        }
+        for (year, semester, name, course) in courses {
+            miei.add_role(&name, course, &semester, &year);
+        }
+        miei
+    }
+
     pub async fn add_channel_to_course(
         &mut self,
         ctx: &Context,
         guild: GuildId,
         course: &str,
         new_channel_names: &str,
-    ) -> anyhow::Result<()> {
-        let course = course.to_uppercase();
-        let course = self.courses.values_mut().find_map(|year| {
-            year.courses
-                .values_mut()
-                .find_map(|semester| semester.courses.get_mut(&course))
-        });
-        if let Some(c) = course {
-            let cat = c.channels[0];
-            let (duvidas_id, anexos_id) =
-                create_channels(ctx, guild, new_channel_names, cat).await?;
-            c.channels.extend_from_slice(&[duvidas_id, anexos_id]);
-            self.write_courses()?;
-            Ok(())
-        } else {
-            Err(anyhow!("Can't find that course"))
-        }
+    ) -> Result<(), CourseError> {
+        let (Some((year, _)), Some(c)) =
+            (self.get_year_semester_names(course), self.course(course))
+        else {
+            return Err(CourseError::NotFound(course.to_string()));
+        };
+        // The category is the first channel of a course.
+        let cat = *c.channels.first().ok_or_else(|| CourseError::Failed {
+            step: "find the course category",
+            source: anyhow!("{} has no channels", course),
+            not_undone: Vec::new(),
+        })?;
+        let role = c.role;
+        let templates = course_style(ctx, guild)
+            .await
+            .templates_for(&year, &course.to_uppercase());
+        let mut journal = self.journal();
+        let created = create_channels(
+            ctx,
+            guild,
+            &templates,
+            new_channel_names,
+            role,
+            cat,
+            &mut journal,
+        )
+        .await;
+        let failure = match created {
+            Ok(new_channels) => {
+                let count = new_channels.len();
+                if let Some(c) = self.course_mut(course) {
+                    c.channels.extend(new_channels);
+                }
+                match self.write_courses() {
+                    Ok(()) => {
+                        self.note(journal);
+                        return Ok(());
+                    }
+                    Err(e) => {
+                        if let Some(c) = self.course_mut(course) {
+                            c.channels.truncate(c.channels.len() - count);
+                        }
+                        Failed::new("save the courses", e)
+                    }
+                }
+            }
+            Err(e) => e,
+        };
+        Err(journal.undo(ctx, guild, failure).await)
+    }
+}
+
+impl GuildData for MiEI {
+    const FILE: &'static str = "courses.json";
+    const MIGRATIONS: &'static [migrations::Migration] = migrations::COURSES;
+
+    fn load(storage: &dyn Storage, guild: GuildId) -> anyhow::Result<Option<Self>> {
+        storage.load_courses(guild)
+    }
+
+    fn save(&self, storage: &dyn Storage) -> anyhow::Result<()> {
+        storage.save_courses(self)
+    }
+
+    fn guild(&self) -> GuildId {
+        self.guild
+    }
+
+    fn set_guild(&mut self, guild: GuildId) {
+        self.guild = guild;
     }
 }
 
 impl TypeMapKey for MiEI {
-    type Value = Arc<RwLock<MiEI>>;
+    type Value = GuildMap<MiEI>;
 }
 
 #[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq, Eq)]
@@ -396,122 +1113,526 @@ struct Semester {
 }
 
 #[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq, Eq)]
-struct Course {
-    role: RoleId,
-    channels: Vec<ChannelId>,
+pub(crate) struct Course {
+    pub(crate) role: RoleId,
+    pub(crate) channels: Vec<ChannelId>,
+    /// Other names students can use for the course, in uppercase.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) aliases: Vec<String>,
+    #[serde(default, skip_serializing_if = "Restrictions::is_empty")]
+    pub(crate) restrictions: Restrictions,
+    #[serde(flatten)]
+    pub(crate) info: CourseInfo,
+}
+
+/// Who may join a course with `$study`, which by default is everyone.
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq, Eq)]
+#[serde(default)]
+pub struct Restrictions {
+    /// A role students need to have to join.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub required_role: Option<RoleId>,
+    /// Students already in this many courses can't join.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_courses: Option<u32>,
+    /// Students can only join by having an admin approve it.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub approval: bool,
+}
+
+/// Why a student can't join a course.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Refusal {
+    MissingRole(RoleId),
+    TooManyCourses(u32),
+    NeedsApproval,
+}
+
+impl Restrictions {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Whether a student with these roles, who is in `courses` courses, may join.
+    pub fn check(&self, roles: &[RoleId], courses: usize) -> Result<(), Refusal> {
+        if let Some(role) = self.required_role
+            && !roles.contains(&role)
+        {
+            return Err(Refusal::MissingRole(role));
+        }
+        if let Some(max) = self.max_courses
+            && courses >= max as usize
+        {
+            return Err(Refusal::TooManyCourses(max));
+        }
+        if self.approval {
+            return Err(Refusal::NeedsApproval);
+        }
+        Ok(())
+    }
+}
+
+/// When the course roles of a year, or of one of its semesters, are taken from everyone.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Expiry {
+    pub year: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub semester: Option<String>,
+    /// The day of every year the roles expire, as MM-DD.
+    pub date: String,
+    /// The last day the roles expired or the policy was set, as YYYY-MM-DD.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_run: Option<String>,
+}
+
+impl Expiry {
+    /// How long after its date a policy that never ran still expires the roles.
+    pub const CATCH_UP_DAYS: i64 = 7;
+
+    fn month_day(&self) -> Option<(u32, u32)> {
+        // A leap year, so that 02-29 is valid.
+        let date = NaiveDate::parse_from_str(&format!("2000-{}", self.date), "%Y-%m-%d").ok()?;
+        Some((date.month(), date.day()))
+    }
+
+    /// Whether the roles have to expire, because the date went by since they last did. Without
+    /// a record of that, a date up to [`Expiry::CATCH_UP_DAYS`] ago still counts, in case the
+    /// bot was down on the day.
+    pub fn is_due(&self, today: NaiveDate) -> bool {
+        let Some((month, day)) = self.month_day() else {
+            return false;
+        };
+        // 02-29 falls on 02-28 in the years it doesn't exist.
+        let latest = [today.year(), today.year() - 1]
+            .into_iter()
+            .filter_map(|y| {
+                NaiveDate::from_ymd_opt(y, month, day)
+                    .or_else(|| NaiveDate::from_ymd_opt(y, month, day - 1))
+            })
+            .find(|d| *d <= today);
+        let last_run = self
+            .last_run
+            .as_deref()
+            .and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok());
+        match (latest, last_run) {
+            (Some(latest), Some(last_run)) => last_run < latest,
+            (Some(latest), None) => (today - latest).num_days() <= Self::CATCH_UP_DAYS,
+            (None, _) => false,
+        }
+    }
+
+    /// The year and semester, as students write them.
+    pub fn describe(&self) -> String {
+        match &self.semester {
+            Some(s) => format!("{}ano{}semestre", self.year, s),
+            None => format!("{}ano", self.year),
+        }
+    }
+}
+
+/// What students may want to know about a course, all of it optional.
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq, Eq)]
+#[serde(default)]
+pub struct CourseInfo {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub full_name: Option<String>,
+    /// The official code of the course.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ects: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub instructors: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub syllabus_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub material_url: Option<String>,
 }
 
 impl Course {
-    async fn remove(&self, ctx: &Context, guild: GuildId) -> serenity::Result<()> {
+    /// Deletes the channels and the role, skipping the ones already deleted by hand.
+    ///
+    /// Fails, with nothing deleted, if the first of them can't be deleted. Once something was
+    /// deleted the rest is still tried, and what couldn't be deleted is returned.
+    async fn remove(
+        &self,
+        ctx: &Context,
+        guild: GuildId,
+        journal: &mut Journal,
+    ) -> anyhow::Result<Vec<String>> {
+        if journal.is_dry_run() {
+            journal.plan(format!(
+                "+ archive of the messages in {}",
+                archive::archive_dir(guild).display()
+            ));
+            // Only the cache is looked at, dry runs make no requests.
+            for channel in &self.channels {
+                let name = ctx
+                    .cache
+                    .channel(channel)
+                    .map_or_else(|| channel.to_string(), |c| c.name.clone());
+                journal.plan(format!("- channel {}", name));
+            }
+            let role = ctx
+                .cache
+                .guild(guild)
+                .and_then(|g| g.roles.get(&self.role).map(|r| r.name.clone()));
+            journal.plan(format!(
+                "- role {}",
+                role.unwrap_or_else(|| self.role.to_string())
+            ));
+            return Ok(Vec::new());
+        }
+        let mut deleted = false;
+        let mut leftovers = Vec::new();
         for channel in &self.channels {
-            channel.delete(&ctx.http).await?;
+            match channel.delete(&ctx.http).await {
+                Ok(_) => deleted = true,
+                Err(e) if is_not_found(&e) => {}
+                Err(e) if !deleted => return Err(e.into()),
+                Err(e) => leftovers.push(format!("channel {} ({})", channel, e)),
+            }
+        }
+        match guild.delete_role(&ctx.http, self.role).await {
+            Ok(()) => {}
+            Err(e) if is_not_found(&e) => {}
+            Err(e) if !deleted => return Err(e.into()),
+            Err(e) => leftovers.push(format!("role {} ({})", self.role, e)),
+        }
+        Ok(leftovers)
+    }
+
+    /// Recolours the role for the new year and renames everything if there's a new name, given
+    /// along with the old one.
+    async fn move_to(
+        &self,
+        style: &CourseStyle,
+        new_year: &str,
+        rename: Option<(&str, &str)>,
+        ctx: &Context,
+        guild: GuildId,
+        journal: &mut Journal,
+    ) -> Result<(), Failed> {
+        let role = fetch_role(ctx, guild, self.role, journal).await?;
+        journal.plan(format!(
+            "~ role {}: colour #{:06x} -> #{:06x}",
+            role.name,
+            role.colour.0,
+            style.role_colour(new_year)
+        ));
+        if !journal.is_dry_run() {
+            guild
+                .edit_role(
+                    &ctx.http,
+                    self.role,
+                    EditRole::new().colour(style.role_colour(new_year)),
+                )
+                .await
+                .map_err(failed("change the role colour"))?;
+            journal.record(Change::EditedRole {
+                role: role.id,
+                name: role.name.clone(),
+                colour: role.colour,
+                mentionable: role.mentionable,
+            });
+        }
+        if let Some((old_name, n)) = rename {
+            self.rename(style, old_name, n, new_year, ctx, journal)
+                .await?;
+            journal.plan(format!("~ role {} -> {}", role.name, n));
+            if !journal.is_dry_run() {
+                guild
+                    .edit_role(&ctx.http, self.role, EditRole::new().name(n))
+                    .await
+                    .map_err(failed("rename the role"))?;
+            }
         }
-        guild.delete_role(&ctx.http, self.role).await?;
         Ok(())
     }
 
+    /// Renames the channels of the course.
     async fn rename(
         &self,
+        style: &CourseStyle,
+        old_name: &str,
         new_name: &str,
         year: &str,
         ctx: &Context,
-        guild: GuildId,
-    ) -> serenity::Result<()> {
+        journal: &mut Journal,
+    ) -> Result<(), Failed> {
         for channel in &self.channels {
-            match channel.to_channel(&ctx.http).await? {
-                SerenityChannel::Guild(mut channel) => match channel.kind {
-                    ChannelType::Text => {
-                        let prefix_index = channel.name.find('-').unwrap_or(channel.name.len());
-                        let prefix = &channel.name[..prefix_index].to_string();
-                        channel
-                            .edit(
-                                &ctx.http,
-                                EditChannel::new().name(format!("{}-{}", prefix, new_name)),
-                            )
-                            .await?;
-                    }
+            let new_channel_name = match fetch_channel(ctx, *channel, journal).await? {
+                Some(channel) => match channel.kind {
+                    ChannelType::Text | ChannelType::Voice | ChannelType::Forum => style
+                        .renamed_channel(&channel.name, old_name, new_name)
+                        .map(|name| (channel, name)),
                     ChannelType::Category => {
-                        channel
-                            .edit(
-                                &ctx.http,
-                                EditChannel::new().name(add_category_emoji(year, new_name)),
-                            )
-                            .await?;
+                        let name = style.category_name(year, new_name);
+                        Some((channel, name))
                     }
-                    _ => {}
+                    _ => None,
                 },
-                SerenityChannel::Private(_) => {}
-                _ => {}
+                None => None,
+            };
+            if let Some((mut channel, name)) = new_channel_name {
+                journal.plan(format!("~ channel {} -> {}", channel.name, name));
+                if journal.is_dry_run() {
+                    continue;
+                }
+                let old_name = channel.name.clone();
+                channel
+                    .edit(&ctx.http, EditChannel::new().name(name))
+                    .await
+                    .map_err(failed("rename a channel"))?;
+                journal.record(Change::RenamedChannel {
+                    channel: channel.id,
+                    name: old_name,
+                });
             }
         }
-        guild
-            .edit_role(&ctx.http, self.role, EditRole::new().name(new_name))
-            .await?;
-
         Ok(())
     }
 
-    async fn deprecate(&mut self, ctx: &Context, guild: GuildId) -> anyhow::Result<()> {
-        let mut role = guild
-            .roles(&ctx.http)
-            .await?
-            .get(&self.role)
-            .ok_or(anyhow!("No such role"))?
-            .clone();
-        let new_role = guild
-            .create_role(
-                &ctx.http,
-                EditRole::new()
-                    .name(&role.name)
-                    .hoist(role.hoist)
-                    .mentionable(false)
-                    .permissions(role.permissions),
+    /// Moves the channels to the deprecated category, where the members of a new role can read
+    /// them but not write. The old role and the category are only deleted by
+    /// [`Deprecation::finish`], since that can't be undone.
+    async fn deprecate(
+        &self,
+        style: &CourseStyle,
+        ctx: &Context,
+        guild: GuildId,
+        journal: &mut Journal,
+    ) -> Result<Deprecation, Failed> {
+        let deprecated_category = style.deprecated_category.ok_or_else(|| {
+            Failed::new(
+                "find the deprecated category",
+                anyhow!("No deprecated category is configured"),
             )
-            .await?;
-        role.delete(&ctx.http).await?;
-        self.role = new_role.id;
-
-        for channel in &mut self.channels {
-            if let SerenityChannel::Guild(mut gchannel) = channel.to_channel(&ctx.http).await? {
-                match gchannel.kind {
-                    ChannelType::Text => {
-                        gchannel
-                            .id
-                            .say(
-                                &ctx.http,
-                                "*está cadeira já não está entre nós, descansa em paz cadeira, \
-                                a tua memória será para sempre preservada \
-                                ||num datacenter qualquer do discord||*",
-                            )
-                            .await?;
-                        gchannel
-                            .create_permission(
-                                &ctx.http,
-                                PermissionOverwrite {
-                                    allow: Permissions::VIEW_CHANNEL,
-                                    deny: Permissions::SEND_MESSAGES,
-                                    kind: Role(self.role),
-                                },
-                            )
-                            .await?;
-                        gchannel
-                            .edit(&ctx.http, EditChannel::new().category(DEPRECATED_CATEGORY))
-                            .await?;
+        })?;
+        let role = fetch_role(ctx, guild, self.role, journal).await?;
+        journal.plan(format!("+ role {} (not mentionable)", role.name));
+        journal.plan(format!("- role {} (the old one)", role.name));
+        let new_role = if journal.is_dry_run() {
+            journal.placeholder(&role.name)
+        } else {
+            let new_role = guild
+                .create_role(
+                    &ctx.http,
+                    EditRole::new()
+                        .name(&role.name)
+                        .hoist(role.hoist)
+                        .mentionable(false)
+                        .permissions(role.permissions),
+                )
+                .await
+                .map_err(failed("create the deprecated role"))?;
+            journal.record(Change::CreatedRole(new_role.id));
+            new_role.id
+        };
+
+        let mut channels = self.channels.clone();
+        let mut categories = Vec::new();
+        for channel in &mut channels {
+            let Some(mut gchannel) = fetch_channel(ctx, *channel, journal).await? else {
+                continue;
+            };
+            match gchannel.kind {
+                ChannelType::Text | ChannelType::Voice | ChannelType::Forum => {
+                    let kind = Role(new_role);
+                    let mut deny = Permissions::SEND_MESSAGES;
+                    // The posts of a forum are threads, which have their own permissions.
+                    if gchannel.kind == ChannelType::Forum {
+                        deny |= Permissions::SEND_MESSAGES_IN_THREADS
+                            | Permissions::CREATE_PUBLIC_THREADS;
                     }
-                    ChannelType::Category => {
-                        gchannel.delete(&ctx.http).await?;
-                        *channel = DEPRECATED_CATEGORY;
+                    let overwrite = PermissionOverwrite {
+                        allow: Permissions::VIEW_CHANNEL,
+                        deny,
+                        kind,
+                    };
+                    let description = journal.describe_overwrite(guild, &overwrite);
+                    journal.plan(format!("~ channel {}: {}", gchannel.name, description));
+                    journal.plan(format!(
+                        "~ channel {} -> category {}",
+                        gchannel.name, deprecated_category
+                    ));
+                    if gchannel.kind != ChannelType::Forum {
+                        journal.plan(format!("+ goodbye message in {}", gchannel.name));
+                    }
+                    if journal.is_dry_run() {
+                        continue;
                     }
-                    _ => {}
+                    let previous = previous_overwrite(&gchannel, kind);
+                    gchannel
+                        .create_permission(&ctx.http, overwrite)
+                        .await
+                        .map_err(failed("lock a channel"))?;
+                    journal.record(Change::Overwrote {
+                        channel: gchannel.id,
+                        kind,
+                        previous,
+                    });
+                    let category = gchannel.parent_id;
+                    gchannel
+                        .edit(&ctx.http, EditChannel::new().category(deprecated_category))
+                        .await
+                        .map_err(failed("move a channel"))?;
+                    journal.record(Change::MovedChannel {
+                        channel: gchannel.id,
+                        category,
+                    });
+                    if gchannel.kind == ChannelType::Forum {
+                        continue;
+                    }
+                    let message = gchannel
+                        .id
+                        .say(
+                            &ctx.http,
+                            "*está cadeira já não está entre nós, descansa em paz cadeira, \
+                            a tua memória será para sempre preservada \
+                            ||num datacenter qualquer do discord||*",
+                        )
+                        .await
+                        .map_err(failed("say goodbye"))?;
+                    journal.record(Change::SentMessage {
+                        channel: gchannel.id,
+                        message: message.id,
+                    });
+                }
+                ChannelType::Category => {
+                    journal.plan(format!("- category {}", gchannel.name));
+                    categories.push(gchannel.id);
+                    *channel = deprecated_category;
                 }
+                _ => {}
             }
         }
-        Ok(())
+        Ok(Deprecation {
+            course: Course {
+                role: new_role,
+                channels,
+                aliases: self.aliases.clone(),
+                restrictions: self.restrictions.clone(),
+                info: self.info.clone(),
+            },
+            old_role: self.role,
+            categories,
+        })
+    }
+
+    /// Undoes a deprecation: the role can be mentioned again and has the colour of the year, and
+    /// the channels go back to a category of their own where the role can write again.
+    async fn restore(
+        &self,
+        style: &CourseStyle,
+        role: &SerenityRole,
+        year: &str,
+        ctx: &Context,
+        guild: GuildId,
+        journal: &mut Journal,
+    ) -> Result<Course, Failed> {
+        guild
+            .edit_role(
+                &ctx.http,
+                role.id,
+                EditRole::new()
+                    .mentionable(true)
+                    .colour(style.role_colour(year)),
+            )
+            .await
+            .map_err(failed("restore the role"))?;
+        journal.record(Change::EditedRole {
+            role: role.id,
+            name: role.name.clone(),
+            colour: role.colour,
+            mentionable: role.mentionable,
+        });
+        let perms = course_overwrites(ctx, guild, role.id);
+        let category = guild
+            .create_channel(
+                &ctx,
+                CreateChannel::new(style.category_name(year, &role.name))
+                    .kind(ChannelType::Category)
+                    .permissions(perms),
+            )
+            .await
+            .map_err(failed("create the category"))?;
+        journal.record(Change::CreatedChannel(category.id));
+
+        let mut channels = vec![category.id];
+        for channel in &self.channels {
+            let SerenityChannel::Guild(mut gchannel) = channel
+                .to_channel(&ctx.http)
+                .await
+                .map_err(failed("fetch a channel"))?
+            else {
+                continue;
+            };
+            if !matches!(
+                gchannel.kind,
+                ChannelType::Text | ChannelType::Voice | ChannelType::Forum
+            ) {
+                continue;
+            }
+            let kind = Role(role.id);
+            let previous = previous_overwrite(&gchannel, kind);
+            gchannel
+                .create_permission(
+                    &ctx.http,
+                    PermissionOverwrite {
+                        allow: Permissions::VIEW_CHANNEL | Permissions::SEND_MESSAGES,
+                        deny: Permissions::empty(),
+                        kind,
+                    },
+                )
+                .await
+                .map_err(failed("unlock a channel"))?;
+            journal.record(Change::Overwrote {
+                channel: gchannel.id,
+                kind,
+                previous,
+            });
+            let old_category = gchannel.parent_id;
+            gchannel
+                .edit(&ctx.http, EditChannel::new().category(category.id))
+                .await
+                .map_err(failed("move a channel"))?;
+            journal.record(Change::MovedChannel {
+                channel: gchannel.id,
+                category: old_category,
+            });
+            channels.push(gchannel.id);
+        }
+        Ok(Course {
+            role: role.id,
+            channels,
+            aliases: self.aliases.clone(),
+            restrictions: self.restrictions.clone(),
+            info: self.info.clone(),
+        })
     }
 }
 
-pub fn read_courses() -> io::Result<MiEI> {
-    Ok(serde_json::from_reader(File::open(COURSES)?)?)
+/// A deprecated course whose old role and category still have to be deleted.
+struct Deprecation {
+    course: Course,
+    old_role: RoleId,
+    categories: Vec<ChannelId>,
+}
+
+impl Deprecation {
+    /// Deletes what the course no longer uses. Whatever fails to be deleted is left behind for
+    /// `$courses doctor` to find.
+    async fn finish(&self, ctx: &Context, guild: GuildId) {
+        if let Err(e) = guild.delete_role(&ctx.http, self.old_role).await {
+            crate::log!("Could not delete deprecated role {}: {}", self.old_role, e);
+        }
+        for category in &self.categories {
+            if let Err(e) = category.delete(&ctx.http).await {
+                crate::log!("Could not delete deprecated category {}: {}", category, e);
+            }
+        }
+    }
 }
 
 pub struct Channel<'a> {
@@ -0,0 +1,260 @@
+//! Undoing what a course operation changed in the guild when one of its steps fails, so that a
+//! half created course doesn't leave roles and channels behind that no course knows about.
+//!
+//! In a dry run the journal instead collects a description of each step, which are then skipped.
+
+use serenity::{
+    all::{EditChannel, EditRole},
+    model::{
+        Colour,
+        channel::{PermissionOverwrite, PermissionOverwriteType},
+        id::{ChannelId, GuildId, MessageId, RoleId},
+    },
+    prelude::Context,
+};
+use std::fmt::{self, Write};
+
+/// Something done to the guild that can be undone.
+pub(crate) enum Change {
+    CreatedRole(RoleId),
+    CreatedChannel(ChannelId),
+    EditedRole {
+        role: RoleId,
+        name: String,
+        colour: Colour,
+        mentionable: bool,
+    },
+    RenamedChannel {
+        channel: ChannelId,
+        name: String,
+    },
+    MovedChannel {
+        channel: ChannelId,
+        category: Option<ChannelId>,
+    },
+    /// The overwrite of `kind` in `channel` was set, replacing `previous` if there was one.
+    Overwrote {
+        channel: ChannelId,
+        kind: PermissionOverwriteType,
+        previous: Option<PermissionOverwrite>,
+    },
+    SentMessage {
+        channel: ChannelId,
+        message: MessageId,
+    },
+}
+
+impl Change {
+    async fn undo(&self, ctx: &Context, guild: GuildId) -> serenity::Result<()> {
+        match self {
+            Change::CreatedRole(role) => guild.delete_role(&ctx.http, *role).await,
+            Change::CreatedChannel(channel) => channel.delete(&ctx.http).await.map(|_| ()),
+            Change::EditedRole {
+                role,
+                name,
+                colour,
+                mentionable,
+            } => guild
+                .edit_role(
+                    &ctx.http,
+                    *role,
+                    EditRole::new()
+                        .name(name)
+                        .colour(*colour)
+                        .mentionable(*mentionable),
+                )
+                .await
+                .map(|_| ()),
+            Change::RenamedChannel { channel, name } => channel
+                .edit(&ctx.http, EditChannel::new().name(name))
+                .await
+                .map(|_| ()),
+            Change::MovedChannel { channel, category } => channel
+                .edit(&ctx.http, EditChannel::new().category(*category))
+                .await
+                .map(|_| ()),
+            Change::Overwrote {
+                channel,
+                kind,
+                previous,
+            } => match previous {
+                Some(previous) => channel.create_permission(&ctx.http, previous.clone()).await,
+                None => channel.delete_permission(&ctx.http, *kind).await,
+            },
+            Change::SentMessage { channel, message } => {
+                channel.delete_message(&ctx.http, *message).await
+            }
+        }
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::CreatedRole(role) => write!(f, "created role {}", role),
+            Change::CreatedChannel(channel) => write!(f, "created channel {}", channel),
+            Change::EditedRole { role, .. } => write!(f, "edited role {}", role),
+            Change::RenamedChannel { channel, .. } => write!(f, "renamed channel {}", channel),
+            Change::MovedChannel { channel, .. } => write!(f, "moved channel {}", channel),
+            Change::Overwrote { channel, .. } => {
+                write!(f, "permissions of channel {}", channel)
+            }
+            Change::SentMessage { channel, .. } => write!(f, "message in channel {}", channel),
+        }
+    }
+}
+
+/// A step of a course operation that failed.
+pub(crate) struct Failed {
+    step: &'static str,
+    source: anyhow::Error,
+}
+
+impl Failed {
+    pub(crate) fn new(step: &'static str, source: impl Into<anyhow::Error>) -> Self {
+        Failed {
+            step,
+            source: source.into(),
+        }
+    }
+}
+
+/// For `map_err`, naming the step that failed.
+pub(crate) fn failed<E: Into<anyhow::Error>>(step: &'static str) -> impl FnOnce(E) -> Failed {
+    move |e| Failed::new(step, e)
+}
+
+/// The changes made so far by a course operation.
+#[derive(Default)]
+pub(crate) struct Journal {
+    changes: Vec<Change>,
+    /// In a dry run, the steps that would have been done.
+    plan: Option<Vec<String>>,
+    /// The names of what a dry run pretended to create, by placeholder id minus one.
+    placeholders: Vec<String>,
+}
+
+impl Journal {
+    pub(crate) fn dry_run() -> Self {
+        Journal {
+            plan: Some(Vec::new()),
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn is_dry_run(&self) -> bool {
+        self.plan.is_some()
+    }
+
+    pub(crate) fn record(&mut self, change: Change) {
+        self.changes.push(change);
+    }
+
+    /// Describes a step of a dry run.
+    pub(crate) fn plan(&mut self, step: String) {
+        if let Some(plan) = &mut self.plan {
+            plan.push(step);
+        }
+    }
+
+    /// Describes the overwrites a dry run would set, one per line under the previous step.
+    pub(crate) fn plan_overwrites(&mut self, guild: GuildId, overwrites: &[PermissionOverwrite]) {
+        for o in overwrites {
+            let step = format!("    {}", self.describe_overwrite(guild, o));
+            self.plan(step);
+        }
+    }
+
+    /// An id for something a dry run pretends to create, so that later steps can refer to it.
+    pub(crate) fn placeholder<T: From<u64>>(&mut self, name: &str) -> T {
+        self.placeholders.push(name.to_string());
+        T::from(self.placeholders.len() as u64)
+    }
+
+    pub(crate) fn describe_overwrite(&self, guild: GuildId, o: &PermissionOverwrite) -> String {
+        let target = match o.kind {
+            PermissionOverwriteType::Role(r) if r.get() == guild.get() => "@everyone".to_string(),
+            PermissionOverwriteType::Role(r) => match self.placeholder_name(r.get()) {
+                Some(name) => format!("role {}", name),
+                None => format!("role {}", r),
+            },
+            PermissionOverwriteType::Member(m) => format!("member {}", m),
+            _ => "unknown".to_string(),
+        };
+        let mut s = target;
+        if !o.allow.is_empty() {
+            write!(s, " allow {}", o.allow.get_permission_names().join(", ")).unwrap();
+        }
+        if !o.deny.is_empty() {
+            write!(s, " deny {}", o.deny.get_permission_names().join(", ")).unwrap();
+        }
+        s
+    }
+
+    fn placeholder_name(&self, id: u64) -> Option<&str> {
+        let index = usize::try_from(id).ok()?.checked_sub(1)?;
+        self.placeholders.get(index).map(String::as_str)
+    }
+
+    /// The steps of a dry run, empty when the changes were actually made.
+    pub(crate) fn take_plan(&mut self) -> Vec<String> {
+        self.plan.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    /// Undoes every change, newest first, and describes what failed.
+    pub(crate) async fn undo(self, ctx: &Context, guild: GuildId, failed: Failed) -> CourseError {
+        let mut not_undone = Vec::new();
+        for change in self.changes.iter().rev() {
+            if let Err(e) = change.undo(ctx, guild).await {
+                not_undone.push(format!("{} ({})", change, e));
+            }
+        }
+        CourseError::Failed {
+            step: failed.step,
+            source: failed.source,
+            not_undone,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum CourseError {
+    NotFound(String),
+    AlreadyExists(String),
+    /// A step failed and the changes made before it were undone, except for `not_undone`.
+    Failed {
+        step: &'static str,
+        source: anyhow::Error,
+        not_undone: Vec<String>,
+    },
+}
+
+impl fmt::Display for CourseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CourseError::NotFound(course) => write!(f, "No such course: {}", course),
+            CourseError::AlreadyExists(course) => write!(f, "Course already exists: {}", course),
+            CourseError::Failed {
+                step,
+                source,
+                not_undone,
+            } => {
+                write!(f, "Could not {}: {}", step, source)?;
+                if not_undone.is_empty() {
+                    write!(f, " (every change was undone)")
+                } else {
+                    write!(f, " (could not undo: {})", not_undone.join(", "))
+                }
+            }
+        }
+    }
+}
+
+impl std::error::Error for CourseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CourseError::Failed { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
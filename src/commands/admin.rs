@@ -1,13 +1,20 @@
+mod channel_templates;
 mod channels;
+mod course_styles;
 mod greeting_channels;
 mod log_channel;
+mod mutes;
 mod user_groups;
 
 use super::cesium::CESIUM_ROLE;
+use channel_templates::*;
 use channels::*;
+use course_styles::*;
 use futures::stream::TryStreamExt;
 use greeting_channels::*;
 use log_channel::*;
+use mutes::*;
+pub use mutes::{parse_duration, remute, start_mute_scheduler};
 use serenity::{
     all::EditMessage,
     framework::standard::{
@@ -21,10 +28,17 @@ use std::{collections::HashSet, str};
 use user_groups::*;
 
 #[group]
-#[commands(edit, say, tomada_de_posse)]
+#[commands(edit, say, tomada_de_posse, mute, unmute)]
 #[required_permissions(ADMINISTRATOR)]
 #[prefixes("sudo")]
-#[sub_groups(Channels, GreetingChannels, LogChannel, UserGroups)]
+#[sub_groups(
+    Channels,
+    ChannelTemplates,
+    CourseStyles,
+    GreetingChannels,
+    LogChannel,
+    UserGroups
+)]
 struct Admin;
 
 #[command]
@@ -0,0 +1,176 @@
+use crate::{
+    channels::{MiEI, course_style},
+    config::{ChannelTemplate, Config},
+    guild,
+    util::say_code_blocks,
+};
+use serde::{Deserialize, Serialize};
+use serenity::{
+    framework::standard::{
+        Args, CommandResult,
+        macros::{command, group},
+    },
+    model::channel::Message,
+    prelude::*,
+};
+
+#[group]
+#[commands(
+    templates_show,
+    templates_define,
+    templates_remove,
+    templates_year,
+    templates_course
+)]
+#[prefixes("templates")]
+struct ChannelTemplates;
+
+/// A set of templates as written in a message.
+#[derive(Serialize, Deserialize)]
+struct TemplateSet {
+    #[serde(default, rename = "channel")]
+    channels: Vec<ChannelTemplate>,
+}
+
+#[command("show")]
+#[description(
+    "Show the sets of channels created for each course and which years and courses use them, \
+    or the templates of one set"
+)]
+#[usage("[set]")]
+pub async fn templates_show(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = msg.guild_id.ok_or("Not in a guild")?;
+    let style = course_style(ctx, guild_id).await;
+    if let Ok(set) = args.single::<String>() {
+        let channels = style
+            .channel_templates
+            .get(&set)
+            .ok_or("No such templates")?
+            .clone();
+        let toml = toml::to_string_pretty(&TemplateSet { channels })?;
+        say_code_blocks(ctx, msg.channel_id, "toml", toml.lines().map(String::from)).await?;
+        return Ok(());
+    }
+    let mut lines = Vec::new();
+    for (set, templates) in &style.channel_templates {
+        let names = templates
+            .iter()
+            .map(|t| t.name.as_str())
+            .collect::<Vec<_>>();
+        lines.push(format!("{}: {}", set, names.join(", ")));
+    }
+    for (year, set) in &style.year_templates {
+        lines.push(format!("{}ano -> {}", year, set));
+    }
+    for (course, set) in &style.course_templates {
+        lines.push(format!("{} -> {}", course, set));
+    }
+    say_code_blocks(ctx, msg.channel_id, "", lines).await?;
+    Ok(())
+}
+
+#[command("define")]
+#[description(
+    "Create or replace a set of channel templates, written in TOML as `[[channel]]` tables with \
+    a `name` containing `{name}`, a `kind` (text, voice or forum), a `topic` and `overwrites`, \
+    each with a `role` (everyone, course or a role id) and `allow` and `deny` lists of \
+    permissions"
+)]
+#[usage("set ```toml ... ```")]
+#[example(
+    "lab ```toml\n[[channel]]\nname = \"duvidas-{name}\"\n\n[[channel]]\nname = \"lab-{name}\"\nkind = \"voice\"\n```"
+)]
+#[min_args(2)]
+pub async fn templates_define(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = msg.guild_id.ok_or("Not in a guild")?;
+    let set = args.single::<String>()?;
+    let body = args.rest().trim();
+    let body = body
+        .strip_prefix("```toml")
+        .or_else(|| body.strip_prefix("```"))
+        .and_then(|b| b.strip_suffix("```"))
+        .unwrap_or(body);
+    let TemplateSet { channels } = toml::from_str(body)?;
+    if channels.is_empty() {
+        return Err("A set needs at least one channel".into());
+    }
+    guild::data::<Config>(ctx, guild_id)
+        .await
+        .write()
+        .await
+        .set_channel_templates(&set, Some(channels))?;
+    msg.channel_id
+        .say(&ctx, format!("Templates {} defined", set))
+        .await?;
+    Ok(())
+}
+
+#[command("remove")]
+#[description("Remove a set of channel templates that no year or course uses")]
+#[usage("set")]
+#[num_args(1)]
+pub async fn templates_remove(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = msg.guild_id.ok_or("Not in a guild")?;
+    let set = args.single::<String>()?;
+    guild::data::<Config>(ctx, guild_id)
+        .await
+        .write()
+        .await
+        .set_channel_templates(&set, None)?;
+    msg.channel_id
+        .say(&ctx, format!("Templates {} removed", set))
+        .await?;
+    Ok(())
+}
+
+#[command("year")]
+#[description(
+    "Choose the set of channels of the new courses of a year. Without a set, the year uses the \
+    default one."
+)]
+#[usage("year [set]")]
+#[example("3 lab")]
+#[min_args(1)]
+#[max_args(2)]
+pub async fn templates_year(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = msg.guild_id.ok_or("Not in a guild")?;
+    let year = args.single::<String>()?;
+    let set = args.single::<String>().ok();
+    guild::data::<Config>(ctx, guild_id)
+        .await
+        .write()
+        .await
+        .set_year_templates(&year, set)?;
+    msg.channel_id.say(&ctx, "Templates chosen").await?;
+    Ok(())
+}
+
+#[command("course")]
+#[description(
+    "Choose the set of channels of a course, over the one of its year. Without a set, the \
+    course uses the one of its year."
+)]
+#[usage("course [set]")]
+#[example("LI3 lab")]
+#[min_args(1)]
+#[max_args(2)]
+pub async fn templates_course(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = msg.guild_id.ok_or("Not in a guild")?;
+    let course = args.single::<String>()?;
+    let set = args.single::<String>().ok();
+    let miei = guild::data::<MiEI>(ctx, guild_id).await;
+    let miei = miei.read().await;
+    // A course that's gone can still have its choice forgotten.
+    let name = match miei.resolve(&course) {
+        Some(name) => name.to_string(),
+        None if set.is_none() => course.to_uppercase(),
+        None => return Err(format!("No such course: {}", course).into()),
+    };
+    guild::data::<Config>(ctx, guild_id)
+        .await
+        .write()
+        .await
+        .set_course_templates(&name, set)?;
+    msg.channel_id.say(&ctx, "Templates chosen").await?;
+    Ok(())
+}
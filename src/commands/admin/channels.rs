@@ -1,11 +1,11 @@
 //! Channels where the bot will respond to commands
 
-use crate::{config::Config, get};
+use crate::{config::Config, guild};
 use itertools::Itertools;
 use serenity::{
     framework::standard::{
-        macros::{command, group},
         Args, CommandResult,
+        macros::{command, group},
     },
     model::{channel::Message, id::ChannelId},
     prelude::*,
@@ -22,7 +22,12 @@ pub struct Channels;
 #[min_args(1)]
 pub async fn add(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
     let channel_id = args.single::<ChannelId>()?;
-    get!(ctx, Config, write).add_allowed_channel(channel_id)?;
+    let guild_id = msg.guild_id.ok_or("Not in a guild")?;
+    guild::data::<Config>(ctx, guild_id)
+        .await
+        .write()
+        .await
+        .add_allowed_channel(channel_id)?;
     msg.channel_id.say(&ctx, "Channel added").await?;
     Ok(())
 }
@@ -31,8 +36,9 @@ pub async fn add(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult
 #[description("Lists all the allowed channels")]
 #[usage("")]
 pub async fn list(ctx: &Context, msg: &Message) -> CommandResult {
-    let share_map = ctx.data.read().await;
-    let config = share_map.get::<Config>().unwrap().write().await;
+    let guild_id = msg.guild_id.ok_or("Not in a guild")?;
+    let config = guild::data::<Config>(ctx, guild_id).await;
+    let config = config.read().await;
     msg.channel_id
         .say(
             &ctx,
@@ -51,7 +57,12 @@ pub async fn list(ctx: &Context, msg: &Message) -> CommandResult {
 #[min_args(1)]
 pub async fn del(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
     let channel_id = args.single::<ChannelId>()?;
-    get!(ctx, Config, write).remove_allowed_channel(channel_id)?;
+    let guild_id = msg.guild_id.ok_or("Not in a guild")?;
+    guild::data::<Config>(ctx, guild_id)
+        .await
+        .write()
+        .await
+        .remove_allowed_channel(channel_id)?;
     msg.channel_id.say(&ctx, "Channel removed").await?;
     Ok(())
 }
@@ -0,0 +1,161 @@
+use crate::{
+    channels::{MiEI, course_style},
+    config::Config,
+    guild, log,
+};
+use serenity::{
+    framework::standard::{
+        Args, CommandResult,
+        macros::{command, group},
+    },
+    model::{
+        channel::{Channel, ChannelType, Message},
+        id::ChannelId,
+    },
+    prelude::*,
+};
+use std::fmt::Write;
+
+#[group]
+#[commands(style_show, deprecated_category, role_colour, category_emoji)]
+#[prefixes("courses")]
+struct CourseStyles;
+
+#[command("show")]
+#[description("Show the deprecated category and the colour and emoji of each year")]
+#[usage("")]
+pub async fn style_show(ctx: &Context, msg: &Message) -> CommandResult {
+    let guild_id = msg.guild_id.ok_or("Not in a guild")?;
+    let style = course_style(ctx, guild_id).await;
+    let mut s = match style.deprecated_category {
+        Some(ch) => format!("Deprecated category: {}\n", ch.mention()),
+        None => "No deprecated category\n".to_string(),
+    };
+    let mut years = style
+        .role_colours
+        .keys()
+        .chain(style.category_emojis.keys())
+        .collect::<Vec<_>>();
+    years.sort();
+    years.dedup();
+    for year in years {
+        writeln!(
+            s,
+            "{}ano: #{:06x} {}",
+            year,
+            style.role_colour(year),
+            style
+                .category_emojis
+                .get(year)
+                .map(String::as_str)
+                .unwrap_or_default()
+        )?;
+    }
+    msg.channel_id.say(&ctx, s).await?;
+    Ok(())
+}
+
+#[command("deprecated")]
+#[description(
+    "Set the category the channels of deprecated courses are moved to, or unset it if none is \
+    given"
+)]
+#[usage("[category_id]")]
+#[max_args(1)]
+pub async fn deprecated_category(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = msg.guild_id.ok_or("Not in a guild")?;
+    let channel_id = if args.is_empty() {
+        None
+    } else {
+        Some(args.single::<ChannelId>()?)
+    };
+    if let Some(id) = channel_id {
+        match id.to_channel(&ctx).await.ok() {
+            Some(Channel::Guild(c))
+                if c.guild_id == guild_id && c.kind == ChannelType::Category => {}
+            _ => return Err(format!("{} is not a category of this server", id).into()),
+        }
+    }
+    guild::data::<Config>(ctx, guild_id)
+        .await
+        .write()
+        .await
+        .set_deprecated_category(channel_id)?;
+    msg.channel_id
+        .say(
+            &ctx,
+            if channel_id.is_some() {
+                "Deprecated category set"
+            } else {
+                "Deprecated category unset"
+            },
+        )
+        .await?;
+    Ok(())
+}
+
+#[command("colour")]
+#[aliases("color")]
+#[description("Set the colour of the course roles of a year, recolouring the existing ones")]
+#[usage("year #rrggbb")]
+#[example("1 #843da4")]
+#[num_args(2)]
+pub async fn role_colour(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = msg.guild_id.ok_or("Not in a guild")?;
+    let year = args.single::<String>()?;
+    let colour = args.single::<String>()?;
+    let colour = u64::from_str_radix(colour.trim_start_matches('#'), 16)
+        .ok()
+        .filter(|c| *c <= 0xffffff)
+        .ok_or("Invalid colour, use #rrggbb")?;
+    guild::data::<Config>(ctx, guild_id)
+        .await
+        .write()
+        .await
+        .set_role_colour(&year, colour)?;
+    restyle(ctx, msg, &year).await
+}
+
+#[command("emoji")]
+#[description(
+    "Set the emoji in front of the course categories of a year, renaming the existing ones. \
+    Without an emoji, the categories have none."
+)]
+#[usage("year [emoji]")]
+#[example("1 📚")]
+#[min_args(1)]
+#[max_args(2)]
+pub async fn category_emoji(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = msg.guild_id.ok_or("Not in a guild")?;
+    let year = args.single::<String>()?;
+    let emoji = args.single::<String>().ok();
+    guild::data::<Config>(ctx, guild_id)
+        .await
+        .write()
+        .await
+        .set_category_emoji(&year, emoji)?;
+    restyle(ctx, msg, &year).await
+}
+
+async fn restyle(ctx: &Context, msg: &Message, year: &str) -> CommandResult {
+    let guild_id = msg.guild_id.ok_or("Not in a guild")?;
+    let style = course_style(ctx, guild_id).await;
+    let failed = guild::data::<MiEI>(ctx, guild_id)
+        .await
+        .read()
+        .await
+        .restyle_year(&style, year, ctx, guild_id)
+        .await;
+    if failed.is_empty() {
+        msg.channel_id.say(&ctx, "Courses updated").await?;
+    } else {
+        log!("Restyle failures: {:?}", failed);
+        msg.channel_id
+            .say(
+                &ctx,
+                format!("Courses updated, with failures:\n{}", failed.join("\n")),
+            )
+            .await?;
+    }
+    Ok(())
+}
@@ -1,8 +1,8 @@
-use crate::{config::Config, get};
+use crate::{config::Config, guild};
 use serenity::{
     framework::standard::{
-        macros::{command, group},
         Args, CommandResult,
+        macros::{command, group},
     },
     model::{channel::Message, id::ChannelId},
     prelude::*,
@@ -26,7 +26,12 @@ pub async fn greet_channel_set(ctx: &Context, msg: &Message, mut args: Args) ->
             Some(m.to_string())
         }
     });
-    get!(ctx, Config, write).set_greet_channel(channel_id, greeting)?;
+    let guild_id = msg.guild_id.ok_or("Not in a guild")?;
+    guild::data::<Config>(ctx, guild_id)
+        .await
+        .write()
+        .await
+        .set_greet_channel(channel_id, greeting)?;
     msg.channel_id.say(&ctx, "Greet channel set").await?;
     Ok(())
 }
@@ -35,7 +40,12 @@ pub async fn greet_channel_set(ctx: &Context, msg: &Message, mut args: Args) ->
 #[description("Disable the greeting channel")]
 #[usage("")]
 pub async fn greet_channel_clear(ctx: &Context, msg: &Message) -> CommandResult {
-    get!(ctx, Config, write).remove_greet_channel()?;
+    let guild_id = msg.guild_id.ok_or("Not in a guild")?;
+    guild::data::<Config>(ctx, guild_id)
+        .await
+        .write()
+        .await
+        .remove_greet_channel()?;
     msg.channel_id.say(&ctx, "Greet channel cleared").await?;
     Ok(())
 }
@@ -44,7 +54,13 @@ pub async fn greet_channel_clear(ctx: &Context, msg: &Message) -> CommandResult
 #[description("Check the current greet channel")]
 #[usage("")]
 pub async fn greet_channel(ctx: &Context, msg: &Message) -> CommandResult {
-    match get!(ctx, Config, read).greet_channel() {
+    let guild_id = msg.guild_id.ok_or("Not in a guild")?;
+    let greet_channel = guild::data::<Config>(ctx, guild_id)
+        .await
+        .read()
+        .await
+        .greet_channel();
+    match greet_channel {
         Some(ch) => {
             msg.channel_id
                 .say(&ctx, format!("Greet channel: {}", ch.mention()))
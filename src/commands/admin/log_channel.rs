@@ -1,8 +1,8 @@
-use crate::{config::Config, get};
+use crate::{config::Config, guild};
 use serenity::{
     framework::standard::{
-        macros::{command, group},
         Args, CommandResult,
+        macros::{command, group},
     },
     model::{channel::Message, id::ChannelId},
     prelude::*,
@@ -17,7 +17,13 @@ struct LogChannel;
 #[description("Check the current log channel")]
 #[usage("")]
 pub async fn log_channel(ctx: &Context, msg: &Message) -> CommandResult {
-    match get!(ctx, Config, read).log_channel() {
+    let guild_id = msg.guild_id.ok_or("Not in a guild")?;
+    let log_channel = guild::data::<Config>(ctx, guild_id)
+        .await
+        .read()
+        .await
+        .log_channel();
+    match log_channel {
         Some(ch) => {
             msg.channel_id
                 .say(&ctx, format!("Log channel: {}", ch.mention()))
@@ -33,7 +39,12 @@ pub async fn log_channel(ctx: &Context, msg: &Message) -> CommandResult {
 #[usage("#channel_mention")]
 pub async fn log_channel_set(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
     let channel_id = args.single::<ChannelId>().ok();
-    get!(ctx, Config, write).set_log_channel(channel_id)?;
+    let guild_id = msg.guild_id.ok_or("Not in a guild")?;
+    guild::data::<Config>(ctx, guild_id)
+        .await
+        .write()
+        .await
+        .set_log_channel(channel_id)?;
     msg.channel_id
         .say(
             &ctx,
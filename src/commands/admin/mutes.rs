@@ -0,0 +1,326 @@
+use crate::{
+    config::{Config, Mute},
+    guild, log,
+};
+use chrono::Utc;
+use serenity::{
+    all::{CreateEmbed, CreateMessage},
+    framework::standard::{Args, CommandResult, macros::command},
+    http::StatusCode,
+    model::{
+        channel::Message,
+        id::{GuildId, UserId},
+    },
+    prelude::*,
+    utils::{parse_role_mention, parse_user_mention},
+};
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+/// How often expired mutes are looked for.
+const CHECK_EVERY: Duration = Duration::from_secs(60);
+
+/// How long lifting an expired mute is retried before it's forgotten with the role still given.
+const GIVE_UP_AFTER: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// The longest a mute can last.
+const MAX_DURATION: Duration = Duration::from_secs(366 * 24 * 60 * 60);
+
+/// Parses durations like `2h`, `30m` or `1d12h`.
+pub fn parse_duration(s: &str) -> Option<Duration> {
+    let mut total = 0u64;
+    let mut n = None::<u64>;
+    for c in s.chars() {
+        if let Some(d) = c.to_digit(10) {
+            n = Some(n.unwrap_or(0).checked_mul(10)?.checked_add(d.into())?);
+            continue;
+        }
+        let unit = match c.to_ascii_lowercase() {
+            's' => 1,
+            'm' => 60,
+            'h' => 60 * 60,
+            'd' => 24 * 60 * 60,
+            'w' => 7 * 24 * 60 * 60,
+            _ => return None,
+        };
+        total = total.checked_add(n.take()?.checked_mul(unit)?)?;
+    }
+    (n.is_none() && total > 0).then(|| Duration::from_secs(total))
+}
+
+fn parse_user(arg: &str) -> Result<UserId, String> {
+    parse_user_mention(arg)
+        .or_else(|| arg.parse().ok())
+        .ok_or_else(|| format!("{} is not a user", arg))
+}
+
+/// Sends a note about a mute to the log channel, if there is one.
+async fn log_mute(ctx: &Context, guild: GuildId, title: &str, description: String) {
+    let config = guild::data::<Config>(ctx, guild).await;
+    let Some(ch) = config.read().await.log_channel() else {
+        return;
+    };
+    if let Err(e) = ch
+        .send_message(
+            &ctx.http,
+            CreateMessage::new().embed(CreateEmbed::new().title(title).description(description)),
+        )
+        .await
+    {
+        log!("Couldn't log {}: {:?}", title, e);
+    }
+}
+
+/// Whether a request failed in a way trying again won't fix, like a deleted role or missing
+/// permissions.
+fn is_permanent(e: &serenity::Error) -> bool {
+    matches!(e, serenity::Error::Http(e)
+        if e.status_code().is_some_and(|s| s.is_client_error() && s != StatusCode::TOO_MANY_REQUESTS))
+}
+
+/// Takes the mute role from a member, if there is one.
+async fn take_mute_role(ctx: &Context, guild: GuildId, user: UserId) -> serenity::Result<()> {
+    let config = guild::data::<Config>(ctx, guild).await;
+    let Some(role) = config.read().await.get_mute_role() else {
+        return Ok(());
+    };
+    match guild.member(ctx, user).await {
+        Ok(member) => member.remove_role(&ctx.http, role).await,
+        // Members who left can't be unmuted, but their mute still ends.
+        Err(e) if is_permanent(&e) => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Takes the mute role from a member and forgets their mute.
+///
+/// If the role couldn't be taken the mute is kept, so it can be lifted again later, unless
+/// trying again won't help or `give_up` is set. Then the mute is forgotten anyway and the error
+/// is returned to be reported.
+async fn lift(
+    ctx: &Context,
+    guild: GuildId,
+    user: UserId,
+    give_up: bool,
+) -> Result<Option<serenity::Error>, String> {
+    let failed = match take_mute_role(ctx, guild, user).await {
+        Ok(()) => None,
+        Err(e) if give_up || is_permanent(&e) => Some(e),
+        Err(e) => return Err(e.to_string()),
+    };
+    let config = guild::data::<Config>(ctx, guild).await;
+    config
+        .write()
+        .await
+        .remove_mute(user)
+        .map_err(|e| e.to_string())?;
+    Ok(failed)
+}
+
+/// Lifts every mute that expired.
+async fn lift_expired(ctx: &Context) {
+    let now = Utc::now().timestamp();
+    for guild in ctx.cache.guilds() {
+        let expired = {
+            let config = guild::data::<Config>(ctx, guild).await;
+            let config = config.read().await;
+            config
+                .mutes()
+                .iter()
+                .filter(|m| m.until <= now)
+                .map(|m| (m.user, m.until))
+                .collect::<Vec<_>>()
+        };
+        for (user, until) in expired {
+            let give_up = now - until > GIVE_UP_AFTER.as_secs() as i64;
+            match lift(ctx, guild, user, give_up).await {
+                Ok(None) => {
+                    log_mute(
+                        ctx,
+                        guild,
+                        "Mute expired",
+                        format!("**User:** {}", user.mention()),
+                    )
+                    .await
+                }
+                Ok(Some(e)) => {
+                    log_mute(
+                        ctx,
+                        guild,
+                        "Mute expired",
+                        format!(
+                            "**User:** {}\nThe mute role could not be removed: {}",
+                            user.mention(),
+                            e
+                        ),
+                    )
+                    .await
+                }
+                Err(e) => log!("Couldn't unmute {}, trying again later: {}", user, e),
+            }
+        }
+    }
+}
+
+/// Starts lifting expired mutes in the background, once no matter how often it's called.
+pub fn start_mute_scheduler(ctx: Context) {
+    static STARTED: AtomicBool = AtomicBool::new(false);
+    if STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(CHECK_EVERY);
+        loop {
+            interval.tick().await;
+            lift_expired(&ctx).await;
+        }
+    });
+}
+
+/// Gives the mute role back to a member who left and came back while muted.
+pub async fn remute(ctx: &Context, guild: GuildId, user: UserId) -> serenity::Result<()> {
+    let config = guild::data::<Config>(ctx, guild).await;
+    let config = config.read().await;
+    if let (Some(role), Some(_)) = (config.get_mute_role(), config.mute_of(user)) {
+        guild
+            .member(ctx, user)
+            .await?
+            .add_role(&ctx.http, role)
+            .await?;
+    }
+    Ok(())
+}
+
+#[command]
+#[description("Mutes a member for a while by giving them the mute role")]
+#[usage("@user duration [reason]")]
+#[example("@user 2h spamming")]
+#[example("@user 1d12h")]
+#[min_args(2)]
+#[sub_commands(mute_role)]
+pub async fn mute(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = msg.guild_id.ok_or("Not in a guild")?;
+    let user = parse_user(&args.single::<String>()?)?;
+    let duration = args.single::<String>()?;
+    let duration = parse_duration(&duration)
+        .ok_or_else(|| format!("{} is not a duration, like 2h or 1d12h", duration))?;
+    if duration > MAX_DURATION {
+        return Err(format!(
+            "Mutes can last at most {} days",
+            MAX_DURATION.as_secs() / (24 * 60 * 60)
+        )
+        .into());
+    }
+    let until = i64::try_from(duration.as_secs())
+        .ok()
+        .and_then(|d| Utc::now().timestamp().checked_add(d))
+        .ok_or("The mute would end too far in the future")?;
+    let reason = Some(args.rest().trim())
+        .filter(|r| !r.is_empty())
+        .map(str::to_string);
+    let config = guild::data::<Config>(ctx, guild_id).await;
+    // The mute is saved before the role is given, so no member is left muted without a record
+    // the scheduler can lift.
+    let (role, previous) = {
+        let mut config = config.write().await;
+        let role = config
+            .get_mute_role()
+            .ok_or("No mute role, set one with `$sudo mute role @role`")?;
+        let previous = config.mute_of(user).cloned();
+        config.add_mute(Mute {
+            user,
+            until,
+            reason: reason.clone(),
+        })?;
+        (role, previous)
+    };
+    let given = match guild_id.member(ctx, user).await {
+        Ok(member) => member.add_role(&ctx.http, role).await,
+        Err(e) => Err(e),
+    };
+    if let Err(e) = given {
+        let mut config = config.write().await;
+        let rolled_back = match previous {
+            Some(previous) => config.add_mute(previous),
+            None => config.remove_mute(user).map(|_| ()),
+        };
+        if let Err(e) = rolled_back {
+            log!("Couldn't forget the mute of {}: {}", user, e);
+        }
+        return Err(e.into());
+    }
+    log_mute(
+        ctx,
+        guild_id,
+        "User muted",
+        format!(
+            "**User:**   {}\n**By:**     {}\n**Until:**  <t:{}:f>\n**Reason:** {}",
+            user.mention(),
+            msg.author.mention(),
+            until,
+            reason.as_deref().unwrap_or("None")
+        ),
+    )
+    .await;
+    msg.channel_id
+        .say(
+            &ctx.http,
+            format!("Muted {} until <t:{}:f>", user.mention(), until),
+        )
+        .await?;
+    Ok(())
+}
+
+#[command("role")]
+#[description("Check or set the role given to muted members")]
+#[usage("[@role]")]
+#[max_args(1)]
+pub async fn mute_role(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let guild_id = msg.guild_id.ok_or("Not in a guild")?;
+    let config = guild::data::<Config>(ctx, guild_id).await;
+    let mut config = config.write().await;
+    if let Some(role) = args.current() {
+        let role = parse_role_mention(role)
+            .or_else(|| role.parse().ok())
+            .ok_or_else(|| format!("{} is not a role", role))?;
+        config.set_mute_role(role)?;
+    }
+    let reply = match config.get_mute_role() {
+        Some(role) => format!("Mute role: {}", role.mention()),
+        None => "No mute role".to_string(),
+    };
+    msg.channel_id.say(&ctx.http, reply).await?;
+    Ok(())
+}
+
+#[command]
+#[description("Lifts the mute of a member before it expires")]
+#[usage("@user")]
+#[min_args(1)]
+#[max_args(1)]
+pub async fn unmute(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = msg.guild_id.ok_or("Not in a guild")?;
+    let user = parse_user(&args.single::<String>()?)?;
+    let failed = lift(ctx, guild_id, user, false).await?;
+    let note = failed
+        .map(|e| format!("\nThe mute role could not be removed: {}", e))
+        .unwrap_or_default();
+    log_mute(
+        ctx,
+        guild_id,
+        "User unmuted",
+        format!(
+            "**User:** {}\n**By:**   {}{}",
+            user.mention(),
+            msg.author.mention(),
+            note
+        ),
+    )
+    .await;
+    msg.channel_id
+        .say(&ctx.http, format!("Unmuted {}{}", user.mention(), note))
+        .await?;
+    Ok(())
+}
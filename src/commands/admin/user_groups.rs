@@ -1,8 +1,8 @@
-use crate::{commands::usermod::*, config::Config, get};
+use crate::{commands::usermod::*, config::Config, guild};
 use serenity::{
     framework::standard::{
-        macros::{command, group},
         Args, CommandResult,
+        macros::{command, group},
     },
     model::{channel::Message, id::RoleId},
     prelude::*,
@@ -20,11 +20,16 @@ struct UserGroups;
 #[min_args(2)]
 async fn add(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
     let role = args.single::<RoleId>()?;
-    if !role_exists(ctx, msg.guild_id.ok_or("Not in a guild")?, role).await? {
+    let guild_id = msg.guild_id.ok_or("Not in a guild")?;
+    if !role_exists(ctx, guild_id, role).await? {
         return Err("Role doesn't exist".into());
     }
     let desc = args.rest();
-    get!(ctx, Config, write).add_user_group(role, desc.to_string())?;
+    guild::data::<Config>(ctx, guild_id)
+        .await
+        .write()
+        .await
+        .add_user_group(role, desc.to_string())?;
     msg.channel_id.say(&ctx, "Role added").await?;
     Ok(())
 }
@@ -35,10 +40,15 @@ async fn add(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
 #[min_args(1)]
 async fn remove(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
     let role = args.single::<RoleId>()?;
-    if !role_exists(ctx, msg.guild_id.ok_or("Not in a guild")?, role).await? {
+    let guild_id = msg.guild_id.ok_or("Not in a guild")?;
+    if !role_exists(ctx, guild_id, role).await? {
         return Err("Role doesn't exist".into());
     }
-    get!(ctx, Config, write).remove_user_group(role)?;
+    guild::data::<Config>(ctx, guild_id)
+        .await
+        .write()
+        .await
+        .remove_user_group(role)?;
     msg.channel_id.say(&ctx, "Role removed").await?;
     Ok(())
 }
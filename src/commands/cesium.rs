@@ -1,4 +1,7 @@
-use crate::get;
+use crate::{
+    guild::{self, GuildData, GuildMap},
+    storage::{Storage, migrations},
+};
 use futures::future::TryFutureExt;
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
@@ -16,13 +19,7 @@ use serenity::{
     },
     prelude::*,
 };
-use std::{
-    collections::HashMap,
-    fs::{File, OpenOptions},
-    io::{self, BufWriter},
-    iter::once,
-    sync::Arc,
-};
+use std::{collections::HashMap, iter::once};
 
 #[group]
 #[commands(add, join, remove)]
@@ -34,7 +31,6 @@ const CESIUM_CATEGORY: ChannelId = ChannelId::new(418798551317872660);
 pub const CESIUM_ROLE: RoleId = RoleId::new(418842665061318676);
 const MODS_ROLE: RoleId = RoleId::new(618572138718298132);
 const MENTOR_ROLE: RoleId = RoleId::new(688760837980291120);
-const CHANNELS: &str = "data/cesium_channels.json";
 
 #[check]
 #[name = "is_mod_or_cesium"]
@@ -68,38 +64,48 @@ pub async fn is_mod_or_cesium(
 
 #[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq, Eq)]
 pub struct ChannelMapping {
+    #[serde(skip)]
+    guild: GuildId,
     last_number: u32,
     channels: HashMap<ChannelId, ChannelId>,
 }
 
 impl ChannelMapping {
-    pub fn load() -> io::Result<Self> {
-        Ok(serde_json::from_reader(File::open(CHANNELS)?)?)
+    fn write_channels(&self) -> anyhow::Result<()> {
+        self.write()
     }
 
-    fn write_channels(&self) -> Result<(), io::Error> {
-        serde_json::to_writer(
-            BufWriter::new(
-                OpenOptions::new()
-                    .write(true)
-                    .truncate(true)
-                    .create(true)
-                    .open(CHANNELS)?,
-            ),
-            &self,
-        )
-        .map_err(|j| j.into())
+    pub(crate) fn last_number(&self) -> u32 {
+        self.last_number
+    }
+
+    /// Every mentor room, as pairs of text and voice channels.
+    pub(crate) fn rooms(&self) -> impl Iterator<Item = (ChannelId, ChannelId)> + '_ {
+        self.channels.iter().map(|(&text, &voice)| (text, voice))
+    }
+
+    pub(crate) fn from_parts(
+        guild: GuildId,
+        last_number: u32,
+        rooms: impl IntoIterator<Item = (ChannelId, ChannelId)>,
+    ) -> Self {
+        ChannelMapping {
+            guild,
+            last_number,
+            channels: rooms.into_iter().collect(),
+        }
     }
 
     fn get_channel(&self, channel: &ChannelId) -> Option<&ChannelId> {
         self.channels.get(channel)
     }
 
-    async fn create_channel<C, U>(&mut self, guild_id: GuildId, ctx: C, users: U) -> CommandResult
+    async fn create_channel<C, U>(&mut self, ctx: C, users: U) -> CommandResult
     where
         C: CacheHttp + AsRef<Http> + Copy,
         U: Iterator<Item = UserId>,
     {
+        let guild_id = self.guild;
         let user_ids = users.collect::<Vec<_>>();
         let users: Vec<_> = user_ids
             .iter()
@@ -144,7 +150,7 @@ impl ChannelMapping {
                 CreateChannel::new(format!("mentor-channel-{}", self.last_number))
                     .kind(ChannelType::Voice)
                     .category(CESIUM_CATEGORY)
-                    .permissions(users.into_iter()),
+                    .permissions(users),
             )
             .await?;
         text.say(
@@ -183,8 +189,29 @@ Bem vindos aos vosso canto privado! {}",
     }
 }
 
+impl GuildData for ChannelMapping {
+    const FILE: &'static str = "cesium_channels.json";
+    const MIGRATIONS: &'static [migrations::Migration] = migrations::MENTOR_ROOMS;
+
+    fn load(storage: &dyn Storage, guild: GuildId) -> anyhow::Result<Option<Self>> {
+        storage.load_mentor_rooms(guild)
+    }
+
+    fn save(&self, storage: &dyn Storage) -> anyhow::Result<()> {
+        storage.save_mentor_rooms(self)
+    }
+
+    fn guild(&self) -> GuildId {
+        self.guild
+    }
+
+    fn set_guild(&mut self, guild: GuildId) {
+        self.guild = guild;
+    }
+}
+
 impl TypeMapKey for ChannelMapping {
-    type Value = Arc<RwLock<ChannelMapping>>;
+    type Value = GuildMap<ChannelMapping>;
 }
 
 #[command]
@@ -195,8 +222,11 @@ pub async fn add(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult
     let guild_id = msg.guild_id.ok_or("Message with no guild id")?;
     args.iter::<UserId>().try_for_each(|x| x.map(|_| ()))?;
     args.restore();
-    get!(ctx, ChannelMapping, write)
-        .create_channel(guild_id, &ctx, args.iter::<UserId>().map(Result::unwrap))
+    guild::data::<ChannelMapping>(ctx, guild_id)
+        .await
+        .write()
+        .await
+        .create_channel(&ctx, args.iter::<UserId>().map(Result::unwrap))
         .await?;
     msg.channel_id.say(&ctx, "Room created").await?;
     Ok(())
@@ -206,7 +236,11 @@ pub async fn add(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult
 #[description("Removes a new private room")]
 #[usage("")]
 pub async fn remove(ctx: &Context, msg: &Message) -> CommandResult {
-    get!(ctx, ChannelMapping, write)
+    let guild_id = msg.guild_id.ok_or("Message with no guild id")?;
+    guild::data::<ChannelMapping>(ctx, guild_id)
+        .await
+        .write()
+        .await
         .delete_channel(msg.channel_id, &ctx)
         .await
 }
@@ -218,8 +252,9 @@ pub async fn remove(ctx: &Context, msg: &Message) -> CommandResult {
 #[usage("StudentMention [channel_mention]")]
 #[min_args(1)]
 pub async fn join(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
-    let data_lock = ctx.data.read().await;
-    let channels = get!(> data_lock, ChannelMapping, read);
+    let guild_id = msg.guild_id.ok_or("Message with no guild id")?;
+    let mapping = guild::data::<ChannelMapping>(ctx, guild_id).await;
+    let channels = mapping.read().await;
     let user = args.single::<UserId>()?;
     let text = match args.single::<ChannelId>() {
         Ok(t) => t,
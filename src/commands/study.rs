@@ -1,10 +1,31 @@
-use crate::{channels::MiEI, get, log};
-use futures::{
-    future,
-    stream::{self, StreamExt},
+mod aliases;
+mod catalogue;
+mod doctor;
+mod doubts;
+mod expiry;
+mod info;
+mod menu;
+mod restrictions;
+mod rollover;
+mod stats;
+
+use crate::{
+    channels::{MiEI, Refusal, archive_course},
+    guild, log,
+    study_args::{self, ParseError, Selector},
+    util::say_code_blocks,
 };
-use once_cell::sync::Lazy;
-use regex::{Regex, RegexBuilder};
+use aliases::*;
+use catalogue::*;
+use doctor::*;
+pub use doubts::DOUBTS_GROUP;
+pub use expiry::start_expiry_scheduler;
+use expiry::*;
+use info::*;
+use menu::*;
+pub use menu::{handle_menu_interaction, study_slash_command};
+use restrictions::*;
+use rollover::*;
 use serenity::{
     all::{Colour, CreateEmbed, CreateMessage},
     framework::standard::{
@@ -18,30 +39,49 @@ use serenity::{
     },
     prelude::*,
 };
-use std::collections::BTreeMap;
+use stats::*;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    ops::{Deref, DerefMut},
+};
+use tokio::sync::RwLockWriteGuard;
 
 #[group]
 #[commands(study, unstudy)]
 struct Study;
 
 #[command]
-#[description("Permite a alguém juntar-se às salas das cadeiras.")]
-#[usage("[CADEIRA|ANO|ANOSEMESTRE, ...]")]
+#[description(
+    "Permite a alguém juntar-se às salas das cadeiras. Um `-` antes tira cadeiras às anteriores."
+)]
+#[usage("[CADEIRA|PREFIXO*|ANO|ANOSEMESTRE, ...]")]
 #[example("Algebra PI")]
 #[example("1ano")]
 #[example("2ano1sem")]
+#[example("1-2ano")]
+#[example("3ano -SD")]
+#[sub_commands(menu, mine)]
 pub async fn study(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
-    let trash = ctx.data.read().await;
-    let roles = get!(> trash, MiEI, read);
-    let (ids, names) = parse_study_args(
-        args.rest(),
-        &roles,
-        &msg.author,
-        ctx,
-        msg.guild_id.ok_or("Guild id not found")?,
-        true,
-    )
-    .await;
+    let guild_id = msg.guild_id.ok_or("Guild id not found")?;
+    let miei = guild::data::<MiEI>(ctx, guild_id).await;
+    let roles = miei.read().await;
+    let (ids, names, outcomes) =
+        parse_study_args(args.rest(), &roles, &msg.author, ctx, guild_id, true).await?;
+    if let Some(s) = report(&outcomes, true) {
+        msg.channel_id.say(&ctx.http, s).await?;
+    }
+    let named = outcomes
+        .iter()
+        .filter(|o| o.named)
+        .flat_map(|o| o.refused.iter().map(|(n, _)| *n))
+        .collect::<Vec<_>>();
+    let refused = outcomes
+        .iter()
+        .flat_map(|o| o.refused.iter().copied())
+        .collect::<Vec<_>>();
+    if let Some(s) = explain_refusals(ctx, guild_id, msg.author.id, &refused, &named).await {
+        msg.channel_id.say(&ctx.http, s).await?;
+    }
     if names.is_empty() {
         msg.channel_id
             .say(&ctx.http, "Não foste adicionado(a) a nenhuma cadeira nova.")
@@ -59,23 +99,74 @@ pub async fn study(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
 }
 
 #[command]
-#[description("Permite a alguém sair das salas das cadeiras.")]
-#[usage("[CADEIRA|ANO|ANOSEMESTRE, ...]")]
+#[description("Mostra as cadeiras que estudas, por ano e semestre.")]
+#[usage("")]
+pub async fn mine(ctx: &Context, msg: &Message) -> CommandResult {
+    let guild_id = msg.guild_id.ok_or("Guild id not found")?;
+    let member = msg.member(&ctx).await?;
+    let miei = guild::data::<MiEI>(ctx, guild_id).await;
+    let roles = miei.read().await;
+    let mine = roles
+        .iter()
+        .filter(|c| {
+            roles
+                .role_by_name(c.channel)
+                .is_some_and(|r| member.roles.contains(&r))
+        })
+        .fold(BTreeMap::<_, Vec<_>>::new(), |mut acc, c| {
+            acc.entry(format!("{}ano{}semestre", c.year, c.semester))
+                .or_default()
+                .push(c.channel);
+            acc
+        });
+    if mine.is_empty() {
+        msg.channel_id
+            .say(&ctx.http, "Não estás em nenhuma cadeira.")
+            .await?;
+        return Ok(());
+    }
+    msg.channel_id
+        .send_message(
+            &ctx.http,
+            CreateMessage::new().embed(
+                CreateEmbed::new()
+                    .title(format!("Cadeiras de {}", msg.author.name))
+                    .fields(mine.iter().map(|(k, v)| (k, v.join("\n"), true)))
+                    .colour(Colour::from_rgb(0, 0, 0)),
+            ),
+        )
+        .await?;
+    Ok(())
+}
+
+#[command]
+#[description(
+    "Permite a alguém sair das salas das cadeiras. `$unstudy all` ou `$unstudy *` sai de todas."
+)]
+#[usage("[CADEIRA|PREFIXO*|ANO|ANOSEMESTRE, ...]")]
 #[example("Algebra PI")]
 #[example("1ano")]
 #[example("2ano1sem")]
+#[example("1-2ano")]
+#[example("all")]
 pub async fn unstudy(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
-    let trash = ctx.data.read().await;
-    let roles = get!(> trash, MiEI, read);
-    let (ids, names) = parse_study_args(
-        args.rest(),
-        &roles,
-        &msg.author,
-        ctx,
-        msg.guild_id.ok_or("Guild id not found")?,
-        false,
-    )
-    .await;
+    let guild_id = msg.guild_id.ok_or("Guild id not found")?;
+    let miei = guild::data::<MiEI>(ctx, guild_id).await;
+    let roles = miei.read().await;
+    let (ids, names, outcomes) = if matches!(args.rest().trim(), "*" | "all") {
+        let member = msg.member(&ctx).await?;
+        let (names, ids) = roles
+            .iter()
+            .filter_map(|c| Some((c.channel, roles.role_by_name(c.channel)?)))
+            .filter(|(_, r)| member.roles.contains(r))
+            .unzip();
+        (ids, names, Vec::new())
+    } else {
+        parse_study_args(args.rest(), &roles, &msg.author, ctx, guild_id, false).await?
+    };
+    if let Some(s) = report(&outcomes, false) {
+        msg.channel_id.say(&ctx.http, s).await?;
+    }
     if names.is_empty() {
         msg.channel_id
             .say(&ctx.http, "Não foste removido(a) de nenhuma cadeira.")
@@ -92,117 +183,319 @@ pub async fn unstudy(ctx: &Context, msg: &Message, args: Args) -> CommandResult
     Ok(())
 }
 
+/// Why something a student asked for matched no course, with what they may have meant.
+enum Unknown<'miei> {
+    Course(Vec<&'miei str>),
+    Year(Vec<String>),
+    Wildcard(Vec<&'miei str>),
+}
+
+/// What became of one of the things a student asked for.
+struct Outcome<'args, 'miei> {
+    token: &'args str,
+    /// Courses joined or left.
+    changed: Vec<&'args str>,
+    /// Courses the student already was in, when joining, or wasn't in, when leaving.
+    unchanged: Vec<&'args str>,
+    /// Courses taken out of the ones asked for before.
+    excluded: Vec<&'args str>,
+    refused: Refused<'args>,
+    unknown: Option<Unknown<'miei>>,
+    /// Whether the student named the course, rather than a year or a prefix.
+    named: bool,
+}
+
+impl<'args> Outcome<'args, '_> {
+    fn new(token: &'args str) -> Self {
+        Outcome {
+            token,
+            changed: Vec::new(),
+            unchanged: Vec::new(),
+            excluded: Vec::new(),
+            refused: Vec::new(),
+            unknown: None,
+            named: false,
+        }
+    }
+
+    /// How it went, or nothing if the courses were joined or left as asked.
+    fn describe(&self, joining: bool) -> Option<String> {
+        let mut parts = Vec::new();
+        if !self.changed.is_empty() {
+            let done = if joining {
+                "adicionado(a) a"
+            } else {
+                "removido(a) de"
+            };
+            parts.push(format!("{} {}", done, self.changed.join(", ")));
+        }
+        if !self.unchanged.is_empty() {
+            let already = if joining {
+                "já estavas em"
+            } else {
+                "não estavas em"
+            };
+            parts.push(format!("{} {}", already, self.unchanged.join(", ")));
+        }
+        if !self.excluded.is_empty() {
+            parts.push(format!("sem {}", self.excluded.join(", ")));
+        }
+        if !self.refused.is_empty() {
+            let names = self.refused.iter().map(|(n, _)| *n).collect::<Vec<_>>();
+            parts.push(format!("recusado em {}", names.join(", ")));
+        }
+        fn suggest<S: AsRef<str>>(s: &str, similar: &[S]) -> String {
+            match similar {
+                [] => s.to_string(),
+                similar => format!(
+                    "{}, querias dizer {}?",
+                    s,
+                    similar.iter().map(S::as_ref).collect::<Vec<_>>().join(", ")
+                ),
+            }
+        }
+        match &self.unknown {
+            Some(Unknown::Course(similar)) => parts.push(suggest("a cadeira não existe", similar)),
+            Some(Unknown::Year(similar)) => {
+                parts.push(suggest("esse ano ou semestre não existe", similar))
+            }
+            Some(Unknown::Wildcard(similar)) => {
+                parts.push(suggest("nenhuma cadeira começa assim", similar))
+            }
+            None => {}
+        }
+        let as_asked =
+            self.unchanged.is_empty() && self.refused.is_empty() && self.unknown.is_none();
+        (!as_asked).then(|| format!("`{}`: {}", self.token, parts.join("; ")))
+    }
+}
+
+/// Reports how each thing a student asked for went, if any of them didn't go as asked.
+fn report(outcomes: &[Outcome], joining: bool) -> Option<String> {
+    if outcomes.iter().all(|o| o.describe(joining).is_none()) {
+        return None;
+    }
+    let lines = outcomes
+        .iter()
+        .map(|o| {
+            o.describe(joining)
+                .unwrap_or_else(|| format!("`{}`: ok", o.token))
+        })
+        .collect::<Vec<_>>();
+    Some(lines.join("\n"))
+}
+
+/// The years, or the semesters of a year, as students write them.
+fn years_and_semesters(roles: &MiEI, year: &str) -> Vec<String> {
+    let mut semesters = roles
+        .semesters()
+        .filter(|(y, _)| *y == year)
+        .map(|(y, s)| format!("{}ano{}sem", y, s))
+        .collect::<Vec<_>>();
+    if semesters.is_empty() {
+        semesters = roles
+            .semesters()
+            .map(|(y, _)| format!("{}ano", y))
+            .collect();
+    }
+    semesters.sort();
+    semesters.dedup();
+    semesters
+}
+
+/// The courses an item stands for, or nothing, with why, in `outcome`.
+fn select<'args, 'miei: 'args>(
+    roles: &'miei MiEI,
+    selector: &Selector<'args>,
+    outcome: &mut Outcome<'args, 'miei>,
+) -> Vec<(&'args str, RoleId)> {
+    match *selector {
+        Selector::Wildcard(prefix) => {
+            let matches = roles.wildcard_roles(prefix).collect::<Vec<_>>();
+            if matches.is_empty() {
+                let similar = roles.similar_courses(prefix);
+                outcome.unknown = Some(Unknown::Wildcard(similar.into_iter().take(3).collect()));
+            }
+            matches
+        }
+        // Aliases become the name of their course.
+        Selector::Course(course) => match roles
+            .resolve(course)
+            .and_then(|name| Some((name, roles.role_by_name(name)?)))
+        {
+            Some(course) => {
+                outcome.named = true;
+                vec![course]
+            }
+            // Courses that look like it are only suggested, so the student retypes the one meant.
+            None => {
+                let similar = roles.similar_courses(course).into_iter().take(3).collect();
+                outcome.unknown = Some(Unknown::Course(similar));
+                Vec::new()
+            }
+        },
+        Selector::Years { from, to, semester } => {
+            // Only the years that exist, however many the student asked for.
+            let mut years = roles
+                .semesters()
+                .map(|(y, _)| y)
+                .filter(|y| y.parse().is_ok_and(|y: u32| (from..=to).contains(&y)))
+                .collect::<Vec<_>>();
+            years.sort_by_key(|y| y.parse::<u32>().ok());
+            years.dedup();
+            let found = years
+                .into_iter()
+                .flat_map(|year| {
+                    let rs = match semester {
+                        Some(sem) => roles
+                            .roles_by_year_and_semester(year, &sem.to_string())
+                            .map(|rs| rs.collect::<Vec<_>>()),
+                        None => roles.roles_by_year(year).map(|rs| rs.collect()),
+                    };
+                    rs.unwrap_or_default()
+                })
+                .collect::<Vec<_>>();
+            if found.is_empty() {
+                let similar = years_and_semesters(roles, &from.to_string());
+                outcome.unknown = Some(Unknown::Year(similar));
+            }
+            found
+        }
+    }
+}
+
+/// What becomes of a course a student asked to join or leave.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Decision {
+    /// The student gets, or loses, the role of the course.
+    Change,
+    /// The student already is in the course, when joining, or isn't, when leaving.
+    Unchanged,
+    Refused(Refusal),
+}
+
+/// Decides what becomes of each course a student with `member_roles` asked to join
+/// (`joining`) or leave.
+pub fn decide(
+    roles: &MiEI,
+    member_roles: &[RoleId],
+    selected: &[(&str, RoleId)],
+    joining: bool,
+) -> Vec<Decision> {
+    let mut courses = roles.course_count(member_roles);
+    selected
+        .iter()
+        .map(|&(name, role)| {
+            if member_roles.contains(&role) == joining {
+                return Decision::Unchanged;
+            }
+            // Restrictions only stop students from joining courses.
+            if joining
+                && let Some(restrictions) = roles.restrictions(name)
+                && let Err(why) = restrictions.check(member_roles, courses)
+            {
+                return Decision::Refused(why);
+            }
+            courses += 1;
+            Decision::Change
+        })
+        .collect()
+}
+
+/// Finds the courses a student asked to join (`joining`) or leave, and what became of each
+/// thing they asked for.
 async fn parse_study_args<'args, 'miei: 'args>(
     args: &'args str,
     roles: &'miei MiEI,
     user: &'_ User,
     ctx: &Context,
     guild_id: GuildId,
-    filter: bool,
-) -> (Vec<RoleId>, Vec<&'args str>) {
-    static REGEX: Lazy<Regex> = Lazy::new(|| {
-        RegexBuilder::new(concat!(
-            r"(",
-            r"(?P<year>\d+) *ano(( *(?P<sem>\d+)( *sem(estre)?)?)| |$)|",
-            r"(?P<wildcard>[^ *]+)\*|",
-            r"(?P<course>\S+)",
-            r")*"
-        ))
-        .case_insensitive(true)
-        .build()
-        .unwrap()
-    });
+    joining: bool,
+) -> Result<(Vec<RoleId>, Vec<&'args str>, Vec<Outcome<'args, 'miei>>), ParseError> {
+    let items = study_args::parse(args)?;
+    let mut outcomes = items
+        .iter()
+        .map(|i| Outcome::new(i.text))
+        .collect::<Vec<_>>();
+    // Each item adds courses to, or takes them out of, the ones of the items before it.
+    let mut selected = Vec::<(usize, &'args str, RoleId)>::new();
+    for (i, item) in items.iter().enumerate() {
+        for (name, role) in select(roles, &item.selector, &mut outcomes[i]) {
+            let before = selected.iter().position(|(_, _, r)| *r == role);
+            match (item.exclude, before) {
+                (true, Some(before)) => {
+                    selected.remove(before);
+                    outcomes[i].excluded.push(name);
+                }
+                (false, None) => selected.push((i, name, role)),
+                _ => {}
+            }
+        }
+    }
     let mut names = Vec::new();
     let mut ids = Vec::new();
-    let mut push = |(n, r)| {
-        ids.push(r);
-        names.push(n);
-    };
-    async fn not_has_role(
-        ctx: &Context,
-        guild_id: GuildId,
-        r: RoleId,
-        user: &User,
-        filter: bool,
-    ) -> bool {
-        !filter || !user.has_role(ctx, guild_id, r).await.unwrap_or(true)
-    }
-    let captures = REGEX.captures_iter(args).collect::<Vec<_>>();
-    for c in captures {
-        if let Some(wild) = c.name("wildcard") {
-            stream::iter(roles.wildcard_roles(wild.as_str()))
-                .filter(|r| not_has_role(ctx, guild_id, r.1, user, filter))
-                .for_each(|x| {
-                    push(x);
-                    future::ready(())
-                })
-                .await;
-        } else if let Some(course) = c.name("course") {
-            stream::iter(
-                roles
-                    .role_by_name(course.as_str())
-                    .map(|r| (course.as_str(), r)),
-            )
-            .filter(|r| not_has_role(ctx, guild_id, r.1, user, filter))
-            .for_each(|x| {
-                push(x);
-                future::ready(())
-            })
-            .await;
-        } else if let Some(year) = c.name("year") {
-            match c.name("sem") {
-                Some(sem) => {
-                    if let Some(rs) = roles.roles_by_year_and_semester(year.as_str(), sem.as_str())
-                    {
-                        stream::iter(rs)
-                            .filter(|r| not_has_role(ctx, guild_id, r.1, user, filter))
-                            .for_each(|x| {
-                                push(x);
-                                future::ready(())
-                            })
-                            .await;
-                    }
-                }
-                None => {
-                    if let Some(rs) = roles.roles_by_year(year.as_str()) {
-                        stream::iter(rs)
-                            .filter(|r| not_has_role(ctx, guild_id, r.1, user, filter))
-                            .for_each(|x| {
-                                push(x);
-                                future::ready(())
-                            })
-                            .await;
-                    }
-                }
+    let member_roles = guild_id
+        .member(ctx, user.id)
+        .await
+        .map(|m| m.roles)
+        .unwrap_or_default();
+    let courses = selected
+        .iter()
+        .map(|&(_, name, role)| (name, role))
+        .collect::<Vec<_>>();
+    let decisions = decide(roles, &member_roles, &courses, joining);
+    for ((i, name, role), decision) in selected.into_iter().zip(decisions) {
+        let outcome = &mut outcomes[i];
+        match decision {
+            Decision::Unchanged => outcome.unchanged.push(name),
+            Decision::Refused(why) => outcome.refused.push((name, why)),
+            Decision::Change => {
+                ids.push(role);
+                names.push(name);
+                outcome.changed.push(name);
             }
         }
     }
-    (ids, names)
+    Ok((ids, names, outcomes))
 }
 
 #[group]
 #[prefixes("courses")]
-#[commands(mk, rm, mv, rename, deprecate, list, add_uc)]
+#[commands(
+    mk, rm, mv, rename, deprecate, list, add_uc, rollover, import, export, doctor, restore, info,
+    set_info, alias, unalias, restrict, approve, expiry, expire, stats
+)]
 struct Courses;
 
 #[command]
 #[description("Cria salas das cadeiras especificadas, associadas ao ano especificado.")]
-#[usage("ano semestre [CADEIRA, ...]")]
+#[usage("ano semestre [CADEIRA, ...] [--dry-run]")]
 #[min_args(3)]
 #[required_permissions(ADMINISTRATOR)]
 pub async fn mk(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
-    let trash = ctx.data.read().await;
-    let mut roles = get!(> trash, MiEI, write);
-    let mut args = args.raw();
+    let guild_id = msg.guild_id.ok_or("Guild id not found")?;
+    let miei = guild::data::<MiEI>(ctx, guild_id).await;
+    let (dry_run, args) = dry_run_flag(&args);
+    let mut roles = Editing::start(&miei, dry_run).await;
+    let mut args = args.into_iter();
     let year = args.next();
     let semester = args.next();
-    if let (Some(y), Some(s), Some(g)) = (year, semester, msg.guild_id) {
+    if let (Some(y), Some(s)) = (year, semester) {
         let mut new_roles = Vec::new();
+        let mut failed = Vec::new();
         for course in args {
-            if let Some(c) = roles.create_role(ctx, y, s, course, g).await? {
-                new_roles.push(c);
+            match roles.create_role(ctx, y, s, course, guild_id).await {
+                Ok(Some(c)) => new_roles.push(c),
+                Ok(None) => {}
+                Err(e) => {
+                    log!("{}", e);
+                    failed.push(format!("{}: {}", course, e));
+                }
             }
         }
+        if let Some(plan) = roles.into_plan() {
+            return show_plan(ctx, msg, plan, failed).await;
+        }
         if new_roles.is_empty() {
             msg.channel_id
                 .say(&ctx.http, "Não foram criadas novas cadeiras.")
@@ -215,55 +508,110 @@ pub async fn mk(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
                 )
                 .await?;
         }
+        if !failed.is_empty() {
+            msg.channel_id
+                .say(&ctx.http, format!("Falhas:\n{}", failed.join("\n")))
+                .await?;
+        }
     }
     Ok(())
 }
 
 #[command]
-#[description("Remove salas das cadeiras especificadas.")]
-#[usage("[CADEIRA, ...]")]
+#[description(
+    "Remove salas das cadeiras especificadas, guardando primeiro as mensagens dos canais num \
+    arquivo."
+)]
+#[usage("[CADEIRA, ...] [--dry-run]")]
 #[required_permissions(ADMINISTRATOR)]
 pub async fn rm(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
-    let trash = ctx.data.read().await;
-    let mut roles = get!(> trash, MiEI, write);
-    if let Some(guild) = msg.guild_id {
-        let mut rm_roles = Vec::new();
-        for course in args.raw() {
-            if let Ok(c) = roles.remove_role(course, ctx, guild).await {
-                rm_roles.push(c);
+    let guild_id = msg.guild_id.ok_or("Guild id not found")?;
+    let miei = guild::data::<MiEI>(ctx, guild_id).await;
+    let (dry_run, args) = dry_run_flag(&args);
+    let mut rm_roles = Vec::new();
+    let mut failed = Vec::new();
+    // Archiving can take minutes, so the courses are only locked to delete them once it's done.
+    let mut archived = HashMap::new();
+    let mut not_archived = HashSet::new();
+    if !dry_run {
+        let courses = {
+            let roles = miei.read().await;
+            args.iter()
+                .filter_map(|&c| Some((c, roles.course(c)?.channels.clone())))
+                .collect::<Vec<_>>()
+        };
+        for (course, channels) in courses {
+            match archive_course(ctx, guild_id, &course.to_uppercase(), &channels).await {
+                Ok(()) => {
+                    archived.insert(course, channels);
+                }
+                Err(e) => {
+                    log!("{:#}", e);
+                    failed.push(format!("{}: {:#}", course, e));
+                    not_archived.insert(course);
+                }
             }
         }
-        if rm_roles.is_empty() {
-            msg.channel_id
-                .say(&ctx.http, "Não foram removidas cadeiras.")
-                .await?;
-        } else {
-            msg.channel_id
-                .say(
-                    &ctx.http,
-                    format!("Cadeiras removidas: {}", rm_roles.join(" ")),
-                )
-                .await?;
+    }
+    let mut roles = Editing::start(&miei, dry_run).await;
+    for course in args {
+        if not_archived.contains(course) {
+            continue;
+        }
+        let channels = archived.get(course).map_or(&[][..], Vec::as_slice);
+        match roles.remove_role(course, channels, ctx, guild_id).await {
+            Ok(c) => rm_roles.push(c),
+            Err(e) => {
+                log!("{:#}", e);
+                failed.push(format!("{}: {:#}", course, e));
+            }
         }
     }
+    if let Some(plan) = roles.into_plan() {
+        return show_plan(ctx, msg, plan, failed).await;
+    }
+    if rm_roles.is_empty() {
+        msg.channel_id
+            .say(&ctx.http, "Não foram removidas cadeiras.")
+            .await?;
+    } else {
+        msg.channel_id
+            .say(
+                &ctx.http,
+                format!("Cadeiras removidas: {}", rm_roles.join(" ")),
+            )
+            .await?;
+    }
+    if !failed.is_empty() {
+        msg.channel_id
+            .say(&ctx.http, format!("Falhas:\n{}", failed.join("\n")))
+            .await?;
+    }
     Ok(())
 }
 
 #[command]
 #[description("Move e renomeia salas da cadeira especificada.")]
-#[usage("cadeira ano_novo semestre_novo [NOME_NOVO]")]
+#[usage("cadeira ano_novo semestre_novo [NOME_NOVO] [--dry-run]")]
 #[min_args(3)]
 #[required_permissions(ADMINISTRATOR)]
 pub async fn mv(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
-    let trash = ctx.data.read().await;
-    let mut roles = get!(> trash, MiEI, write);
-    let mut args = args.raw();
+    let guild_id = msg.guild_id.ok_or("Guild id not found")?;
+    let miei = guild::data::<MiEI>(ctx, guild_id).await;
+    let (dry_run, args) = dry_run_flag(&args);
+    let mut roles = Editing::start(&miei, dry_run).await;
+    let mut args = args.into_iter();
     let course = args.next();
     let new_year = args.next();
     let new_semester = args.next();
-    if let (Some(c), Some(y), Some(s), Some(g)) = (course, new_year, new_semester, msg.guild_id) {
+    if let (Some(c), Some(y), Some(s)) = (course, new_year, new_semester) {
         let new_name = args.next().filter(|&n| !n.eq_ignore_ascii_case(c));
-        match roles.move_course(c, y, s, new_name, ctx, g).await {
+        let moved = roles.move_course(c, y, s, new_name, ctx, guild_id).await;
+        if let Some(plan) = roles.into_plan() {
+            let failed = moved.err().map(|e| e.to_string()).into_iter().collect();
+            return show_plan(ctx, msg, plan, failed).await;
+        }
+        match moved {
             Ok(nc) => {
                 msg.channel_id
                     .say(
@@ -285,17 +633,24 @@ pub async fn mv(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
 
 #[command]
 #[description("Renomeia salas da cadeira especificada.")]
-#[usage("cadeira nome_novo")]
+#[usage("cadeira nome_novo [--dry-run]")]
 #[min_args(2)]
 #[required_permissions(ADMINISTRATOR)]
 pub async fn rename(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
-    let trash = ctx.data.read().await;
-    let mut roles = get!(> trash, MiEI, write);
-    let mut args = args.raw();
+    let guild_id = msg.guild_id.ok_or("Guild id not found")?;
+    let miei = guild::data::<MiEI>(ctx, guild_id).await;
+    let (dry_run, args) = dry_run_flag(&args);
+    let mut roles = Editing::start(&miei, dry_run).await;
+    let mut args = args.into_iter();
     let course = args.next();
     let new_name = args.next();
-    if let (Some(c), Some(n), Some(g)) = (course, new_name, msg.guild_id) {
-        match roles.rename_course(c, n, ctx, g).await {
+    if let (Some(c), Some(n)) = (course, new_name) {
+        let renamed = roles.rename_course(c, n, ctx, guild_id).await;
+        if let Some(plan) = roles.into_plan() {
+            let failed = renamed.err().map(|e| e.to_string()).into_iter().collect();
+            return show_plan(ctx, msg, plan, failed).await;
+        }
+        match renamed {
             Ok(nc) => {
                 msg.channel_id
                     .say(&ctx.http, format!("Cadeira renomeada: {} -> {}", c, nc))
@@ -314,60 +669,193 @@ pub async fn rename(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
 
 #[command]
 #[description("Descontinua salas das cadeiras especificadas.")]
-#[usage("[CADEIRA, ...]")]
+#[usage("[CADEIRA, ...] [--dry-run]")]
 #[required_permissions(ADMINISTRATOR)]
 pub async fn deprecate(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
-    let trash = ctx.data.read().await;
-    let mut roles = get!(> trash, MiEI, write);
-    if let Some(g) = msg.guild_id {
-        let mut deprecated_courses = Vec::new();
-        for course in args.raw() {
-            if let Ok(c) = roles.deprecate_course(course, ctx, g).await {
-                deprecated_courses.push(c);
+    let guild_id = msg.guild_id.ok_or("Guild id not found")?;
+    let miei = guild::data::<MiEI>(ctx, guild_id).await;
+    let (dry_run, args) = dry_run_flag(&args);
+    let mut roles = Editing::start(&miei, dry_run).await;
+    let mut deprecated_courses = Vec::new();
+    let mut failed = Vec::new();
+    for course in args {
+        match roles.deprecate_course(course, ctx, guild_id).await {
+            Ok(c) => deprecated_courses.push(c),
+            Err(e) => {
+                log!("{}", e);
+                failed.push(format!("{}: {}", course, e));
             }
         }
-        if deprecated_courses.is_empty() {
-            msg.channel_id
-                .say(&ctx.http, "Não foram descontinuadas cadeiras.")
-                .await?;
-        } else {
+    }
+    if let Some(plan) = roles.into_plan() {
+        return show_plan(ctx, msg, plan, failed).await;
+    }
+    if deprecated_courses.is_empty() {
+        msg.channel_id
+            .say(&ctx.http, "Não foram descontinuadas cadeiras.")
+            .await?;
+    } else {
+        msg.channel_id
+            .say(
+                &ctx.http,
+                format!("Cadeiras descontinuadas: {}", deprecated_courses.join(" ")),
+            )
+            .await?;
+    }
+    if !failed.is_empty() {
+        msg.channel_id
+            .say(&ctx.http, format!("Falhas:\n{}", failed.join("\n")))
+            .await?;
+    }
+    Ok(())
+}
+
+#[command]
+#[description("Recupera uma cadeira descontinuada, colocando-a no ano e semestre especificados.")]
+#[usage("CADEIRA ano semestre")]
+#[example("CP 3 1")]
+#[num_args(3)]
+#[required_permissions(ADMINISTRATOR)]
+pub async fn restore(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = msg.guild_id.ok_or("Guild id not found")?;
+    let course = args.single::<String>()?;
+    let year = args.single::<String>()?;
+    let semester = args.single::<String>()?;
+    let miei = guild::data::<MiEI>(ctx, guild_id).await;
+    let mut roles = miei.write().await;
+    match roles
+        .restore_course(&course, &year, &semester, ctx, guild_id)
+        .await
+    {
+        Ok(c) => {
             msg.channel_id
                 .say(
                     &ctx.http,
-                    format!("Cadeiras descontinuadas: {}", deprecated_courses.join(" ")),
+                    format!(
+                        "Cadeira recuperada: {} -> {}ano{}semestre",
+                        c, year, semester
+                    ),
                 )
                 .await?;
         }
+        Err(e) => {
+            log!("{}", e);
+            msg.channel_id
+                .say(&ctx.http, format!("Não foram recuperadas cadeiras.\n{}", e))
+                .await?;
+        }
     }
     Ok(())
 }
 
 #[command]
 #[description("Add channel to existing course.")]
-#[usage("CADEIRA NEW_CHANNEL")]
+#[usage("CADEIRA NEW_CHANNEL [--dry-run]")]
 #[min_args(2)]
 #[required_permissions(ADMINISTRATOR)]
 pub async fn add_uc(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
-    let trash = ctx.data.read().await;
-    let mut roles = get!(> trash, MiEI, write);
-    let guild = msg.guild_id.ok_or("not in a guild")?;
-    let mut args = args.raw();
-    let course = args.next().unwrap();
-    let new_channel = args.next().unwrap();
+    let guild_id = msg.guild_id.ok_or("Guild id not found")?;
+    let miei = guild::data::<MiEI>(ctx, guild_id).await;
+    let (dry_run, args) = dry_run_flag(&args);
+    let mut roles = Editing::start(&miei, dry_run).await;
+    let mut args = args.into_iter();
+    let course = args.next().ok_or("Missing course")?;
+    let new_channel = args.next().ok_or("Missing channel name")?;
     roles
-        .add_channel_to_course(ctx, guild, course, new_channel)
+        .add_channel_to_course(ctx, guild_id, course, new_channel)
         .await?;
+    if let Some(plan) = roles.into_plan() {
+        return show_plan(ctx, msg, plan, Vec::new()).await;
+    }
     msg.channel_id.say(ctx, "added").await?;
 
     Ok(())
 }
 
+/// The courses a command changes: for a dry run a copy, taken under the read lock, otherwise
+/// the courses themselves, under the write lock.
+enum Editing<'a> {
+    DryRun(MiEI),
+    Locked(RwLockWriteGuard<'a, MiEI>),
+}
+
+impl<'a> Editing<'a> {
+    async fn start(miei: &'a RwLock<MiEI>, dry_run: bool) -> Self {
+        if dry_run {
+            Editing::DryRun(miei.read().await.dry_run())
+        } else {
+            Editing::Locked(miei.write().await)
+        }
+    }
+
+    /// The courses with the steps of the dry run, if it was one.
+    fn into_plan(self) -> Option<MiEI> {
+        match self {
+            Editing::DryRun(plan) => Some(plan),
+            Editing::Locked(_) => None,
+        }
+    }
+}
+
+impl Deref for Editing<'_> {
+    type Target = MiEI;
+
+    fn deref(&self) -> &MiEI {
+        match self {
+            Editing::DryRun(miei) => miei,
+            Editing::Locked(miei) => miei,
+        }
+    }
+}
+
+impl DerefMut for Editing<'_> {
+    fn deref_mut(&mut self) -> &mut MiEI {
+        match self {
+            Editing::DryRun(miei) => miei,
+            Editing::Locked(miei) => miei,
+        }
+    }
+}
+
+/// Takes `--dry-run` out of the arguments, telling whether it was there.
+fn dry_run_flag(args: &Args) -> (bool, Vec<&str>) {
+    let (flags, args) = args.raw().partition::<Vec<_>, _>(|a| *a == "--dry-run");
+    (!flags.is_empty(), args)
+}
+
+/// Shows what a `--dry-run` would have done to the guild, and what would have failed.
+async fn show_plan(
+    ctx: &Context,
+    msg: &Message,
+    mut plan: MiEI,
+    failed: Vec<String>,
+) -> CommandResult {
+    let steps = plan.take_plan();
+    if steps.is_empty() {
+        msg.channel_id
+            .say(&ctx.http, "Simulação: nada seria alterado.")
+            .await?;
+    } else {
+        msg.channel_id
+            .say(&ctx.http, "Simulação, nada foi alterado:")
+            .await?;
+        say_code_blocks(ctx, msg.channel_id, "diff", steps).await?;
+    }
+    if !failed.is_empty() {
+        msg.channel_id
+            .say(&ctx.http, format!("Falhas:\n{}", failed.join("\n")))
+            .await?;
+    }
+    Ok(())
+}
+
 #[command]
 #[description("Lista as cadeiras disponíveis.")]
 #[usage("")]
 pub async fn list(ctx: &Context, msg: &Message) -> CommandResult {
-    let trash = ctx.data.read().await;
-    let roles = get!(> trash, MiEI, read);
+    let guild_id = msg.guild_id.ok_or("Guild id not found")?;
+    let miei = guild::data::<MiEI>(ctx, guild_id).await;
+    let roles = miei.read().await;
 
     msg.channel_id
         .send_message(
@@ -377,7 +865,9 @@ pub async fn list(ctx: &Context, msg: &Message) -> CommandResult {
                     .title("Informação sobre as cadeiras disponíveis")
                     .description(
                         "`$study CADEIRA` junta-te às salas das cadeiras.
-`$study Xano` junta-te a todas as cadeiras de um ano.",
+`$study Xano` junta-te a todas as cadeiras de um ano.
+`$study mine` mostra as cadeiras em que estás.
+`$courses info CADEIRA` mostra a informação sobre uma cadeira.",
                     )
                     .fields(
                         roles
@@ -387,6 +877,12 @@ pub async fn list(ctx: &Context, msg: &Message) -> CommandResult {
                                     .entry(format!("{}ano{}semestre", c.year, c.semester))
                                     .or_default();
                                 s.push_str(c.channel);
+                                if let Some(full_name) =
+                                    roles.info(c.channel).and_then(|i| i.full_name.as_ref())
+                                {
+                                    s.push_str(" - ");
+                                    s.push_str(full_name);
+                                }
                                 s.push('\n');
                                 acc
                             })
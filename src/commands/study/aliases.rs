@@ -0,0 +1,68 @@
+//! Other names students can use for the courses.
+
+use crate::{channels::MiEI, guild};
+use serenity::{
+    framework::standard::{Args, CommandResult, macros::command},
+    model::channel::Message,
+    prelude::*,
+};
+
+#[command]
+#[description(
+    "Acrescenta outros nomes a uma cadeira, que podem ser usados no $study. Sem nomes, mostra \
+    os que a cadeira tem."
+)]
+#[usage("CADEIRA [NOME, ...]")]
+#[example("ALGEBRA-LINEAR ALGEBRA ALC")]
+#[min_args(1)]
+#[required_permissions(ADMINISTRATOR)]
+pub async fn alias(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = msg.guild_id.ok_or("Guild id not found")?;
+    let course = args.single::<String>()?.to_uppercase();
+    let miei = guild::data::<MiEI>(ctx, guild_id).await;
+    let mut roles = miei.write().await;
+    if roles.course(&course).is_none() {
+        msg.channel_id
+            .say(&ctx.http, format!("A cadeira {} não existe.", course))
+            .await?;
+        return Ok(());
+    }
+    let mut failed = Vec::new();
+    for alias in args.raw() {
+        if let Err(e) = roles.add_alias(&course, alias) {
+            failed.push(e.to_string());
+        }
+    }
+    let aliases = roles.aliases(&course);
+    let mut reply = if aliases.is_empty() {
+        format!("A cadeira {} não tem outros nomes.", course)
+    } else {
+        format!("{} também é conhecida por {}.", course, aliases.join(", "))
+    };
+    if !failed.is_empty() {
+        reply.push_str(&format!("\nFalhas:\n{}", failed.join("\n")));
+    }
+    msg.channel_id.say(&ctx.http, reply).await?;
+    Ok(())
+}
+
+#[command]
+#[description("Remove outros nomes das cadeiras.")]
+#[usage("[NOME, ...]")]
+#[example("ALC")]
+#[min_args(1)]
+#[required_permissions(ADMINISTRATOR)]
+pub async fn unalias(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let guild_id = msg.guild_id.ok_or("Guild id not found")?;
+    let miei = guild::data::<MiEI>(ctx, guild_id).await;
+    let mut roles = miei.write().await;
+    let lines = args
+        .raw()
+        .map(|alias| match roles.remove_alias(alias) {
+            Ok(course) => format!("{} deixou de ser {}.", alias.to_uppercase(), course),
+            Err(e) => e.to_string(),
+        })
+        .collect::<Vec<_>>();
+    msg.channel_id.say(&ctx.http, lines.join("\n")).await?;
+    Ok(())
+}
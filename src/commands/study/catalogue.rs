@@ -0,0 +1,294 @@
+//! Keeping the courses in sync with a catalogue file.
+
+use crate::{
+    catalogue::{Catalogue, Entry, Format},
+    channels::{MiEI, course_style},
+    config::CourseStyle,
+    guild, log,
+    util::{confirm, say_code_blocks},
+};
+use serenity::{
+    all::{CreateAttachment, CreateMessage},
+    framework::standard::{CommandResult, macros::command},
+    model::{
+        channel::Message,
+        id::{ChannelId, GuildId},
+    },
+    prelude::*,
+};
+use std::collections::{HashMap, HashSet};
+
+/// The names of the channels the templates of a course make for a topic of the course.
+fn template_names(style: &CourseStyle, year: &str, code: &str, topic: &str) -> Vec<String> {
+    style
+        .templates_for(year, code)
+        .iter()
+        .map(|t| t.name_for(topic))
+        .collect()
+}
+
+/// What has to be done to make the courses match a catalogue.
+#[derive(Default)]
+struct Reconciliation<'c> {
+    create: Vec<&'c Entry>,
+    /// The extra channels missing from existing courses, with the names of their channels.
+    channels: Vec<(&'c str, &'c str, Vec<String>)>,
+    names: Vec<(&'c str, &'c str)>,
+    misplaced: Vec<String>,
+    unlisted: Vec<String>,
+}
+
+impl<'c> Reconciliation<'c> {
+    fn new(
+        catalogue: &'c Catalogue,
+        miei: &MiEI,
+        channel_names: &HashMap<ChannelId, String>,
+        style: &CourseStyle,
+    ) -> Self {
+        let mut r = Reconciliation::default();
+        for entry in &catalogue.courses {
+            let Some(course) = miei.course(&entry.code) else {
+                r.create.push(entry);
+                continue;
+            };
+            let (year, semester) = miei
+                .get_year_semester_names(&entry.code)
+                .unwrap_or_else(|| (entry.year.clone(), entry.semester.clone()));
+            if year != entry.year || semester != entry.semester {
+                r.misplaced.push(format!(
+                    "{} está em {}ano{}semestre mas o catálogo diz {}ano{}semestre",
+                    entry.code, year, semester, entry.year, entry.semester
+                ));
+            }
+            let existing = course
+                .channels
+                .iter()
+                .filter_map(|c| channel_names.get(c))
+                .map(|n| n.to_lowercase())
+                .collect::<HashSet<_>>();
+            for c in &entry.channels {
+                let names = template_names(style, &year, &entry.code, c);
+                if !names.iter().all(|n| existing.contains(&n.to_lowercase())) {
+                    r.channels.push((&entry.code, c, names));
+                }
+            }
+            if let Some(name) = &entry.name
+                && course.info.full_name.as_ref() != Some(name)
+            {
+                r.names.push((&entry.code, name));
+            }
+        }
+        let listed = catalogue
+            .courses
+            .iter()
+            .map(|e| e.code.as_str())
+            .collect::<HashSet<_>>();
+        r.unlisted = miei
+            .iter()
+            .filter(|c| !listed.contains(c.channel))
+            .map(|c| format!("{} ({}ano{}semestre)", c.channel, c.year, c.semester))
+            .collect();
+        r.unlisted.sort();
+        r
+    }
+
+    fn has_changes(&self) -> bool {
+        !(self.create.is_empty() && self.channels.is_empty() && self.names.is_empty())
+    }
+
+    fn preview(&self, style: &CourseStyle) -> Vec<String> {
+        let created = self.create.iter().map(|e| {
+            format!(
+                "+ {} {}ano{}semestre{}",
+                e.code,
+                e.year,
+                e.semester,
+                e.name
+                    .as_ref()
+                    .map(|n| format!(" ({})", n))
+                    .unwrap_or_default()
+            )
+        });
+        let created_channels = self.create.iter().flat_map(|e| {
+            e.channels.iter().map(move |c| {
+                let names = template_names(style, &e.year, &e.code, c);
+                format!("+ {}: {}", e.code, names.join(" "))
+            })
+        });
+        let channels = self
+            .channels
+            .iter()
+            .map(|(code, _, names)| format!("+ {}: {}", code, names.join(" ")));
+        let names = self
+            .names
+            .iter()
+            .map(|(code, name)| format!("+ {}: {}", code, name));
+        let misplaced = self.misplaced.iter().map(|m| format!("! {}", m));
+        let unlisted = self
+            .unlisted
+            .iter()
+            .map(|c| format!("! {} não está no catálogo", c));
+        created
+            .chain(created_channels)
+            .chain(channels)
+            .chain(names)
+            .chain(misplaced)
+            .chain(unlisted)
+            .collect()
+    }
+
+    async fn apply(&self, miei: &mut MiEI, ctx: &Context, guild: GuildId) -> Vec<String> {
+        let mut failed = Vec::new();
+        for entry in &self.create {
+            match miei
+                .create_role(ctx, &entry.year, &entry.semester, &entry.code, guild)
+                .await
+            {
+                Ok(None) => failed.push(format!("{}: já existe", entry.code)),
+                Ok(Some(_)) => {
+                    for c in &entry.channels {
+                        if let Err(e) = miei.add_channel_to_course(ctx, guild, &entry.code, c).await
+                        {
+                            failed.push(format!("{} ({}): {}", entry.code, c, e));
+                        }
+                    }
+                    if let Some(name) = &entry.name
+                        && let Err(e) =
+                            miei.edit_info(&entry.code, |i| i.full_name = Some(name.clone()))
+                    {
+                        failed.push(format!("{}: {}", entry.code, e));
+                    }
+                }
+                Err(e) => failed.push(format!("{}: {}", entry.code, e)),
+            }
+        }
+        for (code, c, _) in &self.channels {
+            if let Err(e) = miei.add_channel_to_course(ctx, guild, code, c).await {
+                failed.push(format!("{} ({}): {}", code, c, e));
+            }
+        }
+        for (code, name) in &self.names {
+            if let Err(e) = miei.edit_info(code, |i| i.full_name = Some(name.to_string())) {
+                failed.push(format!("{}: {}", code, e));
+            }
+        }
+        failed
+    }
+}
+
+async fn fetch_channel_names(
+    ctx: &Context,
+    guild: GuildId,
+) -> serenity::Result<HashMap<ChannelId, String>> {
+    Ok(guild
+        .channels(&ctx.http)
+        .await?
+        .into_iter()
+        .map(|(id, c)| (id, c.name))
+        .collect())
+}
+
+#[command]
+#[description(
+    "Sincroniza as cadeiras com um catálogo em anexo (TOML ou CSV). Cria as cadeiras e \
+    canais que faltam e indica as cadeiras que não estão no catálogo."
+)]
+#[usage("(anexo: catalogo.toml | catalogo.csv)")]
+#[required_permissions(ADMINISTRATOR)]
+pub async fn import(ctx: &Context, msg: &Message) -> CommandResult {
+    let guild_id = msg.guild_id.ok_or("Guild id not found")?;
+    let attachment = msg
+        .attachments
+        .first()
+        .ok_or("Envia o catálogo como anexo")?;
+    let format = Format::from_file_name(&attachment.filename)
+        .ok_or("O catálogo tem de ser um ficheiro .toml ou .csv")?;
+    let catalogue = Catalogue::parse(&String::from_utf8(attachment.download().await?)?, format)?;
+    let channel_names = fetch_channel_names(ctx, guild_id).await?;
+    let style = course_style(ctx, guild_id).await;
+    let miei = guild::data::<MiEI>(ctx, guild_id).await;
+    let reconciliation =
+        Reconciliation::new(&catalogue, &*miei.read().await, &channel_names, &style);
+    let preview = reconciliation.preview(&style);
+    if preview.is_empty() {
+        msg.channel_id
+            .say(&ctx.http, "As cadeiras já estão de acordo com o catálogo.")
+            .await?;
+        return Ok(());
+    }
+    let prompt = say_code_blocks(ctx, msg.channel_id, "diff", preview.clone())
+        .await?
+        .ok_or("Empty preview")?;
+    if !reconciliation.has_changes() {
+        return Ok(());
+    }
+    let prompt = prompt.reply(&ctx.http, "Aplicar estas alterações?").await?;
+    if !confirm(ctx, &prompt, msg.author.id).await? {
+        msg.channel_id
+            .say(&ctx.http, "Importação cancelada.")
+            .await?;
+        return Ok(());
+    }
+
+    let mut roles = miei.write().await;
+    // The courses may have changed while waiting for the confirmation.
+    let style = course_style(ctx, guild_id).await;
+    let channel_names = fetch_channel_names(ctx, guild_id).await?;
+    if Reconciliation::new(&catalogue, &roles, &channel_names, &style).preview(&style) != preview {
+        msg.channel_id
+            .say(
+                &ctx.http,
+                "As cadeiras mudaram entretanto, nada foi alterado. Volta a importar o catálogo.",
+            )
+            .await?;
+        return Ok(());
+    }
+    let failed = reconciliation.apply(&mut roles, ctx, guild_id).await;
+    drop(roles);
+    if failed.is_empty() {
+        msg.channel_id.say(&ctx.http, "Catálogo importado.").await?;
+    } else {
+        log!("Catalogue import failures: {:?}", failed);
+        msg.channel_id
+            .say(&ctx.http, "Catálogo importado, com falhas:")
+            .await?;
+        say_code_blocks(ctx, msg.channel_id, "", failed).await?;
+    }
+    Ok(())
+}
+
+#[command]
+#[description(
+    "Exporta todas as cadeiras, incluindo as descontinuadas, num catálogo que pode ser \
+    importado com `$courses import`."
+)]
+#[usage("")]
+#[required_permissions(ADMINISTRATOR)]
+pub async fn export(ctx: &Context, msg: &Message) -> CommandResult {
+    let guild_id = msg.guild_id.ok_or("Guild id not found")?;
+    let channel_names = fetch_channel_names(ctx, guild_id).await?;
+    let role_names = guild_id
+        .roles(&ctx.http)
+        .await?
+        .into_iter()
+        .map(|(id, r)| (id, r.name))
+        .collect();
+    let style = course_style(ctx, guild_id).await;
+    let catalogue = {
+        let miei = guild::data::<MiEI>(ctx, guild_id).await;
+        let miei = miei.read().await;
+        Catalogue::from_courses(&miei, &style, &channel_names, &role_names)
+    };
+    msg.channel_id
+        .send_message(
+            &ctx.http,
+            CreateMessage::new()
+                .content(format!("{} cadeiras exportadas.", catalogue.courses.len()))
+                .add_file(CreateAttachment::bytes(
+                    catalogue.to_toml()?.into_bytes(),
+                    "cadeiras.toml",
+                )),
+        )
+        .await?;
+    Ok(())
+}
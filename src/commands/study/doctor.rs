@@ -0,0 +1,435 @@
+//! Finding and repairing the differences between the courses and what actually exists in the
+//! guild, which appear when roles or channels are changed by hand.
+
+use crate::{
+    channels::{MiEI, course_overwrites, course_role, course_style},
+    config::CourseStyle,
+    guild::{self, GuildData},
+    log,
+    util::{confirm, is_not_found, say_code_blocks},
+};
+use anyhow::anyhow;
+use serenity::{
+    all::{CreateChannel, EditChannel},
+    framework::standard::{CommandResult, macros::command},
+    model::{
+        channel::{
+            ChannelType, GuildChannel, Message, PermissionOverwrite, PermissionOverwriteType,
+        },
+        guild::Role,
+        id::{ChannelId, GuildId, RoleId},
+        permissions::Permissions,
+    },
+    prelude::*,
+};
+use std::collections::{HashMap, HashSet};
+
+/// A course whose role was deleted.
+struct MissingRole {
+    course: String,
+    year: String,
+    /// A role with the name of the course that isn't used by any course, which can take its place
+    /// instead of creating a new one.
+    replacement: Option<RoleId>,
+}
+
+/// A course with channels that were deleted.
+struct MissingChannels {
+    course: String,
+    year: String,
+    channels: Vec<ChannelId>,
+    category: bool,
+}
+
+/// A course channel that no course knows about.
+struct Orphan {
+    channel: ChannelId,
+    name: String,
+    /// The course the channel can be given back to, found by its category or its name.
+    course: Option<String>,
+}
+
+/// Everything that doesn't match between the courses and the guild.
+#[derive(Default)]
+struct Diagnosis {
+    missing_roles: Vec<MissingRole>,
+    missing_channels: Vec<MissingChannels>,
+    /// Deleted channels of deprecated courses, by the role of the course.
+    deprecated_channels: Vec<(RoleId, Vec<ChannelId>)>,
+    deprecated_roles: Vec<RoleId>,
+    orphans: Vec<Orphan>,
+    unmanaged: Vec<String>,
+}
+
+impl Diagnosis {
+    fn new(
+        miei: &MiEI,
+        style: &CourseStyle,
+        guild: GuildId,
+        channels: &HashMap<ChannelId, GuildChannel>,
+        roles: &HashMap<RoleId, Role>,
+    ) -> Self {
+        let mut d = Diagnosis::default();
+        let tracked_channels = miei
+            .courses()
+            .map(|(_, _, _, c)| c)
+            .chain(miei.deprecated_courses())
+            .flat_map(|c| &c.channels)
+            .collect::<HashSet<_>>();
+        let tracked_roles = miei
+            .courses()
+            .map(|(_, _, _, c)| c)
+            .chain(miei.deprecated_courses())
+            .map(|c| c.role)
+            .collect::<HashSet<_>>();
+        let dead = |c: &[ChannelId]| {
+            c.iter()
+                .filter(|c| !channels.contains_key(c))
+                .copied()
+                .collect::<Vec<_>>()
+        };
+
+        let mut replacements = HashSet::new();
+        for (year, _, name, course) in miei.courses() {
+            if !roles.contains_key(&course.role) {
+                let replacement = roles
+                    .values()
+                    .find(|r| r.name == name && !tracked_roles.contains(&r.id))
+                    .map(|r| r.id);
+                replacements.extend(replacement);
+                d.missing_roles.push(MissingRole {
+                    course: name.to_string(),
+                    year: year.to_string(),
+                    replacement,
+                });
+            }
+            let missing = dead(&course.channels);
+            if !missing.is_empty() {
+                d.missing_channels.push(MissingChannels {
+                    course: name.to_string(),
+                    year: year.to_string(),
+                    category: course.channels.first().is_some_and(|c| missing.contains(c)),
+                    channels: missing,
+                });
+            }
+        }
+        for course in miei.deprecated_courses() {
+            if !roles.contains_key(&course.role) {
+                d.deprecated_roles.push(course.role);
+            }
+            let missing = dead(&course.channels);
+            if !missing.is_empty() {
+                d.deprecated_channels.push((course.role, missing));
+            }
+        }
+
+        // The names the templates of the courses give their channels, lowercase like Discord
+        // keeps them, as what goes before and after the name of the course.
+        let template_names = miei
+            .courses()
+            .map(|(year, _, name, _)| (name, style.templates_for(year, name)))
+            .collect::<Vec<_>>();
+        let patterns = template_names
+            .iter()
+            .flat_map(|(_, templates)| templates)
+            .filter_map(|t| {
+                let (before, after) = t.name.split_once("{name}")?;
+                Some((before.to_lowercase(), after.to_lowercase()))
+            })
+            .collect::<HashSet<_>>();
+        d.orphans = channels
+            .values()
+            .filter(|c| {
+                matches!(c.kind, ChannelType::Text | ChannelType::Forum)
+                    && !tracked_channels.contains(&c.id)
+            })
+            .filter(|c| {
+                let name = c.name.to_lowercase();
+                patterns.iter().any(|(before, after)| {
+                    name.strip_prefix(before.as_str())
+                        .and_then(|n| n.strip_suffix(after.as_str()))
+                        .is_some_and(|n| !n.is_empty())
+                })
+            })
+            .map(|c| {
+                let by_category = c.parent_id.and_then(|p| {
+                    miei.courses()
+                        .find(|(_, _, _, course)| course.channels.first() == Some(&p))
+                        .map(|(_, _, name, _)| name.to_string())
+                });
+                let by_name = || {
+                    template_names
+                        .iter()
+                        .find(|(name, templates)| {
+                            templates
+                                .iter()
+                                .any(|t| t.name_for(name).to_lowercase() == c.name.to_lowercase())
+                        })
+                        .map(|(name, _)| name.to_string())
+                };
+                Orphan {
+                    channel: c.id,
+                    name: c.name.clone(),
+                    course: by_category.or_else(by_name),
+                }
+            })
+            .collect();
+        d.orphans.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let everyone = RoleId::new(guild.get());
+        d.unmanaged = roles
+            .values()
+            .filter(|r| {
+                r.id != everyone
+                    && !r.managed
+                    && !tracked_roles.contains(&r.id)
+                    && !replacements.contains(&r.id)
+            })
+            .filter(|r| style.is_course_colour(r.colour.0 as u64) || miei.role_exists(&r.name))
+            .map(|r| r.name.clone())
+            .collect();
+        d.unmanaged.sort();
+        d
+    }
+
+    fn has_repairs(&self) -> bool {
+        !(self.missing_roles.is_empty()
+            && self.missing_channels.is_empty()
+            && self.deprecated_channels.is_empty()
+            && self.orphans.iter().all(|o| o.course.is_none()))
+    }
+
+    fn report(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        for m in &self.missing_roles {
+            lines.push(format!("- {}: o cargo já não existe", m.course));
+            lines.push(match m.replacement {
+                Some(r) => format!("+ {}: passa a usar o cargo {} que já existe", m.course, r),
+                None => format!("+ {}: é criado um cargo novo", m.course),
+            });
+        }
+        for m in &self.missing_channels {
+            for c in &m.channels {
+                lines.push(format!("- {}: o canal {} já não existe", m.course, c));
+            }
+            if m.category {
+                lines.push(format!("+ {}: é criada uma categoria nova", m.course));
+            }
+        }
+        for (role, channels) in &self.deprecated_channels {
+            for c in channels {
+                lines.push(format!(
+                    "- descontinuada ({}): o canal {} já não existe",
+                    role, c
+                ));
+            }
+        }
+        for o in &self.orphans {
+            lines.push(match &o.course {
+                Some(course) => format!("+ {}: volta a incluir o canal {}", course, o.name),
+                None => format!("! o canal {} não pertence a nenhuma cadeira", o.name),
+            });
+        }
+        for r in &self.deprecated_roles {
+            lines.push(format!(
+                "! o cargo {} de uma cadeira descontinuada já não existe",
+                r
+            ));
+        }
+        for name in &self.unmanaged {
+            lines.push(format!("! o cargo {} não pertence a nenhuma cadeira", name));
+        }
+        lines
+    }
+
+    async fn repair(
+        &self,
+        miei: &mut MiEI,
+        style: &CourseStyle,
+        ctx: &Context,
+        guild: GuildId,
+    ) -> Vec<String> {
+        let mut failed = Vec::new();
+        for m in &self.missing_roles {
+            match repair_role(miei, style, ctx, guild, m).await {
+                Ok(unshown) => {
+                    failed.extend(unshown.into_iter().map(|e| format!("{}: {}", m.course, e)))
+                }
+                Err(e) => failed.push(format!("{}: {}", m.course, e)),
+            }
+        }
+        for m in &self.missing_channels {
+            if let Err(e) = repair_channels(miei, style, ctx, guild, m).await {
+                failed.push(format!("{}: {}", m.course, e));
+            }
+        }
+        for (role, channels) in &self.deprecated_channels {
+            if let Some(course) = miei
+                .deprecated_courses_mut()
+                .iter_mut()
+                .find(|c| c.role == *role)
+            {
+                course.channels.retain(|c| !channels.contains(c));
+            }
+        }
+        for o in &self.orphans {
+            if let Some(course) = &o.course {
+                match miei.course_mut(course) {
+                    Some(c) => c.channels.push(o.channel),
+                    None => failed.push(format!("{}: a cadeira já não existe", course)),
+                }
+            }
+        }
+        if let Err(e) = miei.write() {
+            failed.push(format!("Não foi possível guardar as cadeiras: {}", e));
+        }
+        failed
+    }
+}
+
+/// Gives a course a role again, returning the channels that couldn't be shown to it.
+async fn repair_role(
+    miei: &mut MiEI,
+    style: &CourseStyle,
+    ctx: &Context,
+    guild: GuildId,
+    m: &MissingRole,
+) -> anyhow::Result<Vec<String>> {
+    let Some(channels) = miei.course(&m.course).map(|c| c.channels.clone()) else {
+        return Err(anyhow!("a cadeira já não existe"));
+    };
+    let role = match m.replacement {
+        Some(role) => role,
+        None => {
+            guild
+                .create_role(&ctx.http, course_role(style, &m.course, &m.year))
+                .await?
+                .id
+        }
+    };
+    if let Some(course) = miei.course_mut(&m.course) {
+        course.role = role;
+    }
+    let mut unshown = Vec::new();
+    for channel in channels {
+        match channel
+            .create_permission(
+                &ctx.http,
+                PermissionOverwrite {
+                    allow: Permissions::VIEW_CHANNEL,
+                    deny: Permissions::empty(),
+                    kind: PermissionOverwriteType::Role(role),
+                },
+            )
+            .await
+        {
+            Ok(()) => {}
+            // Deleted channels are dealt with separately.
+            Err(e) if is_not_found(&e) => {}
+            Err(e) => unshown.push(format!(
+                "o canal {} não ficou visível ao cargo: {}",
+                channel, e
+            )),
+        }
+    }
+    Ok(unshown)
+}
+
+async fn repair_channels(
+    miei: &mut MiEI,
+    style: &CourseStyle,
+    ctx: &Context,
+    guild: GuildId,
+    m: &MissingChannels,
+) -> anyhow::Result<()> {
+    let Some(course) = miei.course_mut(&m.course) else {
+        return Err(anyhow!("a cadeira já não existe"));
+    };
+    course.channels.retain(|c| !m.channels.contains(c));
+    if !m.category {
+        return Ok(());
+    }
+    let role = course.role;
+    let category = guild
+        .create_channel(
+            &ctx,
+            CreateChannel::new(style.category_name(&m.year, &m.course))
+                .kind(ChannelType::Category)
+                .permissions(course_overwrites(ctx, guild, role)),
+        )
+        .await?;
+    let course = miei
+        .course_mut(&m.course)
+        .ok_or_else(|| anyhow!("a cadeira já não existe"))?;
+    course.channels.insert(0, category.id);
+    for channel in &course.channels[1..] {
+        channel
+            .edit(&ctx.http, EditChannel::new().category(category.id))
+            .await?;
+    }
+    Ok(())
+}
+
+#[command]
+#[description(
+    "Compara as cadeiras com os cargos e canais que existem no servidor, mostra as diferenças \
+    e oferece-se para as corrigir."
+)]
+#[usage("")]
+#[required_permissions(ADMINISTRATOR)]
+pub async fn doctor(ctx: &Context, msg: &Message) -> CommandResult {
+    let guild_id = msg.guild_id.ok_or("Guild id not found")?;
+    let channels = guild_id.channels(&ctx.http).await?;
+    let roles = guild_id.roles(&ctx.http).await?;
+    let style = course_style(ctx, guild_id).await;
+    let miei = guild::data::<MiEI>(ctx, guild_id).await;
+    let diagnosis = Diagnosis::new(&*miei.read().await, &style, guild_id, &channels, &roles);
+    let report = diagnosis.report();
+    if report.is_empty() {
+        msg.channel_id
+            .say(&ctx.http, "As cadeiras estão de acordo com o servidor.")
+            .await?;
+        return Ok(());
+    }
+    let prompt = say_code_blocks(ctx, msg.channel_id, "diff", report.clone())
+        .await?
+        .ok_or("Empty report")?;
+    if !diagnosis.has_repairs() {
+        return Ok(());
+    }
+    let prompt = prompt.reply(&ctx.http, "Corrigir?").await?;
+    if !confirm(ctx, &prompt, msg.author.id).await? {
+        msg.channel_id.say(&ctx.http, "Nada foi alterado.").await?;
+        return Ok(());
+    }
+
+    let mut courses = miei.write().await;
+    // The server or the courses may have changed while waiting for the confirmation.
+    let channels = guild_id.channels(&ctx.http).await?;
+    let roles = guild_id.roles(&ctx.http).await?;
+    let style = course_style(ctx, guild_id).await;
+    let diagnosis = Diagnosis::new(&courses, &style, guild_id, &channels, &roles);
+    if diagnosis.report() != report {
+        msg.channel_id
+            .say(
+                &ctx.http,
+                "As cadeiras mudaram entretanto, nada foi alterado. Volta a pedir o diagnóstico.",
+            )
+            .await?;
+        return Ok(());
+    }
+    let failed = diagnosis.repair(&mut courses, &style, ctx, guild_id).await;
+    drop(courses);
+    if failed.is_empty() {
+        msg.channel_id
+            .say(&ctx.http, "Cadeiras corrigidas.")
+            .await?;
+    } else {
+        log!("Doctor failures: {:?}", failed);
+        msg.channel_id
+            .say(&ctx.http, "Cadeiras corrigidas, com falhas:")
+            .await?;
+        say_code_blocks(ctx, msg.channel_id, "", failed).await?;
+    }
+    Ok(())
+}
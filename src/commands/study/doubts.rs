@@ -0,0 +1,185 @@
+//! Doubts asked as posts in the forum channels of the courses.
+
+use crate::{channels::MiEI, config::RESOLVED_TAG, guild, is_admin, util::say_code_blocks};
+use chrono::Utc;
+use serenity::{
+    all::EditThread,
+    framework::standard::{
+        Args, CommandResult,
+        macros::{command, group},
+    },
+    model::{
+        channel::{ChannelType, GuildChannel, Message},
+        id::{ChannelId, GuildId},
+    },
+    prelude::*,
+};
+use std::collections::HashMap;
+
+#[group]
+#[prefixes("duvidas")]
+#[commands(resolved, unanswered, archive)]
+struct Doubts;
+
+/// The forums of the courses, by the name of their course.
+async fn course_forums(
+    ctx: &Context,
+    guild: GuildId,
+    course: Option<&str>,
+) -> serenity::Result<HashMap<ChannelId, String>> {
+    let channels = guild.channels(&ctx.http).await?;
+    let miei = guild::data::<MiEI>(ctx, guild).await;
+    let miei = miei.read().await;
+    Ok(miei
+        .courses()
+        .filter(|(_, _, name, _)| course.is_none_or(|c| c.eq_ignore_ascii_case(name)))
+        .flat_map(|(_, _, name, c)| c.channels.iter().map(move |ch| (*ch, name)))
+        .filter(|(ch, _)| {
+            channels
+                .get(ch)
+                .is_some_and(|c| c.kind == ChannelType::Forum)
+        })
+        .map(|(ch, name)| (ch, name.to_string()))
+        .collect())
+}
+
+/// The active posts in the forums of the courses, by the name of their course.
+async fn course_threads(
+    ctx: &Context,
+    guild: GuildId,
+    course: Option<&str>,
+) -> serenity::Result<Vec<(String, GuildChannel)>> {
+    let forums = course_forums(ctx, guild, course).await?;
+    let mut threads = guild
+        .get_active_threads(&ctx.http)
+        .await?
+        .threads
+        .into_iter()
+        .filter_map(|t| Some((forums.get(&t.parent_id?)?.clone(), t)))
+        .collect::<Vec<_>>();
+    threads.sort_by(|(a, _), (b, _)| a.cmp(b));
+    Ok(threads)
+}
+
+/// Seconds since the last message of a post.
+fn idle_for(thread: &GuildChannel) -> i64 {
+    let last = thread
+        .last_message_id
+        .map(|m| m.created_at())
+        .unwrap_or_else(|| thread.id.created_at());
+    Utc::now().timestamp() - last.unix_timestamp()
+}
+
+#[command("resolvida")]
+#[aliases("resolved")]
+#[description(
+    "Marca como resolvida a dúvida em que é usado. Só quem fez a pergunta ou um administrador \
+    o pode fazer."
+)]
+#[usage("")]
+pub async fn resolved(ctx: &Context, msg: &Message) -> CommandResult {
+    let thread = msg
+        .channel(&ctx)
+        .await?
+        .guild()
+        .filter(|c| c.kind == ChannelType::PublicThread)
+        .ok_or("Este comando só pode ser usado numa dúvida.")?;
+    if thread.owner_id != Some(msg.author.id) && !is_admin(ctx, msg).await {
+        return Err("Só quem fez a pergunta a pode marcar como resolvida.".into());
+    }
+    let forum = thread
+        .parent_id
+        .ok_or("Este comando só pode ser usado numa dúvida.")?
+        .to_channel(&ctx)
+        .await?
+        .guild()
+        .filter(|c| c.kind == ChannelType::Forum)
+        .ok_or("Este comando só pode ser usado numa dúvida.")?;
+    let tag = forum
+        .available_tags
+        .iter()
+        .find(|t| t.name.eq_ignore_ascii_case(RESOLVED_TAG))
+        .ok_or("Este fórum não tem uma etiqueta de resolvida.")?;
+    if thread.applied_tags.contains(&tag.id) {
+        msg.channel_id
+            .say(&ctx.http, "Esta dúvida já está resolvida.")
+            .await?;
+        return Ok(());
+    }
+    let tags = thread.applied_tags.iter().copied().chain([tag.id]);
+    msg.channel_id
+        .edit_thread(&ctx.http, EditThread::new().applied_tags(tags))
+        .await?;
+    msg.channel_id
+        .say(&ctx.http, "Dúvida marcada como resolvida.")
+        .await?;
+    Ok(())
+}
+
+#[command("abertas")]
+#[aliases("unanswered")]
+#[description("Lista as dúvidas que ainda não tiveram resposta, de todas as cadeiras ou de uma.")]
+#[usage("[CADEIRA]")]
+#[example("LI3")]
+#[max_args(1)]
+pub async fn unanswered(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = msg.guild_id.ok_or("Guild id not found")?;
+    let course = args.single::<String>().ok();
+    let threads = course_threads(ctx, guild_id, course.as_deref()).await?;
+    let lines = threads
+        .iter()
+        .filter(|(_, t)| t.message_count.unwrap_or(0) == 0)
+        .map(|(course, t)| format!("{}: {}", course, t.name))
+        .collect::<Vec<_>>();
+    if lines.is_empty() {
+        msg.channel_id
+            .say(&ctx.http, "Não há dúvidas por responder.")
+            .await?;
+    } else {
+        say_code_blocks(ctx, msg.channel_id, "", lines).await?;
+    }
+    Ok(())
+}
+
+#[command("arquivar")]
+#[aliases("archive")]
+#[description(
+    "Arquiva as dúvidas sem mensagens novas há mais do que os dias especificados (14 por omissão)."
+)]
+#[usage("[dias]")]
+#[example("30")]
+#[max_args(1)]
+#[required_permissions(ADMINISTRATOR)]
+pub async fn archive(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = msg.guild_id.ok_or("Guild id not found")?;
+    let days = if args.is_empty() {
+        14
+    } else {
+        args.single::<u32>()
+            .map_err(|_| "O número de dias tem de ser um número.")?
+    };
+    let threads = course_threads(ctx, guild_id, None).await?;
+    let mut archived = 0;
+    let mut failed = Vec::new();
+    for (course, thread) in threads
+        .iter()
+        .filter(|(_, t)| idle_for(t) > i64::from(days) * 24 * 60 * 60)
+    {
+        match thread
+            .id
+            .edit_thread(&ctx.http, EditThread::new().archived(true))
+            .await
+        {
+            Ok(_) => archived += 1,
+            Err(e) => failed.push(format!("{}: {} ({})", course, thread.name, e)),
+        }
+    }
+    msg.channel_id
+        .say(&ctx.http, format!("{} dúvidas arquivadas.", archived))
+        .await?;
+    if !failed.is_empty() {
+        msg.channel_id.say(&ctx.http, "Falhas:").await?;
+        say_code_blocks(ctx, msg.channel_id, "", failed).await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,238 @@
+//! Taking the course roles of past semesters, or years, from everyone, so students pick their
+//! courses again every semester.
+
+use crate::{channels::MiEI, config::Config, guild, log};
+use chrono::{Local, NaiveDate};
+use futures::stream::StreamExt;
+use serenity::{
+    all::{CreateMessage, EditMember},
+    framework::standard::{Args, CommandResult, macros::command},
+    model::{channel::Message, id::GuildId},
+    prelude::*,
+};
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+/// How often the expiry dates are checked.
+const CHECK_EVERY: Duration = Duration::from_secs(60 * 60);
+
+/// The members whose course roles expired.
+pub struct Expired {
+    pub members: usize,
+    /// The members whose roles couldn't be taken, like the ones with a role above the bot's.
+    pub failed: Vec<String>,
+}
+
+/// Takes the course roles of a year, or of one of its semesters, from every member that has
+/// them and tells them how to choose their courses again. Fails only when the members can't
+/// be listed, members that can't be edited are skipped.
+pub async fn expire_roles(
+    ctx: &Context,
+    guild: GuildId,
+    year: &str,
+    semester: Option<&str>,
+) -> serenity::Result<Expired> {
+    let roles = {
+        let miei = guild::data::<MiEI>(ctx, guild).await;
+        let miei = miei.read().await;
+        miei.roles_of(year, semester)
+    };
+    let channel = {
+        let config = guild::data::<Config>(ctx, guild).await;
+        let config = config.read().await;
+        config.allowed_channels().min().copied()
+    };
+    let mut notice = match semester {
+        Some(semester) => format!(
+            "O {}º semestre do {}º ano acabou e as tuas cadeiras dele foram removidas. Escolhe \
+            as cadeiras que estudas agora com `/study` ou `$study menu`",
+            semester, year
+        ),
+        None => format!(
+            "O ano letivo acabou e as tuas cadeiras do {}º ano foram removidas. Escolhe as \
+            cadeiras que estudas agora com `/study` ou `$study menu`",
+            year
+        ),
+    };
+    if let Some(channel) = channel {
+        notice.push_str(&format!(" em {}", channel.mention()));
+    }
+    notice.push('.');
+    let mut expired = Expired {
+        members: 0,
+        failed: Vec::new(),
+    };
+    let mut members = guild.members_iter(&ctx.http).boxed();
+    while let Some(member) = members.next().await {
+        let member = member?;
+        if !member.roles.iter().any(|r| roles.contains(r)) {
+            continue;
+        }
+        let kept = member
+            .roles
+            .iter()
+            .filter(|r| !roles.contains(r))
+            .copied()
+            .collect::<Vec<_>>();
+        if let Err(e) = guild
+            .edit_member(&ctx.http, member.user.id, EditMember::new().roles(kept))
+            .await
+        {
+            log!("Could not expire the roles of {}: {}", member.user.name, e);
+            expired.failed.push(member.user.name.clone());
+            continue;
+        }
+        expired.members += 1;
+        // Members may not accept messages from the bot, which is no reason to stop.
+        if let Err(e) = member
+            .user
+            .direct_message(&ctx.http, CreateMessage::new().content(&notice))
+            .await
+        {
+            log!(
+                "Could not tell {} their roles expired: {}",
+                member.user.name,
+                e
+            );
+        }
+    }
+    Ok(expired)
+}
+
+/// Expires the roles of every policy whose date went by.
+async fn expire_due(ctx: &Context, today: NaiveDate) {
+    for guild in ctx.cache.guilds() {
+        let due = {
+            let miei = guild::data::<MiEI>(ctx, guild).await;
+            let miei = miei.read().await;
+            miei.expiry()
+                .iter()
+                .filter(|e| e.is_due(today))
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+        for e in due {
+            match expire_roles(ctx, guild, &e.year, e.semester.as_deref()).await {
+                Ok(expired) => log!(
+                    "Expired the roles of {} from {} members, failed for {}",
+                    e.describe(),
+                    expired.members,
+                    expired.failed.len()
+                ),
+                Err(err) => {
+                    // Tried again in the next check, which only messages who still has roles.
+                    log!("Could not expire the roles of {}: {}", e.describe(), err);
+                    continue;
+                }
+            }
+            let miei = guild::data::<MiEI>(ctx, guild).await;
+            if let Err(err) = miei
+                .write()
+                .await
+                .expired(&e.year, e.semester.as_deref(), today)
+            {
+                log!("Could not save the expiry of {}: {:#}", e.describe(), err);
+            }
+        }
+    }
+}
+
+/// Starts checking the expiry dates in the background, once no matter how often it's called.
+pub fn start_expiry_scheduler(ctx: Context) {
+    static STARTED: AtomicBool = AtomicBool::new(false);
+    if STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(CHECK_EVERY);
+        loop {
+            interval.tick().await;
+            expire_due(&ctx, Local::now().date_naive()).await;
+        }
+    });
+}
+
+/// The year and semester of `ano [semestre] ...`, the semester being there when there are
+/// `with_semester` arguments.
+fn year_semester<'a>(args: &'a [&'a str], with_semester: usize) -> (&'a str, Option<&'a str>) {
+    (args[0], (args.len() == with_semester).then(|| args[1]))
+}
+
+#[command]
+#[description(
+    "Faz as cadeiras de um ano, ou de um semestre, expirar todos os anos na data dada (MM-DD): \
+    os cargos são tirados a todos, que recebem uma mensagem para escolher as cadeiras de novo. \
+    `none` deixa de as fazer expirar. Sem argumentos, mostra as datas."
+)]
+#[usage("[ano [semestre] MM-DD|none]")]
+#[example("1 1 02-01")]
+#[example("3 09-01")]
+#[example("3 none")]
+#[max_args(3)]
+#[required_permissions(ADMINISTRATOR)]
+pub async fn expiry(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let guild_id = msg.guild_id.ok_or("Guild id not found")?;
+    let miei = guild::data::<MiEI>(ctx, guild_id).await;
+    let mut miei = miei.write().await;
+    let args = args.raw().collect::<Vec<_>>();
+    if let [.., date] = args.as_slice() {
+        if args.len() < 2 {
+            return Err("Falta a data.".into());
+        }
+        let (year, semester) = year_semester(&args, 3);
+        let date = Some(*date).filter(|d| !d.eq_ignore_ascii_case("none"));
+        miei.set_expiry(year, semester, date, Local::now().date_naive())?;
+    }
+    let lines = miei
+        .expiry()
+        .iter()
+        .map(|e| format!("{}: {}", e.describe(), e.date))
+        .collect::<Vec<_>>();
+    let reply = if lines.is_empty() {
+        "Nenhuma cadeira expira.".to_string()
+    } else {
+        lines.join("\n")
+    };
+    msg.channel_id.say(&ctx.http, reply).await?;
+    Ok(())
+}
+
+#[command]
+#[description(
+    "Tira já os cargos das cadeiras de um ano, ou de um semestre, a todos, que recebem uma \
+    mensagem para escolher as cadeiras de novo."
+)]
+#[usage("ano [semestre]")]
+#[example("1 1")]
+#[min_args(1)]
+#[max_args(2)]
+#[required_permissions(ADMINISTRATOR)]
+pub async fn expire(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let guild_id = msg.guild_id.ok_or("Guild id not found")?;
+    let args = args.raw().collect::<Vec<_>>();
+    let (year, semester) = year_semester(&args, 2);
+    {
+        let miei = guild::data::<MiEI>(ctx, guild_id).await;
+        if miei.read().await.roles_of(year, semester).is_empty() {
+            return Err("Esse ano ou semestre não tem cadeiras.".into());
+        }
+    }
+    msg.channel_id
+        .say(&ctx.http, "A tirar os cargos, isto pode demorar...")
+        .await?;
+    let expired = expire_roles(ctx, guild_id, year, semester).await?;
+    let today = Local::now().date_naive();
+    let miei = guild::data::<MiEI>(ctx, guild_id).await;
+    miei.write().await.expired(year, semester, today)?;
+    let mut reply = format!("Os cargos foram tirados a {} membros.", expired.members);
+    if !expired.failed.is_empty() {
+        reply.push_str(&format!(
+            "\nNão foi possível tirá-los a: {}",
+            expired.failed.join(", ")
+        ));
+    }
+    msg.channel_id.say(&ctx.http, reply).await?;
+    Ok(())
+}
@@ -0,0 +1,151 @@
+//! What students may want to know about each course.
+
+use crate::{
+    channels::{CourseInfo, MiEI},
+    guild,
+};
+use serenity::{
+    all::{Colour, CreateEmbed, CreateEmbedFooter, CreateMessage, Mentionable},
+    framework::standard::{Args, CommandResult, macros::command},
+    model::channel::Message,
+    prelude::*,
+};
+
+#[command]
+#[description("Mostra a informação sobre uma cadeira.")]
+#[usage("CADEIRA")]
+#[example("PI")]
+#[num_args(1)]
+pub async fn info(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let guild_id = msg.guild_id.ok_or("Guild id not found")?;
+    let miei = guild::data::<MiEI>(ctx, guild_id).await;
+    let embed = {
+        let roles = miei.read().await;
+        let name = args.rest().trim().to_uppercase();
+        let name = roles.resolve(&name).map_or(name, str::to_string);
+        let (Some(course), Some((year, semester))) =
+            (roles.course(&name), roles.get_year_semester_names(&name))
+        else {
+            msg.channel_id
+                .say(&ctx.http, format!("A cadeira {} não existe.", name))
+                .await?;
+            return Ok(());
+        };
+        let info = &course.info;
+        let mut embed = CreateEmbed::new()
+            .title(match &info.full_name {
+                Some(full_name) => format!("{} - {}", name, full_name),
+                None => name.clone(),
+            })
+            .field("Ano", format!("{}ano{}semestre", year, semester), true)
+            .colour(Colour::from_rgb(0, 0, 0));
+        for (title, value) in [
+            ("Código", info.code.clone()),
+            (
+                "Também conhecida por",
+                Some(course.aliases.join(", ")).filter(|a| !a.is_empty()),
+            ),
+            ("ECTS", info.ects.clone()),
+            (
+                "Docentes",
+                Some(info.instructors.join("\n")).filter(|i| !i.is_empty()),
+            ),
+            ("Programa", info.syllabus_url.clone()),
+            ("Material", info.material_url.clone()),
+        ] {
+            if let Some(value) = value {
+                embed = embed.field(title, value, true);
+            }
+        }
+        let channels = course
+            .channels
+            .iter()
+            .skip(1)
+            .map(|c| c.mention().to_string())
+            .collect::<Vec<_>>();
+        if !channels.is_empty() {
+            embed = embed.field("Canais", channels.join(" "), false);
+        }
+        embed.footer(CreateEmbedFooter::new(format!(
+            "$study {} para te juntares",
+            name
+        )))
+    };
+    msg.channel_id
+        .send_message(&ctx.http, CreateMessage::new().embed(embed))
+        .await?;
+    Ok(())
+}
+
+/// Sets a field of the information of a course, an empty value clears it.
+fn set_field(info: &mut CourseInfo, field: &str, value: Option<String>) -> Result<(), String> {
+    let url = |value: Option<String>| match value {
+        Some(v) if !v.starts_with("http://") && !v.starts_with("https://") => {
+            Err(format!("{} não é um link", v))
+        }
+        v => Ok(v),
+    };
+    match field {
+        "nome" => info.full_name = value,
+        "codigo" | "código" => info.code = value,
+        "ects" => {
+            if let Some(v) = &value
+                && v.replace(',', ".").parse::<f32>().is_err()
+            {
+                return Err(format!("{} não é um número", v));
+            }
+            info.ects = value;
+        }
+        "docentes" => {
+            info.instructors = value
+                .iter()
+                .flat_map(|v| v.split(','))
+                .map(str::trim)
+                .filter(|i| !i.is_empty())
+                .map(str::to_string)
+                .collect();
+        }
+        "programa" => info.syllabus_url = url(value)?,
+        "material" => info.material_url = url(value)?,
+        f => {
+            return Err(format!(
+                "Campo desconhecido: {}. Os campos são nome, codigo, ects, docentes, programa e \
+                material.",
+                f
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[command("set")]
+#[description(
+    "Altera a informação de uma cadeira. Os campos são nome, codigo, ects, docentes (separados \
+    por vírgulas), programa e material. Sem valor, o campo é apagado."
+)]
+#[usage("CADEIRA campo [valor]")]
+#[example("PI nome Programação Imperativa")]
+#[example("PI docentes Fulano, Sicrano")]
+#[example("PI material")]
+#[min_args(2)]
+#[required_permissions(ADMINISTRATOR)]
+pub async fn set_info(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = msg.guild_id.ok_or("Guild id not found")?;
+    let course = args.single::<String>()?;
+    let field = args.single::<String>()?.to_lowercase();
+    let value = Some(args.rest().trim().to_string()).filter(|v| !v.is_empty());
+    let miei = guild::data::<MiEI>(ctx, guild_id).await;
+    let mut roles = miei.write().await;
+    let reply = match roles.info(&course).cloned() {
+        None => format!("A cadeira {} não existe.", course.to_uppercase()),
+        Some(mut info) => match set_field(&mut info, &field, value) {
+            Err(e) => e,
+            Ok(()) => {
+                roles.edit_info(&course, |i| *i = info)?;
+                format!("Informação de {} alterada.", course.to_uppercase())
+            }
+        },
+    };
+    msg.channel_id.say(&ctx.http, reply).await?;
+    Ok(())
+}
@@ -0,0 +1,300 @@
+//! Choosing courses with select menus, one for each semester, instead of typing their names.
+//!
+//! The menus belong to whoever asked for them: their id says whose they are, so choices made
+//! after a restart still work, and anyone else using them gets menus of their own.
+
+use super::restrictions::explain_refusals;
+use crate::{channels::MiEI, guild, log};
+use serenity::{
+    all::{
+        CommandInteraction, ComponentInteraction, ComponentInteractionDataKind, CreateActionRow,
+        CreateCommand, CreateInteractionResponse, CreateInteractionResponseFollowup,
+        CreateInteractionResponseMessage, CreateMessage, CreateSelectMenu, CreateSelectMenuKind,
+        CreateSelectMenuOption, EditMember, Interaction,
+    },
+    framework::standard::{CommandResult, macros::command},
+    model::{
+        application::ActionRowComponent,
+        channel::Message,
+        id::{GuildId, RoleId, UserId},
+    },
+    prelude::*,
+};
+use std::collections::{BTreeMap, HashSet};
+
+const MENU: &str = "study-menu";
+/// Discord's limits on options in a menu and on menus in a message.
+const OPTIONS: usize = 25;
+const ROWS: usize = 5;
+
+/// The menus of a member, in messages of at most [`ROWS`] menus.
+fn menus(miei: &MiEI, user: UserId, roles: &[RoleId]) -> Vec<Vec<CreateActionRow>> {
+    let mut semesters = BTreeMap::<_, Vec<_>>::new();
+    for c in miei.iter() {
+        semesters
+            .entry((c.year, c.semester))
+            .or_default()
+            .push(c.channel);
+    }
+    let mut rows = Vec::new();
+    for ((year, semester), mut courses) in semesters {
+        courses.sort_unstable();
+        for (part, courses) in courses.chunks(OPTIONS).enumerate() {
+            let options = courses
+                .iter()
+                .filter_map(|name| {
+                    let role = miei.role_by_name(name)?;
+                    let mut option = CreateSelectMenuOption::new(*name, *name)
+                        .default_selection(roles.contains(&role));
+                    if let Some(full_name) = miei.info(name).and_then(|i| i.full_name.as_ref()) {
+                        option =
+                            option.description(full_name.chars().take(100).collect::<String>());
+                    }
+                    Some(option)
+                })
+                .collect::<Vec<_>>();
+            // Discord refuses menus without options.
+            if options.is_empty() {
+                continue;
+            }
+            let count = options.len() as u8;
+            rows.push(CreateActionRow::SelectMenu(
+                CreateSelectMenu::new(
+                    format!("{}:{}:{}:{}:{}", MENU, user, year, semester, part),
+                    CreateSelectMenuKind::String { options },
+                )
+                .placeholder(format!("{}ano {}semestre", year, semester))
+                .min_values(0)
+                .max_values(count),
+            ));
+        }
+    }
+    rows.chunks(ROWS).map(<[_]>::to_vec).collect()
+}
+
+/// The menus of a message as they were sent, with the courses of `roles` chosen.
+fn rebuilt(miei: &MiEI, message: &Message, roles: &HashSet<RoleId>) -> Vec<CreateActionRow> {
+    message
+        .components
+        .iter()
+        .flat_map(|row| &row.components)
+        .filter_map(|c| match c {
+            ActionRowComponent::SelectMenu(m) => Some(m),
+            _ => None,
+        })
+        .map(|m| {
+            let options = m
+                .options
+                .iter()
+                .map(|o| {
+                    let chosen = miei
+                        .role_by_name(&o.value)
+                        .is_some_and(|r| roles.contains(&r));
+                    let mut option =
+                        CreateSelectMenuOption::new(&o.label, &o.value).default_selection(chosen);
+                    if let Some(description) = &o.description {
+                        option = option.description(description);
+                    }
+                    option
+                })
+                .collect();
+            let mut menu = CreateSelectMenu::new(
+                m.custom_id.clone().unwrap_or_default(),
+                CreateSelectMenuKind::String { options },
+            )
+            .min_values(m.min_values.unwrap_or(0))
+            .max_values(m.max_values.unwrap_or(1));
+            if let Some(placeholder) = &m.placeholder {
+                menu = menu.placeholder(placeholder);
+            }
+            CreateActionRow::SelectMenu(menu)
+        })
+        .collect()
+}
+
+#[command]
+#[description("Mostra menus com as cadeiras de cada semestre, onde podes escolher as que estudas.")]
+#[usage("")]
+pub async fn menu(ctx: &Context, msg: &Message) -> CommandResult {
+    let guild_id = msg.guild_id.ok_or("Guild id not found")?;
+    let member = msg.member(&ctx).await?;
+    let miei = guild::data::<MiEI>(ctx, guild_id).await;
+    let messages = menus(&*miei.read().await, msg.author.id, &member.roles);
+    if messages.is_empty() {
+        msg.channel_id
+            .say(&ctx.http, "Não há cadeiras disponíveis.")
+            .await?;
+    }
+    for (i, rows) in messages.into_iter().enumerate() {
+        let mut message = CreateMessage::new().components(rows);
+        if i == 0 {
+            message = message.content(format!(
+                "{}, escolhe as cadeiras que estudas:",
+                msg.author.mention()
+            ));
+        }
+        msg.channel_id.send_message(&ctx.http, message).await?;
+    }
+    Ok(())
+}
+
+/// The slash command that shows the menus only to whoever uses it.
+pub fn study_slash_command() -> CreateCommand {
+    CreateCommand::new("study")
+        .description("Escolhe as cadeiras que estudas")
+        .dm_permission(false)
+}
+
+/// The menus of a member as a response and follow ups only they can see.
+async fn private_menus(
+    ctx: &Context,
+    guild: GuildId,
+    user: UserId,
+    roles: &[RoleId],
+) -> (
+    CreateInteractionResponse,
+    Vec<CreateInteractionResponseFollowup>,
+) {
+    let miei = guild::data::<MiEI>(ctx, guild).await;
+    let mut messages = menus(&*miei.read().await, user, roles).into_iter();
+    let response = CreateInteractionResponse::Message(
+        CreateInteractionResponseMessage::new()
+            .ephemeral(true)
+            .content("Escolhe as cadeiras que estudas:")
+            .components(messages.next().unwrap_or_default()),
+    );
+    let follow_ups = messages
+        .map(|rows| {
+            CreateInteractionResponseFollowup::new()
+                .ephemeral(true)
+                .components(rows)
+        })
+        .collect();
+    (response, follow_ups)
+}
+
+async fn slash_study(ctx: &Context, command: &CommandInteraction) -> serenity::Result<()> {
+    let (Some(guild), Some(member)) = (command.guild_id, &command.member) else {
+        return Ok(());
+    };
+    let (response, follow_ups) = private_menus(ctx, guild, command.user.id, &member.roles).await;
+    command.create_response(&ctx.http, response).await?;
+    for follow_up in follow_ups {
+        command.create_followup(&ctx.http, follow_up).await?;
+    }
+    Ok(())
+}
+
+async fn choose(ctx: &Context, choice: &ComponentInteraction) -> serenity::Result<()> {
+    let (Some(guild), Some(member)) = (choice.guild_id, &choice.member) else {
+        return Ok(());
+    };
+    let owner = choice.data.custom_id.split(':').nth(1);
+    if owner != Some(choice.user.id.to_string().as_str()) {
+        // Someone else's menus: they get their own.
+        let (response, follow_ups) = private_menus(ctx, guild, choice.user.id, &member.roles).await;
+        choice.create_response(&ctx.http, response).await?;
+        for follow_up in follow_ups {
+            choice.create_followup(&ctx.http, follow_up).await?;
+        }
+        return Ok(());
+    }
+    let ComponentInteractionDataKind::StringSelect { values } = &choice.data.kind else {
+        return Ok(());
+    };
+    // The courses of this menu, as it was sent, so later changes to the courses don't make it
+    // touch courses it never showed.
+    let shown = choice
+        .message
+        .components
+        .iter()
+        .flat_map(|row| &row.components)
+        .find_map(|c| match c {
+            ActionRowComponent::SelectMenu(m)
+                if m.custom_id.as_deref() == Some(&choice.data.custom_id) =>
+            {
+                Some(
+                    m.options
+                        .iter()
+                        .map(|o| o.value.clone())
+                        .collect::<Vec<_>>(),
+                )
+            }
+            _ => None,
+        })
+        .unwrap_or_default();
+    let (joined, left, refused, roles, rows) = {
+        let miei = guild::data::<MiEI>(ctx, guild).await;
+        let miei = miei.read().await;
+        let mut roles = member.roles.iter().copied().collect::<HashSet<_>>();
+        let (mut joined, mut left, mut refused) = (Vec::new(), Vec::new(), Vec::new());
+        let shown = shown
+            .iter()
+            .filter_map(|name| Some((name.as_str(), miei.role_by_name(name)?)));
+        let (chosen, unchosen): (Vec<_>, Vec<_>) =
+            shown.partition(|(name, _)| values.iter().any(|v| v == name));
+        for (name, role) in unchosen {
+            if roles.remove(&role) {
+                left.push(name);
+            }
+        }
+        let mut courses = miei.course_count(&roles.iter().copied().collect::<Vec<_>>());
+        for (name, role) in chosen {
+            if roles.contains(&role) {
+                continue;
+            }
+            match miei
+                .restrictions(name)
+                .map_or(Ok(()), |r| r.check(&member.roles, courses))
+            {
+                Ok(()) => {
+                    roles.insert(role);
+                    courses += 1;
+                    joined.push(name);
+                }
+                Err(why) => refused.push((name, why)),
+            }
+        }
+        let rows = rebuilt(&miei, &choice.message, &roles);
+        (joined, left, refused, roles, rows)
+    };
+    if !joined.is_empty() || !left.is_empty() {
+        guild
+            .edit_member(&ctx.http, choice.user.id, EditMember::new().roles(roles))
+            .await?;
+    }
+    // The menus show what the member studies now, so only refusals need explaining.
+    choice
+        .create_response(
+            &ctx.http,
+            CreateInteractionResponse::UpdateMessage(
+                CreateInteractionResponseMessage::new().components(rows),
+            ),
+        )
+        .await?;
+    // Every course in the menu is picked by name.
+    let named = refused.iter().map(|(n, _)| *n).collect::<Vec<_>>();
+    if let Some(explained) = explain_refusals(ctx, guild, choice.user.id, &refused, &named).await {
+        choice
+            .create_followup(
+                &ctx.http,
+                CreateInteractionResponseFollowup::new()
+                    .ephemeral(true)
+                    .content(explained),
+            )
+            .await?;
+    }
+    Ok(())
+}
+
+/// Handles the slash command and the choices made in the menus.
+pub async fn handle_menu_interaction(ctx: &Context, interaction: &Interaction) {
+    let result = match interaction {
+        Interaction::Command(c) if c.data.name == "study" => slash_study(ctx, c).await,
+        Interaction::Component(c) if c.data.custom_id.starts_with(MENU) => choose(ctx, c).await,
+        _ => return,
+    };
+    if let Err(e) = result {
+        log!("Study menu failed: {:?}", e);
+    }
+}
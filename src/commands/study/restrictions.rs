@@ -0,0 +1,255 @@
+//! Courses that not everyone can join with `$study`.
+
+use crate::{
+    channels::{MiEI, Refusal},
+    config::Config,
+    guild,
+};
+use once_cell::sync::Lazy;
+use serenity::{
+    all::{CreateEmbed, CreateMessage},
+    framework::standard::{Args, CommandResult, macros::command},
+    model::{
+        channel::Message,
+        id::{GuildId, RoleId, UserId},
+    },
+    prelude::*,
+    utils::{parse_role_mention, parse_user_mention},
+};
+use std::{
+    collections::HashSet,
+    sync::{Mutex, MutexGuard, PoisonError},
+};
+
+/// Courses that were refused, with why.
+pub type Refused<'a> = Vec<(&'a str, Refusal)>;
+
+/// Approvals asked for and not given yet, so students aren't asked about twice.
+static PENDING: Lazy<Mutex<HashSet<(GuildId, UserId, String)>>> = Lazy::new(Default::default);
+
+fn pending() -> MutexGuard<'static, HashSet<(GuildId, UserId, String)>> {
+    PENDING.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn role_name(ctx: &Context, guild: GuildId, role: RoleId) -> String {
+    ctx.cache
+        .guild(guild)
+        .and_then(|g| g.roles.get(&role).map(|r| r.name.clone()))
+        .unwrap_or_else(|| role.to_string())
+}
+
+/// Explains to a student why they couldn't join the courses, and asks the admins to approve
+/// the ones that need it.
+///
+/// Approval is only asked for the courses the student `named`, not the ones they got with a
+/// year or a prefix, and only once until it's given.
+pub async fn explain_refusals(
+    ctx: &Context,
+    guild: GuildId,
+    user: UserId,
+    refused: &Refused<'_>,
+    named: &[&str],
+) -> Option<String> {
+    let mut lines = Vec::new();
+    let mut approvals = Vec::new();
+    for (course, why) in refused {
+        lines.push(match why {
+            Refusal::MissingRole(role) => format!(
+                "A cadeira `{}` é só para quem tem o cargo {}.",
+                course,
+                role_name(ctx, guild, *role)
+            ),
+            Refusal::TooManyCourses(max) => format!(
+                "A cadeira `{}` é só para quem está em menos de {} cadeiras.",
+                course, max
+            ),
+            Refusal::NeedsApproval if named.contains(course) => {
+                approvals.push(*course);
+                format!(
+                    "A cadeira `{}` precisa da aprovação de um administrador.",
+                    course
+                )
+            }
+            Refusal::NeedsApproval => format!(
+                "A cadeira `{}` precisa da aprovação de um administrador, pede-a com `$study {}`.",
+                course, course
+            ),
+        });
+    }
+    let asked = approvals.len();
+    approvals.retain(|c| pending().insert((guild, user, c.to_uppercase())));
+    if asked > 0 && approvals.is_empty() {
+        lines.push("O pedido já foi enviado aos administradores.".to_string());
+    } else if !approvals.is_empty() {
+        let config = guild::data::<Config>(ctx, guild).await;
+        let log_channel = config.read().await.log_channel();
+        let sent = match log_channel {
+            Some(ch) => ch
+                .send_message(
+                    &ctx.http,
+                    CreateMessage::new().embed(
+                        CreateEmbed::new()
+                            .title("Pedido de aprovação")
+                            .description(format!(
+                                "{} pediu para entrar em {}.\n`$courses approve {} {}`",
+                                user.mention(),
+                                approvals.join(", "),
+                                user,
+                                approvals.join(" ")
+                            )),
+                    ),
+                )
+                .await
+                .is_ok(),
+            None => false,
+        };
+        lines.push(if sent {
+            "O pedido foi enviado aos administradores.".to_string()
+        } else {
+            let mut pending = pending();
+            for c in &approvals {
+                pending.remove(&(guild, user, c.to_uppercase()));
+            }
+            "Pede a um administrador para te adicionar.".to_string()
+        });
+    }
+    (!lines.is_empty()).then(|| lines.join("\n"))
+}
+
+fn describe(ctx: &Context, guild: GuildId, miei: &MiEI, course: &str) -> String {
+    let Some(r) = miei.restrictions(course) else {
+        return format!("A cadeira {} não existe.", course);
+    };
+    let mut lines = vec![format!("Restrições de {}:", course)];
+    if let Some(role) = r.required_role {
+        lines.push(format!("role: {}", role_name(ctx, guild, role)));
+    }
+    if let Some(max) = r.max_courses {
+        lines.push(format!("max: {}", max));
+    }
+    if r.approval {
+        lines.push("approval: yes".to_string());
+    }
+    if r.is_empty() {
+        lines.push("nenhuma".to_string());
+    }
+    lines.join("\n")
+}
+
+enum Edit {
+    Role(Option<RoleId>),
+    Max(Option<u32>),
+    Approval(bool),
+}
+
+fn parse_edit(arg: &str) -> Result<Edit, String> {
+    let (key, value) = arg
+        .split_once('=')
+        .ok_or_else(|| format!("Esperava chave=valor, não {}", arg))?;
+    let value = value.to_lowercase();
+    let none = value == "none";
+    match key.to_lowercase().as_str() {
+        "role" if none => Ok(Edit::Role(None)),
+        "role" => parse_role_mention(&value)
+            .or_else(|| value.parse().ok())
+            .map(|r| Edit::Role(Some(r)))
+            .ok_or_else(|| format!("{} não é um cargo.", value)),
+        "max" if none => Ok(Edit::Max(None)),
+        "max" => value
+            .parse()
+            .map(|m| Edit::Max(Some(m)))
+            .map_err(|_| format!("{} não é um número.", value)),
+        "approval" => match value.as_str() {
+            "yes" | "sim" => Ok(Edit::Approval(true)),
+            "no" | "nao" | "não" => Ok(Edit::Approval(false)),
+            v => Err(format!("Esperava yes ou no, não {}", v)),
+        },
+        k => Err(format!("A restrição {} não existe.", k)),
+    }
+}
+
+#[command]
+#[description(
+    "Restringe quem se pode juntar a uma cadeira com o $study: quem tem um cargo (role), quem \
+    está em menos de um número de cadeiras (max) ou com a aprovação de um administrador \
+    (approval). `none` ou `no` remove a restrição. Sem restrições, mostra as que a cadeira tem."
+)]
+#[usage("CADEIRA [role=CARGO|none] [max=N|none] [approval=yes|no]")]
+#[example("MEI-ESPECIALIZACAO role=@5ano max=2")]
+#[example("LEI-PROJETO approval=yes")]
+#[min_args(1)]
+#[required_permissions(ADMINISTRATOR)]
+pub async fn restrict(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = msg.guild_id.ok_or("Guild id not found")?;
+    let course = args.single::<String>()?.to_uppercase();
+    let miei = guild::data::<MiEI>(ctx, guild_id).await;
+    let mut roles = miei.write().await;
+    let edits = args.raw().map(parse_edit).collect::<Result<Vec<_>, _>>()?;
+    if !edits.is_empty() {
+        roles.edit_restrictions(&course, |r| {
+            for edit in edits {
+                match edit {
+                    Edit::Role(role) => r.required_role = role,
+                    Edit::Max(max) => r.max_courses = max,
+                    Edit::Approval(approval) => r.approval = approval,
+                }
+            }
+        })?;
+    }
+    msg.channel_id
+        .say(&ctx.http, describe(ctx, guild_id, &roles, &course))
+        .await?;
+    Ok(())
+}
+
+#[command]
+#[description("Junta alguém às salas das cadeiras, ignorando as suas restrições.")]
+#[usage("MEMBRO [CADEIRA, ...]")]
+#[example("@aluno LEI-PROJETO")]
+#[min_args(2)]
+#[required_permissions(ADMINISTRATOR)]
+pub async fn approve(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = msg.guild_id.ok_or("Guild id not found")?;
+    let user = args.single::<String>()?;
+    let user = parse_user_mention(&user)
+        .or_else(|| user.parse().ok())
+        .ok_or_else(|| format!("{} não é um membro.", user))?;
+    let miei = guild::data::<MiEI>(ctx, guild_id).await;
+    let roles = miei.read().await;
+    let mut ids = Vec::new();
+    let mut names = Vec::new();
+    let mut unknown = Vec::new();
+    for course in args.raw() {
+        // Approvals are asked for by the name of the course, even if the admin uses an alias.
+        match roles
+            .resolve(course)
+            .and_then(|name| Some((name, roles.role_by_name(name)?)))
+        {
+            Some((name, role)) => {
+                ids.push(role);
+                names.push(name.to_string());
+            }
+            None => unknown.push(course),
+        }
+    }
+    if ids.is_empty() {
+        return Err(format!("As cadeiras {} não existem.", unknown.join(" ")).into());
+    }
+    guild_id
+        .member(ctx, user)
+        .await?
+        .add_roles(&ctx.http, &ids)
+        .await?;
+    {
+        let mut pending = pending();
+        for name in &names {
+            pending.remove(&(guild_id, user, name.clone()));
+        }
+    }
+    let mut reply = format!("{} juntou-se a {}", user.mention(), names.join(" "));
+    if !unknown.is_empty() {
+        reply.push_str(&format!("\nAs cadeiras {} não existem.", unknown.join(" ")));
+    }
+    msg.channel_id.say(&ctx.http, reply).await?;
+    Ok(())
+}
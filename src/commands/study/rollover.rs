@@ -0,0 +1,352 @@
+//! Moving the courses into a new academic year in one go.
+
+use crate::{
+    channels::MiEI,
+    guild, log,
+    util::{confirm, say_code_blocks},
+};
+use serenity::{
+    all::EditMessage,
+    framework::standard::{Args, CommandResult, macros::command},
+    model::channel::Message,
+    prelude::*,
+};
+use std::{collections::HashSet, fmt::Write, str::FromStr};
+
+enum Step {
+    Move {
+        course: String,
+        year: String,
+        semester: String,
+        new_name: Option<String>,
+    },
+    Rename {
+        course: String,
+        new_name: String,
+    },
+    Deprecate {
+        course: String,
+    },
+    Create {
+        year: String,
+        semester: String,
+        course: String,
+    },
+}
+
+impl Step {
+    fn parse(line: &str) -> Result<Vec<Self>, String> {
+        let mut words = line.split_whitespace();
+        let keyword = words.next().unwrap_or_default();
+        let mut word = |what: &str| {
+            words
+                .next()
+                .map(str::to_string)
+                .ok_or_else(|| format!("falta {}", what))
+        };
+        let step = match keyword {
+            "mv" => Step::Move {
+                course: word("a cadeira")?.to_uppercase(),
+                year: word("o ano")?,
+                semester: word("o semestre")?,
+                new_name: word("").ok().map(|n| n.to_uppercase()),
+            },
+            "rename" => Step::Rename {
+                course: word("a cadeira")?.to_uppercase(),
+                new_name: word("o nome novo")?.to_uppercase(),
+            },
+            "deprecate" => Step::Deprecate {
+                course: word("a cadeira")?.to_uppercase(),
+            },
+            "mk" => {
+                let year = word("o ano")?;
+                let semester = word("o semestre")?;
+                let steps = words
+                    .map(|c| Step::Create {
+                        year: year.clone(),
+                        semester: semester.clone(),
+                        course: c.to_uppercase(),
+                    })
+                    .collect::<Vec<_>>();
+                if steps.is_empty() {
+                    return Err("faltam as cadeiras".into());
+                }
+                return Ok(steps);
+            }
+            k => return Err(format!("passo desconhecido: {}", k)),
+        };
+        match words.next() {
+            Some(extra) => Err(format!("argumento a mais: {}", extra)),
+            None => Ok(vec![step]),
+        }
+    }
+
+    /// Order in which the steps are applied, so that names freed by deprecations and renames can
+    /// be reused by the steps that come after them.
+    fn order(&self) -> u8 {
+        match self {
+            Step::Deprecate { .. } => 0,
+            Step::Move { .. } | Step::Rename { .. } => 1,
+            Step::Create { .. } => 2,
+        }
+    }
+}
+
+/// The changes to make to the courses for the new academic year, one step per line:
+///
+/// ```text
+/// mv CADEIRA ano semestre [NOME_NOVO]
+/// rename CADEIRA NOME_NOVO
+/// deprecate CADEIRA
+/// mk ano semestre CADEIRA...
+/// ```
+struct Plan {
+    steps: Vec<Step>,
+}
+
+impl FromStr for Plan {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut steps = s
+            .lines()
+            .map(|l| l.split('#').next().unwrap_or_default().trim())
+            .enumerate()
+            .filter(|(_, l)| !l.is_empty() && !l.starts_with("```"))
+            .map(|(i, l)| Step::parse(l).map_err(|e| format!("Linha {}: {}", i + 1, e)))
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>();
+        if steps.is_empty() {
+            return Err("O plano está vazio".into());
+        }
+        steps.sort_by_key(Step::order);
+        Ok(Plan { steps })
+    }
+}
+
+impl Plan {
+    /// Checks the plan against the current courses, returning every problem found.
+    fn check(&self, miei: &MiEI) -> Vec<String> {
+        let mut problems = Vec::new();
+        let mut touched = HashSet::new();
+        let mut names = miei
+            .iter()
+            .map(|c| c.channel.to_string())
+            .collect::<HashSet<_>>();
+        for step in &self.steps {
+            let (course, new_name) = match step {
+                Step::Move {
+                    course, new_name, ..
+                } => (Some(course), new_name.as_ref()),
+                Step::Rename { course, new_name } => (Some(course), Some(new_name)),
+                Step::Deprecate { course } => (Some(course), None),
+                Step::Create { course, .. } => (None, Some(course)),
+            };
+            if let Some(course) = course {
+                if !miei.role_exists(course) {
+                    problems.push(format!("A cadeira {} não existe", course));
+                }
+                if !touched.insert(course) {
+                    problems.push(format!("A cadeira {} aparece mais do que uma vez", course));
+                }
+                if new_name.is_some() || matches!(step, Step::Deprecate { .. }) {
+                    names.remove(course);
+                }
+            }
+            // Aliases can't become names, only names that are freed can be reused.
+            if let Some(name) = new_name
+                && (!names.insert(name.clone())
+                    || (miei.name_taken(name) && !miei.role_exists(name)))
+            {
+                problems.push(format!("A cadeira {} já existe", name));
+            }
+        }
+        problems
+    }
+
+    /// The plan as a diff of the courses, with the year and semester of each one.
+    fn preview(&self, miei: &MiEI) -> Vec<String> {
+        let location = |course: &str| {
+            miei.get_year_semester_names(course)
+                .map(|(y, s)| format!("{}ano{}semestre", y, s))
+                .unwrap_or_default()
+        };
+        let mut lines = Vec::new();
+        for step in &self.steps {
+            match step {
+                Step::Move {
+                    course,
+                    year,
+                    semester,
+                    new_name,
+                } => {
+                    lines.push(format!("- {} {}", course, location(course)));
+                    lines.push(format!(
+                        "+ {} {}ano{}semestre",
+                        new_name.as_ref().unwrap_or(course),
+                        year,
+                        semester
+                    ));
+                }
+                Step::Rename { course, new_name } => {
+                    lines.push(format!("- {} {}", course, location(course)));
+                    lines.push(format!("+ {} {}", new_name, location(course)));
+                }
+                Step::Deprecate { course } => {
+                    lines.push(format!("- {} {} (descontinuada)", course, location(course)));
+                }
+                Step::Create {
+                    year,
+                    semester,
+                    course,
+                } => lines.push(format!(
+                    "+ {} {}ano{}semestre (nova)",
+                    course, year, semester
+                )),
+            }
+        }
+        lines
+    }
+}
+
+#[derive(Default)]
+struct Summary {
+    moved: Vec<String>,
+    renamed: Vec<String>,
+    deprecated: Vec<String>,
+    created: Vec<String>,
+    failed: Vec<String>,
+}
+
+#[command]
+#[description(
+    "Aplica um plano de transição de ano letivo às cadeiras. O plano pode ser escrito na \
+    mensagem ou enviado como anexo, com um passo por linha."
+)]
+#[usage(
+    "\n[mv CADEIRA ano semestre [NOME_NOVO]]\n[rename CADEIRA NOME_NOVO]\n[deprecate CADEIRA]\n[mk ano semestre CADEIRA...]"
+)]
+#[example("\nmv PI 1 2\nrename SD SDIST\ndeprecate CP\nmk 1 1 ALGEBRA")]
+#[required_permissions(ADMINISTRATOR)]
+pub async fn rollover(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let guild_id = msg.guild_id.ok_or("Guild id not found")?;
+    let plan = match msg.attachments.first() {
+        Some(attachment) => String::from_utf8(attachment.download().await?)?,
+        None => args.rest().to_string(),
+    };
+    let plan = plan.parse::<Plan>()?;
+    let miei = guild::data::<MiEI>(ctx, guild_id).await;
+    let preview = {
+        let roles = miei.read().await;
+        let problems = plan.check(&roles);
+        if !problems.is_empty() {
+            msg.channel_id
+                .say(&ctx.http, "O plano tem problemas, nada foi alterado:")
+                .await?;
+            say_code_blocks(ctx, msg.channel_id, "", problems).await?;
+            return Ok(());
+        }
+        plan.preview(&roles)
+    };
+    let prompt = say_code_blocks(ctx, msg.channel_id, "diff", preview.clone())
+        .await?
+        .ok_or("Empty preview")?;
+    let prompt = prompt.reply(&ctx.http, "Aplicar estas alterações?").await?;
+    if !confirm(ctx, &prompt, msg.author.id).await? {
+        msg.channel_id
+            .say(&ctx.http, "Transição cancelada.")
+            .await?;
+        return Ok(());
+    }
+
+    let mut roles = miei.write().await;
+    // The courses may have changed while waiting for the confirmation.
+    if !plan.check(&roles).is_empty() || plan.preview(&roles) != preview {
+        msg.channel_id
+            .say(
+                &ctx.http,
+                "As cadeiras mudaram entretanto, nada foi alterado. Volta a pedir a transição.",
+            )
+            .await?;
+        return Ok(());
+    }
+    let total = plan.steps.len();
+    let mut progress = msg
+        .channel_id
+        .say(&ctx.http, format!("A aplicar: 0/{}", total))
+        .await?;
+    let mut summary = Summary::default();
+    for (done, step) in plan.steps.iter().enumerate() {
+        match step {
+            Step::Move {
+                course,
+                year,
+                semester,
+                new_name,
+            } => {
+                match roles
+                    .move_course(course, year, semester, new_name.as_deref(), ctx, guild_id)
+                    .await
+                {
+                    Ok(c) => summary.moved.push(c),
+                    Err(e) => summary.failed.push(format!("{}: {}", course, e)),
+                }
+            }
+            Step::Rename { course, new_name } => {
+                match roles.rename_course(course, new_name, ctx, guild_id).await {
+                    Ok(c) => summary.renamed.push(format!("{} -> {}", course, c)),
+                    Err(e) => summary.failed.push(format!("{}: {}", course, e)),
+                }
+            }
+            Step::Deprecate { course } => {
+                match roles.deprecate_course(course, ctx, guild_id).await {
+                    Ok(c) => summary.deprecated.push(c),
+                    Err(e) => summary.failed.push(format!("{}: {}", course, e)),
+                }
+            }
+            Step::Create {
+                year,
+                semester,
+                course,
+            } => match roles
+                .create_role(ctx, year, semester, course, guild_id)
+                .await
+            {
+                Ok(Some(c)) => summary.created.push(c.to_string()),
+                Ok(None) => summary.failed.push(format!("{}: já existe", course)),
+                Err(e) => summary.failed.push(format!("{}: {}", course, e)),
+            },
+        }
+        // Stopping halfway over a progress message would leave the rollover half done.
+        if let Err(e) = progress
+            .edit(
+                ctx,
+                EditMessage::new().content(format!("A aplicar: {}/{}", done + 1, total)),
+            )
+            .await
+        {
+            log!("Could not show the rollover progress: {}", e);
+        }
+    }
+
+    let mut report = String::from("Transição concluída.\n");
+    for (what, courses) in [
+        ("Movidas", &summary.moved),
+        ("Renomeadas", &summary.renamed),
+        ("Descontinuadas", &summary.deprecated),
+        ("Criadas", &summary.created),
+    ] {
+        if !courses.is_empty() {
+            writeln!(report, "**{}:** {}", what, courses.join(" "))?;
+        }
+    }
+    msg.channel_id.say(&ctx.http, report).await?;
+    if !summary.failed.is_empty() {
+        log!("Rollover failures: {:?}", summary.failed);
+        msg.channel_id.say(&ctx.http, "Falhas:").await?;
+        say_code_blocks(ctx, msg.channel_id, "", summary.failed).await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,204 @@
+//! How many students each course has and how much they ask in its `duvidas-` channels.
+
+use crate::{channels::MiEI, guild, util::say_code_blocks};
+use chrono::Utc;
+use futures::stream::{StreamExt, TryStreamExt};
+use serde::Serialize;
+use serenity::{
+    all::{CreateAttachment, CreateMessage},
+    framework::standard::{Args, CommandResult, macros::command},
+    model::{
+        channel::{ChannelType, GuildChannel, Message},
+        id::{ChannelId, GuildId, RoleId},
+    },
+    prelude::*,
+};
+use std::collections::HashMap;
+
+/// The days of activity counted when none are given.
+const DEFAULT_DAYS: u32 = 30;
+
+/// How many archived threads of a channel are looked at, the most Discord gives at once.
+const ARCHIVED_THREADS: u64 = 100;
+
+/// A row of the statistics, one for each `duvidas-` channel of a course, or one for the course
+/// if it has none.
+#[derive(Serialize, Clone)]
+struct Row {
+    ano: String,
+    semestre: String,
+    cadeira: String,
+    membros: usize,
+    canal: String,
+    /// None when the messages couldn't be read.
+    mensagens: Option<usize>,
+}
+
+/// How many messages were sent in a channel since `since`, a unix timestamp, including the
+/// messages of its threads, active or archived, which for a forum are its posts.
+async fn recent_messages(
+    ctx: &Context,
+    channel: &GuildChannel,
+    threads: &[GuildChannel],
+    since: i64,
+) -> serenity::Result<usize> {
+    async fn count(ctx: &Context, channel: ChannelId, since: i64) -> serenity::Result<usize> {
+        channel
+            .messages_iter(&ctx.http)
+            .take_while(|m| {
+                let recent = m
+                    .as_ref()
+                    .map_or(true, |m| m.timestamp.unix_timestamp() >= since);
+                futures::future::ready(recent)
+            })
+            .try_fold(0, |n, _| futures::future::ok(n + 1))
+            .await
+    }
+    let mut total = if channel.kind == ChannelType::Forum {
+        0
+    } else {
+        count(ctx, channel.id, since).await?
+    };
+    for thread in threads.iter().filter(|t| t.parent_id == Some(channel.id)) {
+        total += count(ctx, thread.id, since).await?;
+    }
+    // Threads archived after a message in the window count too. Only the threads archived last
+    // are fetched, which usually go back much further than the days counted.
+    let archived = channel
+        .id
+        .get_archived_public_threads(&ctx.http, None, Some(ARCHIVED_THREADS))
+        .await?
+        .threads;
+    let recent = archived.iter().filter(|t| {
+        t.last_message_id
+            .is_some_and(|m| m.created_at().unix_timestamp() >= since)
+    });
+    for thread in recent {
+        total += count(ctx, thread.id, since).await?;
+    }
+    Ok(total)
+}
+
+async fn member_counts(ctx: &Context, guild: GuildId) -> serenity::Result<HashMap<RoleId, usize>> {
+    let mut counts = HashMap::new();
+    let mut members = guild.members_iter(&ctx.http).boxed();
+    while let Some(member) = members.next().await {
+        for role in member?.roles {
+            *counts.entry(role).or_default() += 1;
+        }
+    }
+    Ok(counts)
+}
+
+#[command]
+#[description(
+    "Mostra quantos membros tem cada cadeira, por ano e semestre, e quantas mensagens houve \
+    nos canais `duvidas-` nos últimos dias (30 por omissão). Com `csv`, envia-as num ficheiro."
+)]
+#[usage("[dias] [csv]")]
+#[example("7")]
+#[example("30 csv")]
+#[max_args(2)]
+#[required_permissions(ADMINISTRATOR)]
+pub async fn stats(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let guild_id = msg.guild_id.ok_or("Guild id not found")?;
+    let mut days = DEFAULT_DAYS;
+    let mut csv = false;
+    for arg in args.raw() {
+        if arg.eq_ignore_ascii_case("csv") {
+            csv = true;
+        } else {
+            days = arg
+                .parse()
+                .map_err(|_| "O número de dias tem de ser um número.")?;
+        }
+    }
+    msg.channel_id.broadcast_typing(&ctx.http).await?;
+    let since = Utc::now().timestamp() - i64::from(days) * 24 * 60 * 60;
+    let channels = guild_id.channels(&ctx.http).await?;
+    let threads = guild_id.get_active_threads(&ctx.http).await?.threads;
+    let members = member_counts(ctx, guild_id).await?;
+    let mut courses = {
+        let miei = guild::data::<MiEI>(ctx, guild_id).await;
+        let miei = miei.read().await;
+        miei.courses()
+            .map(|(year, semester, name, c)| {
+                let doubts = c
+                    .channels
+                    .iter()
+                    .filter_map(|ch| channels.get(ch))
+                    .filter(|ch| ch.name.starts_with("duvidas-"))
+                    .cloned()
+                    .collect::<Vec<_>>();
+                let row = Row {
+                    ano: year.to_string(),
+                    semestre: semester.to_string(),
+                    cadeira: name.to_string(),
+                    membros: members.get(&c.role).copied().unwrap_or(0),
+                    canal: String::new(),
+                    mensagens: Some(0),
+                };
+                (row, doubts)
+            })
+            .collect::<Vec<_>>()
+    };
+    courses.sort_by(|(a, _), (b, _)| {
+        (&a.ano, &a.semestre, &a.cadeira).cmp(&(&b.ano, &b.semestre, &b.cadeira))
+    });
+    let mut rows = Vec::new();
+    for (row, doubts) in courses {
+        if doubts.is_empty() {
+            rows.push(row);
+            continue;
+        }
+        for channel in doubts {
+            rows.push(Row {
+                canal: channel.name.clone(),
+                mensagens: recent_messages(ctx, &channel, &threads, since).await.ok(),
+                ..row.clone()
+            });
+        }
+    }
+    if csv {
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        for row in &rows {
+            writer.serialize(row)?;
+        }
+        msg.channel_id
+            .send_message(
+                &ctx.http,
+                CreateMessage::new()
+                    .content(format!(
+                        "Estatísticas das cadeiras, com as mensagens dos últimos {} dias.",
+                        days
+                    ))
+                    .add_file(CreateAttachment::bytes(
+                        writer.into_inner()?,
+                        "cadeiras.csv",
+                    )),
+            )
+            .await?;
+        return Ok(());
+    }
+    let width = rows.iter().map(|r| r.cadeira.len()).max().unwrap_or(0);
+    let mut lines = vec![format!(
+        "{:<9} {:<width$} {:>7}  mensagens em {} dias",
+        "ano/sem", "cadeira", "membros", days
+    )];
+    lines.extend(rows.iter().map(|r| {
+        let messages = match (r.canal.as_str(), r.mensagens) {
+            ("", _) => "-".to_string(),
+            (canal, Some(n)) => format!("{}: {}", canal, n),
+            (canal, None) => format!("{}: ?", canal),
+        };
+        format!(
+            "{:<9} {:<width$} {:>7}  {}",
+            format!("{}ano{}sem", r.ano, r.semestre),
+            r.cadeira,
+            r.membros,
+            messages
+        )
+    }));
+    say_code_blocks(ctx, msg.channel_id, "", lines).await?;
+    Ok(())
+}
@@ -1,4 +1,4 @@
-use crate::{config::Config, get};
+use crate::{config::Config, guild};
 use serenity::{
     all::{CreateEmbed, CreateMessage},
     framework::standard::{
@@ -23,11 +23,17 @@ struct UserMod;
 #[min_args(1)]
 pub async fn join(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
     let request = args.rest();
-    let role = match role_by_name(ctx, msg.guild_id.ok_or("Not in a server")?, request).await? {
+    let guild_id = msg.guild_id.ok_or("Not in a server")?;
+    let role = match role_by_name(ctx, guild_id, request).await? {
         Some(role) => role,
         None => return Err("No such role".into()),
     };
-    if get!(ctx, Config, read).user_group_exists(role) {
+    let is_user_group = guild::data::<Config>(ctx, guild_id)
+        .await
+        .read()
+        .await
+        .user_group_exists(role);
+    if is_user_group {
         let member = msg.member(&ctx).await?;
         if !member.roles.contains(&role) {
             member.add_role(&ctx, role).await?;
@@ -48,11 +54,17 @@ pub async fn join(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
 #[usage("role_name")]
 pub async fn leave(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
     let request = args.rest();
-    let role = match role_by_name(ctx, msg.guild_id.ok_or("Not in a server")?, request).await? {
+    let guild_id = msg.guild_id.ok_or("Not in a server")?;
+    let role = match role_by_name(ctx, guild_id, request).await? {
         Some(role) => role,
         None => return Err("No such role".into()),
     };
-    if get!(ctx, Config, read).user_group_exists(role) {
+    let is_user_group = guild::data::<Config>(ctx, guild_id)
+        .await
+        .read()
+        .await
+        .user_group_exists(role);
+    if is_user_group {
         let member = msg.member(&ctx).await?;
         if member.roles.contains(&role) {
             member.remove_role(&ctx, role).await?;
@@ -71,13 +83,10 @@ pub async fn leave(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
 #[command("-l")]
 #[description("List user groups")]
 pub async fn list(ctx: &Context, msg: &Message) -> CommandResult {
-    let map = ctx.data.read().await;
-    let config = get!(> map, Config, read);
-    let guild = msg
-        .guild_id
-        .ok_or("Not in a server")?
-        .to_partial_guild(&ctx)
-        .await?;
+    let guild_id = msg.guild_id.ok_or("Not in a server")?;
+    let config = guild::data::<Config>(ctx, guild_id).await;
+    let config = config.read().await;
+    let guild = guild_id.to_partial_guild(&ctx).await?;
     msg.channel_id
         .send_message(
             &ctx,
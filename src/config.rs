@@ -1,19 +1,28 @@
-use crate::util::SendSyncError as Error;
+use crate::{
+    guild::{GuildData, GuildMap},
+    storage::{Storage, migrations},
+    util::SendSyncError as Error,
+};
 use serde::{Deserialize, Serialize};
 use serde_with::{DisplayFromStr, serde_as};
 use serenity::{
-    model::id::{ChannelId, RoleId},
-    prelude::{RwLock, TypeMapKey},
-};
-use std::{
-    collections::{HashMap, HashSet},
-    fs::File,
-    sync::Arc,
+    model::{
+        channel::{ChannelType, PermissionOverwrite, PermissionOverwriteType},
+        id::{ChannelId, GuildId, RoleId, UserId},
+        permissions::Permissions,
+    },
+    prelude::TypeMapKey,
 };
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// The deprecated category the single guild version of the bot had hardcoded.
+const LEGACY_DEPRECATED_CATEGORY: ChannelId = ChannelId::new(618553779192856577);
 
 #[serde_as]
-#[derive(Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Default, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Config {
+    #[serde(skip)]
+    guild: GuildId,
     #[serde(default)]
     allowed_channels: HashSet<ChannelId>,
     #[serde(default)]
@@ -27,17 +36,263 @@ pub struct Config {
     user_groups: HashMap<RoleId, String>,
     #[serde(default)]
     mute_role: Option<RoleId>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    mutes: Vec<Mute>,
+    #[serde(default)]
+    course_style: CourseStyle,
 }
 
-const CONFIG: &str = "data/config.json";
+/// A member who has the mute role until some time.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Mute {
+    pub user: UserId,
+    /// When the mute is lifted, as a unix timestamp.
+    pub until: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
 
-impl Config {
-    fn serialize(&self) -> Result<(), Error> {
-        serde_json::to_writer(File::create(CONFIG)?, self).map_err(|e| e.into())
+/// How the roles and categories of the courses look.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CourseStyle {
+    /// Where the channels of deprecated courses go.
+    pub deprecated_category: Option<ChannelId>,
+    /// The colour of the course roles of each year.
+    pub role_colours: BTreeMap<String, u64>,
+    /// The emoji in front of the course categories of each year.
+    pub category_emojis: BTreeMap<String, String>,
+    /// Sets of channels created for each course, by name.
+    pub channel_templates: BTreeMap<String, Vec<ChannelTemplate>>,
+    /// The set of channels of the courses of each year, when it isn't the default one.
+    pub year_templates: BTreeMap<String, String>,
+    /// The set of channels of a course, overriding the one of its year.
+    pub course_templates: BTreeMap<String, String>,
+}
+
+/// The set of channels used when no other is chosen.
+pub const DEFAULT_TEMPLATES: &str = "default";
+
+/// A channel created for each course, or for each extra topic of a course.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChannelTemplate {
+    /// The name of the channel, where `{name}` is replaced by the name of the course or topic.
+    pub name: String,
+    #[serde(default)]
+    pub kind: TemplateKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub topic: Option<String>,
+    /// Overwrites on top of the ones of the course category.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub overwrites: Vec<TemplateOverwrite>,
+    /// The tags of the posts of a forum channel.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TemplateKind {
+    #[default]
+    Text,
+    Voice,
+    Forum,
+}
+
+impl From<TemplateKind> for ChannelType {
+    fn from(kind: TemplateKind) -> Self {
+        match kind {
+            TemplateKind::Text => ChannelType::Text,
+            TemplateKind::Voice => ChannelType::Voice,
+            TemplateKind::Forum => ChannelType::Forum,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TemplateOverwrite {
+    /// `everyone`, `course` for the role of the course, or the id of a role.
+    pub role: String,
+    /// Names of permissions, like `SEND_MESSAGES`.
+    #[serde(default)]
+    pub allow: Vec<String>,
+    #[serde(default)]
+    pub deny: Vec<String>,
+}
+
+/// The set of channels with a forum for doubts.
+pub const FORUM_TEMPLATES: &str = "forum";
+
+/// The tag of the doubts that were answered.
+pub const RESOLVED_TAG: &str = "resolvido";
+
+impl ChannelTemplate {
+    fn text(name: &str) -> Self {
+        ChannelTemplate {
+            name: name.to_string(),
+            kind: TemplateKind::Text,
+            topic: None,
+            overwrites: Vec::new(),
+            tags: Vec::new(),
+        }
+    }
+
+    fn defaults() -> Vec<Self> {
+        vec![Self::text("duvidas-{name}"), Self::text("anexos-{name}")]
+    }
+
+    fn forum_defaults() -> Vec<Self> {
+        let duvidas = ChannelTemplate {
+            kind: TemplateKind::Forum,
+            topic: Some("Dúvidas de {name}".to_string()),
+            tags: [RESOLVED_TAG, "exame", "projeto"]
+                .map(str::to_string)
+                .into(),
+            ..Self::text("duvidas-{name}")
+        };
+        vec![duvidas, Self::text("anexos-{name}")]
+    }
+
+    pub fn name_for(&self, name: &str) -> String {
+        self.name.replace("{name}", name)
+    }
+
+    pub fn topic_for(&self, name: &str) -> Option<String> {
+        self.topic.as_ref().map(|t| t.replace("{name}", name))
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.name.contains("{name}") {
+            return Err(format!("The name {} has no {{name}}", self.name));
+        }
+        if !self.tags.is_empty() && self.kind != TemplateKind::Forum {
+            return Err(format!("Only forums have tags: {}", self.name));
+        }
+        for overwrite in &self.overwrites {
+            if !matches!(overwrite.role.as_str(), "everyone" | "course")
+                && overwrite.role.parse::<u64>().is_err()
+            {
+                return Err(format!("Invalid role: {}", overwrite.role));
+            }
+            if let Some(p) = overwrite
+                .allow
+                .iter()
+                .chain(&overwrite.deny)
+                .find(|p| Permissions::from_name(p).is_none())
+            {
+                return Err(format!("Invalid permission: {}", p));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl TemplateOverwrite {
+    pub fn to_overwrite(&self, guild: GuildId, course_role: RoleId) -> PermissionOverwrite {
+        let permissions = |names: &[String]| {
+            names
+                .iter()
+                .filter_map(|p| Permissions::from_name(p))
+                .fold(Permissions::empty(), |a, b| a | b)
+        };
+        let role = match self.role.as_str() {
+            "everyone" => RoleId::new(guild.get()),
+            "course" => course_role,
+            id => id.parse().map(RoleId::new).unwrap_or(course_role),
+        };
+        PermissionOverwrite {
+            allow: permissions(&self.allow),
+            deny: permissions(&self.deny),
+            kind: PermissionOverwriteType::Role(role),
+        }
+    }
+}
+
+impl Default for CourseStyle {
+    fn default() -> Self {
+        fn years<T>(values: [T; 5]) -> BTreeMap<String, T> {
+            ["1", "2", "3", "4", "5"]
+                .into_iter()
+                .map(str::to_string)
+                .zip(values)
+                .collect()
+        }
+        CourseStyle {
+            deprecated_category: None,
+            role_colours: years([0x843da4, 0x2ecc71, 0x498db, 0xf1c40f, 0x1e1e1e]),
+            category_emojis: years(["📚", "📗", "📘", "📙", "📓"].map(str::to_string)),
+            channel_templates: [
+                (DEFAULT_TEMPLATES.to_string(), ChannelTemplate::defaults()),
+                (
+                    FORUM_TEMPLATES.to_string(),
+                    ChannelTemplate::forum_defaults(),
+                ),
+            ]
+            .into(),
+            year_templates: BTreeMap::new(),
+            course_templates: BTreeMap::new(),
+        }
+    }
+}
+
+impl CourseStyle {
+    pub fn role_colour(&self, year: &str) -> u64 {
+        self.role_colours.get(year).copied().unwrap_or(0xffffff)
+    }
+
+    /// Whether a role has the colour of the course roles of some year.
+    pub fn is_course_colour(&self, colour: u64) -> bool {
+        self.role_colours.values().any(|c| *c == colour)
+    }
+
+    /// The channels of a course: the set chosen for the course, or else for its year, or else
+    /// the default one.
+    pub fn templates_for(&self, year: &str, course: &str) -> Vec<ChannelTemplate> {
+        let set = self
+            .course_templates
+            .get(course)
+            .or_else(|| self.year_templates.get(year))
+            .map_or(DEFAULT_TEMPLATES, String::as_str);
+        self.channel_templates
+            .get(set)
+            .or_else(|| self.channel_templates.get(DEFAULT_TEMPLATES))
+            .cloned()
+            .unwrap_or_else(ChannelTemplate::defaults)
+    }
+
+    /// The name a channel of a course gets when the course is renamed from `old` to `new`: the
+    /// one its template gives the new name or, for channels no template made, its name with
+    /// the old name of the course replaced. None when the name has nothing of the course in it.
+    pub fn renamed_channel(&self, channel: &str, old: &str, new: &str) -> Option<String> {
+        if let Some(template) = self
+            .channel_templates
+            .values()
+            .flatten()
+            .find(|t| t.name_for(old).eq_ignore_ascii_case(channel))
+        {
+            return Some(template.name_for(new));
+        }
+        let start = channel.to_lowercase().find(&old.to_lowercase())?;
+        Some(format!(
+            "{}{}{}",
+            channel.get(..start)?,
+            new,
+            channel.get(start + old.len()..)?
+        ))
+    }
+
+    /// The name of the category of a course.
+    pub fn category_name(&self, year: &str, course: &str) -> String {
+        match self.category_emojis.get(year) {
+            Some(emoji) => format!("{} {}", emoji, course),
+            None => course.to_string(),
+        }
     }
+}
 
-    pub fn new() -> Result<Self, Error> {
-        serde_json::from_reader(File::open(CONFIG)?).map_err(|e| e.into())
+impl Config {
+    fn serialize(&self) -> Result<(), Error> {
+        self.write().map_err(|e| e.into())
     }
 
     pub fn add_allowed_channel(&mut self, ch: ChannelId) -> Result<(), Error> {
@@ -120,8 +375,166 @@ impl Config {
         self.mute_role = Some(rl);
         Config::serialize(self)
     }
+
+    pub fn mutes(&self) -> &[Mute] {
+        &self.mutes
+    }
+
+    /// The mute of a member, if they are muted.
+    pub fn mute_of(&self, user: UserId) -> Option<&Mute> {
+        self.mutes.iter().find(|m| m.user == user)
+    }
+
+    /// Mutes a member, replacing the mute they had.
+    pub fn add_mute(&mut self, mute: Mute) -> Result<(), Error> {
+        self.mutes.retain(|m| m.user != mute.user);
+        self.mutes.push(mute);
+        Config::serialize(self)
+    }
+
+    pub fn remove_mute(&mut self, user: UserId) -> Result<Option<Mute>, Error> {
+        let Some(i) = self.mutes.iter().position(|m| m.user == user) else {
+            return Ok(None);
+        };
+        let mute = self.mutes.remove(i);
+        Config::serialize(self)?;
+        Ok(Some(mute))
+    }
+
+    pub fn course_style(&self) -> &CourseStyle {
+        &self.course_style
+    }
+
+    pub fn set_deprecated_category(&mut self, ch: Option<ChannelId>) -> Result<(), Error> {
+        self.course_style.deprecated_category = ch;
+        Config::serialize(self)
+    }
+
+    /// Gives the config of the single guild version of the bot the deprecated category it had
+    /// hardcoded, unless it has one. Doesn't save the config, as it's still being imported.
+    pub(crate) fn keep_legacy_deprecated_category(&mut self) {
+        self.course_style
+            .deprecated_category
+            .get_or_insert(LEGACY_DEPRECATED_CATEGORY);
+    }
+
+    pub fn set_role_colour(&mut self, year: &str, colour: u64) -> Result<(), Error> {
+        self.course_style
+            .role_colours
+            .insert(year.to_string(), colour);
+        Config::serialize(self)
+    }
+
+    pub fn set_channel_templates(
+        &mut self,
+        name: &str,
+        templates: Option<Vec<ChannelTemplate>>,
+    ) -> Result<(), Error> {
+        match templates {
+            Some(templates) => {
+                if let Some(e) = templates.iter().find_map(|t| t.validate().err()) {
+                    return Err(e.into());
+                }
+                self.course_style
+                    .channel_templates
+                    .insert(name.to_string(), templates);
+            }
+            None => {
+                let style = &self.course_style;
+                if style
+                    .year_templates
+                    .values()
+                    .chain(style.course_templates.values())
+                    .any(|s| s == name)
+                {
+                    return Err(format!("The templates {} are still in use", name).into());
+                }
+                self.course_style.channel_templates.remove(name);
+            }
+        }
+        Config::serialize(self)
+    }
+
+    pub fn set_year_templates(&mut self, year: &str, set: Option<String>) -> Result<(), Error> {
+        let selection = &mut self.course_style.year_templates;
+        Config::select_templates(selection, &self.course_style.channel_templates, year, set)?;
+        Config::serialize(self)
+    }
+
+    /// Chooses the set of channels of a course, by its name, not an alias.
+    pub fn set_course_templates(&mut self, course: &str, set: Option<String>) -> Result<(), Error> {
+        let selection = &mut self.course_style.course_templates;
+        Config::select_templates(selection, &self.course_style.channel_templates, course, set)?;
+        Config::serialize(self)
+    }
+
+    /// Keeps the set of channels chosen for a course when it's renamed.
+    pub fn rename_course_templates(&mut self, old: &str, new: &str) -> Result<(), Error> {
+        let selection = &mut self.course_style.course_templates;
+        let Some(set) = selection.remove(old) else {
+            return Ok(());
+        };
+        selection.insert(new.to_string(), set);
+        Config::serialize(self)
+    }
+
+    /// Forgets the set of channels chosen for a course that was removed.
+    pub fn forget_course_templates(&mut self, course: &str) -> Result<(), Error> {
+        if self.course_style.course_templates.remove(course).is_none() {
+            return Ok(());
+        }
+        Config::serialize(self)
+    }
+
+    fn select_templates(
+        selection: &mut BTreeMap<String, String>,
+        templates: &BTreeMap<String, Vec<ChannelTemplate>>,
+        key: &str,
+        set: Option<String>,
+    ) -> Result<(), Error> {
+        match set {
+            Some(set) if !templates.contains_key(&set) => {
+                return Err(format!("No such templates: {}", set).into());
+            }
+            Some(set) => selection.insert(key.to_string(), set),
+            None => selection.remove(key),
+        };
+        Ok(())
+    }
+
+    pub fn set_category_emoji(&mut self, year: &str, emoji: Option<String>) -> Result<(), Error> {
+        match emoji {
+            Some(emoji) => self
+                .course_style
+                .category_emojis
+                .insert(year.to_string(), emoji),
+            None => self.course_style.category_emojis.remove(year),
+        };
+        Config::serialize(self)
+    }
+}
+
+impl GuildData for Config {
+    const FILE: &'static str = "config.json";
+    const MIGRATIONS: &'static [migrations::Migration] = migrations::CONFIG;
+
+    fn load(storage: &dyn Storage, guild: GuildId) -> anyhow::Result<Option<Self>> {
+        storage.load_config(guild)
+    }
+
+    fn save(&self, storage: &dyn Storage) -> anyhow::Result<()> {
+        storage.save_config(self)
+    }
+
+    fn guild(&self) -> GuildId {
+        self.guild
+    }
+
+    fn set_guild(&mut self, guild: GuildId) {
+        self.guild = guild;
+    }
 }
 
 impl TypeMapKey for Config {
-    type Value = Arc<RwLock<Config>>;
+    type Value = GuildMap<Config>;
 }
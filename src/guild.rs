@@ -0,0 +1,86 @@
+//! State that is kept separately for each guild the bot is in.
+
+use crate::storage::{self, Json, Storage, migrations::Migration};
+use anyhow::{Context as _, anyhow};
+use serde::{Serialize, de::DeserializeOwned};
+use serenity::{
+    model::id::GuildId,
+    prelude::{Context, RwLock, TypeMapKey},
+};
+use std::{collections::HashMap, sync::Arc};
+
+pub type GuildMap<T> = Arc<RwLock<HashMap<GuildId, Arc<RwLock<T>>>>>;
+
+pub trait GuildData:
+    TypeMapKey<Value = GuildMap<Self>> + Serialize + DeserializeOwned + Default + Send + Sync + 'static
+{
+    /// Name of the document holding this state, used as the file name by the json storage.
+    const FILE: &'static str;
+
+    /// Steps that upgrade documents written by older versions of the bot.
+    const MIGRATIONS: &'static [Migration];
+
+    fn guild(&self) -> GuildId;
+
+    fn set_guild(&mut self, guild: GuildId);
+
+    fn load(storage: &dyn Storage, guild: GuildId) -> anyhow::Result<Option<Self>>;
+
+    fn save(&self, storage: &dyn Storage) -> anyhow::Result<()>;
+
+    fn new(guild: GuildId) -> Self {
+        let mut data = Self::default();
+        data.set_guild(guild);
+        data
+    }
+
+    fn write(&self) -> anyhow::Result<()> {
+        self.save(storage::get())
+    }
+}
+
+/// Loads the state of every guild known to the storage.
+///
+/// Fails if any stored state can't be read, so the bot never starts with an empty state on top
+/// of a damaged one. Also fails if a legacy file wasn't moved into a guild yet, as no guild
+/// would see its state.
+pub fn load_all<T: GuildData>() -> anyhow::Result<GuildMap<T>> {
+    let storage = storage::get();
+    if Json::default().read_legacy::<T>()?.is_some() {
+        return Err(anyhow!(
+            "data/{} is from a version of the bot that only supported one guild, start the bot \
+            with `--legacy-guild <guild id>` to move it into that guild",
+            T::FILE
+        ));
+    }
+    let guilds = storage
+        .guilds()?
+        .into_iter()
+        .map(|guild| {
+            let mut data = T::load(storage, guild)
+                .with_context(|| format!("Could not load {} for guild {}", T::FILE, guild))?
+                .unwrap_or_default();
+            data.set_guild(guild);
+            Ok((guild, Arc::new(RwLock::new(data))))
+        })
+        .collect::<anyhow::Result<_>>()?;
+    Ok(Arc::new(RwLock::new(guilds)))
+}
+
+/// Gets the state of a guild, creating it if the guild has none yet.
+pub async fn data<T: GuildData>(ctx: &Context, guild: GuildId) -> Arc<RwLock<T>> {
+    let share_map = ctx.data.read().await;
+    let guilds = share_map
+        .get::<T>()
+        .expect("Guild state was not initialized");
+    if let Some(data) = guilds.read().await.get(&guild) {
+        return Arc::clone(data);
+    }
+    Arc::clone(
+        guilds
+            .write()
+            .await
+            .entry(guild)
+            .or_insert_with(|| Arc::new(RwLock::new(T::new(guild)))),
+    )
+}
@@ -4,22 +4,29 @@
 
 use tokio as _;
 
+pub mod archive;
+pub mod catalogue;
 pub mod channels;
 pub mod commands;
 pub mod config;
+pub mod guild;
+pub mod storage;
+pub mod study_args;
 mod util;
 
 use crate::config::Config;
 use once_cell::sync::Lazy;
 use regex::Regex;
 use serenity::{
-    all::{ActivityData, Colour, CreateEmbed, CreateEmbedFooter, CreateMessage},
+    all::{
+        ActivityData, Colour, Command, CreateEmbed, CreateEmbedFooter, CreateMessage, Interaction,
+    },
     framework::standard::{
         Args, CommandGroup, CommandResult, DispatchError, HelpOptions, help_commands,
         macros::{help, hook},
     },
     model::{
-        channel::Message,
+        channel::{Channel, ChannelType, Message},
         gateway::Ready,
         guild::Member,
         id::{ChannelId, GuildId, UserId},
@@ -53,6 +60,13 @@ impl EventHandler for Handler {
     async fn ready(&self, ctx: Context, _ready: Ready) {
         ctx.set_presence(Some(ActivityData::playing("$man")), OnlineStatus::Online);
         crate::log!("Up and running");
+        if let Err(e) =
+            Command::create_global_command(&ctx.http, commands::study::study_slash_command()).await
+        {
+            crate::log!("Could not register the study slash command: {}", e);
+        }
+        commands::study::start_expiry_scheduler(ctx.clone());
+        commands::admin::start_mute_scheduler(ctx.clone());
         if let Some(id) = ctx.data.write().await.remove::<UpdateNotify>() {
             ChannelId::from(*id)
                 .send_message(&ctx, CreateMessage::new().content("Rebooted successfully!"))
@@ -61,9 +75,17 @@ impl EventHandler for Handler {
         }
     }
 
+    async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
+        commands::study::handle_menu_interaction(&ctx, &interaction).await;
+    }
+
     async fn guild_member_addition(&self, ctx: Context, new_member: Member) {
-        let share_map = ctx.data.read().await;
-        let config = get!(> share_map, Config, read);
+        if let Err(e) = commands::admin::remute(&ctx, new_member.guild_id, new_member.user.id).await
+        {
+            log!("Couldn't mute {} again: {:?}", new_member.user.name, e);
+        }
+        let config = guild::data::<Config>(&ctx, new_member.guild_id).await;
+        let config = config.read().await;
         if let (Some(ch), Some(greet_message)) =
             (config.greet_channel(), config.greet_channel_message())
         {
@@ -85,12 +107,12 @@ impl EventHandler for Handler {
     async fn guild_member_removal(
         &self,
         ctx: Context,
-        _: GuildId,
+        guild_id: GuildId,
         user: User,
         member_data: Option<Member>,
     ) {
-        let share_map = ctx.data.read().await;
-        let config = get!(> share_map, Config, read);
+        let config = guild::data::<Config>(&ctx, guild_id).await;
+        let config = config.read().await;
         if let Some(ch) = config.log_channel() {
             let (nick, avatar) = member_data
                 .as_ref()
@@ -126,6 +148,9 @@ impl EventHandler for Handler {
             Regex::new("(https?://)?(www.)?discord.(gg|li|me)/[[:alnum:]]{2,32}").unwrap()
         });
 
+        let Some(guild_id) = msg.guild_id else {
+            return;
+        };
         if INVITE.is_match(&msg.content) {
             let link = INVITE.find(&msg.content).unwrap().as_str();
             let guild = msg.guild(&ctx.cache).unwrap().clone();
@@ -146,8 +171,8 @@ impl EventHandler for Handler {
                     .await
                     .unwrap();
 
-                let share_map = ctx.data.read().await;
-                let config = get!(> share_map, Config, read);
+                let config = guild::data::<Config>(&ctx, guild_id).await;
+                let config = config.read().await;
 
                 if let Some(ch) = config.log_channel() {
                     let channel_name = match msg.channel(&ctx).await.unwrap().guild() {
@@ -208,7 +233,10 @@ async fn my_help(
 
 #[hook]
 pub async fn before_hook(ctx: &Context, msg: &Message, _: &str) -> bool {
-    valid_channel(ctx, msg).await || is_admin(ctx, msg).await || is_cesium_cmd(msg).await
+    valid_channel(ctx, msg).await
+        || is_admin(ctx, msg).await
+        || is_cesium_cmd(msg).await
+        || is_doubts_cmd(ctx, msg).await
 }
 
 #[hook]
@@ -264,7 +292,14 @@ pub async fn dispatch_error_hook(
 }
 
 pub async fn valid_channel(ctx: &Context, msg: &Message) -> bool {
-    get!(ctx, Config, read).channel_is_allowed(msg.channel_id)
+    match msg.guild_id {
+        Some(guild_id) => guild::data::<Config>(ctx, guild_id)
+            .await
+            .read()
+            .await
+            .channel_is_allowed(msg.channel_id),
+        None => false,
+    }
 }
 
 pub async fn is_admin(ctx: &Context, msg: &Message) -> bool {
@@ -285,3 +320,23 @@ pub async fn is_admin(ctx: &Context, msg: &Message) -> bool {
 pub async fn is_cesium_cmd(msg: &Message) -> bool {
     msg.content.split_whitespace().next() == Some("$cesium")
 }
+
+/// Doubts are handled in the posts of the course forums, outside of the allowed channels.
+pub async fn is_doubts_cmd(ctx: &Context, msg: &Message) -> bool {
+    if msg.content.split_whitespace().next() != Some("$duvidas") {
+        return false;
+    }
+    let Ok(Channel::Guild(thread)) = msg.channel(ctx).await else {
+        return false;
+    };
+    let Some(parent) = thread
+        .parent_id
+        .filter(|_| thread.thread_metadata.is_some())
+    else {
+        return false;
+    };
+    matches!(
+        parent.to_channel(ctx).await,
+        Ok(Channel::Guild(forum)) if forum.kind == ChannelType::Forum
+    )
+}
@@ -2,14 +2,30 @@
 #![expect(deprecated)] // standard framework is deprecated
 
 use rusteze::{
-    channels::{MiEI, read_courses},
+    channels::MiEI,
     commands::{admin::*, cesium::*, misc::*, study::*, usermod::*},
     config::Config,
+    guild::{GuildData, GuildMap, load_all},
+    storage::{self, Json, Sqlite, Storage},
     *,
 };
-use serenity::{all::standard::Configuration, framework::standard::StandardFramework, prelude::*};
+use serenity::{
+    all::standard::Configuration, framework::standard::StandardFramework, model::id::GuildId,
+    prelude::*,
+};
 use std::{fs, sync::Arc};
 
+fn load<T: GuildData>() -> GuildMap<T> {
+    match load_all() {
+        Ok(data) => data,
+        Err(e) => {
+            log!("Could not load {}, refusing to start", T::FILE);
+            log!("Error: {:#}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
 #[tokio::main]
 async fn main() {
     let token = match fs::read_to_string("data/auth") {
@@ -20,13 +36,42 @@ async fn main() {
             std::process::exit(1);
         }
     };
+    // The guild that takes the state left in data/ by versions that only supported one guild.
+    let legacy_guild = std::env::args()
+        .skip_while(|x| x != "--legacy-guild")
+        .nth(1)
+        .map(|id| match id.parse::<u64>() {
+            Ok(id) if id != 0 => GuildId::new(id),
+            _ => {
+                log!("{} is not a guild id", id);
+                std::process::exit(1);
+            }
+        });
+    let store: Box<dyn Storage> = if std::env::args().any(|a| a == "--json") {
+        if let Some(guild) = legacy_guild
+            && let Err(e) = Json::default().adopt_legacy(guild)
+        {
+            log!("Could not move the legacy files");
+            log!("Error: {:#}", e);
+            std::process::exit(1);
+        }
+        Box::new(Json::default())
+    } else {
+        match Sqlite::open("data/rusteze.db", legacy_guild) {
+            Ok(db) => Box::new(db),
+            Err(e) => {
+                log!("Could not open the database");
+                log!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+    };
+    storage::init(store);
     let mut client_builder = Client::builder(token, GatewayIntents::all())
         .event_handler(Handler)
-        .type_map_insert::<MiEI>(Arc::new(RwLock::new(read_courses().unwrap_or_default())))
-        .type_map_insert::<Config>(Arc::new(RwLock::new(Config::new().unwrap_or_default())))
-        .type_map_insert::<ChannelMapping>(Arc::new(RwLock::new(
-            ChannelMapping::load().unwrap_or_default(),
-        )))
+        .type_map_insert::<MiEI>(load::<MiEI>())
+        .type_map_insert::<Config>(load::<Config>())
+        .type_map_insert::<ChannelMapping>(load::<ChannelMapping>())
         .framework({
             let framework = StandardFramework::new();
             framework.configure(Configuration::new().prefix("$"));
@@ -36,6 +81,7 @@ async fn main() {
                 .on_dispatch_error(dispatch_error_hook)
                 .group(&STUDY_GROUP)
                 .group(&COURSES_GROUP)
+                .group(&DOUBTS_GROUP)
                 .group(&ADMIN_GROUP)
                 .group(&MISC_GROUP)
                 .group(&CESIUM_GROUP)
@@ -0,0 +1,43 @@
+//! Where the per guild state is persisted.
+
+mod json;
+pub mod migrations;
+pub mod sqlite;
+
+pub use json::Json;
+pub(crate) use json::write_atomically;
+pub use sqlite::Sqlite;
+
+use crate::{channels::MiEI, commands::cesium::ChannelMapping, config::Config};
+use once_cell::sync::OnceCell;
+use serenity::model::id::GuildId;
+
+pub trait Storage: Send + Sync {
+    /// Every guild that has some state stored.
+    fn guilds(&self) -> anyhow::Result<Vec<GuildId>>;
+
+    fn load_config(&self, guild: GuildId) -> anyhow::Result<Option<Config>>;
+
+    fn save_config(&self, config: &Config) -> anyhow::Result<()>;
+
+    fn load_courses(&self, guild: GuildId) -> anyhow::Result<Option<MiEI>>;
+
+    fn save_courses(&self, courses: &MiEI) -> anyhow::Result<()>;
+
+    fn load_mentor_rooms(&self, guild: GuildId) -> anyhow::Result<Option<ChannelMapping>>;
+
+    fn save_mentor_rooms(&self, rooms: &ChannelMapping) -> anyhow::Result<()>;
+}
+
+static STORAGE: OnceCell<Box<dyn Storage>> = OnceCell::new();
+
+/// Sets the storage used by the bot. Must be called once, before any state is loaded.
+pub fn init(storage: Box<dyn Storage>) {
+    if STORAGE.set(storage).is_err() {
+        panic!("Storage was already initialized");
+    }
+}
+
+pub fn get() -> &'static dyn Storage {
+    STORAGE.get().expect("Storage was not initialized").as_ref()
+}
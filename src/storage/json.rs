@@ -0,0 +1,208 @@
+//! One json file per kind of state, inside a `data/<guild_id>/` directory for each guild.
+
+use super::{Storage, migrations};
+use crate::{
+    channels::MiEI, commands::cesium::ChannelMapping, config::Config, guild::GuildData, log,
+};
+use anyhow::{Context, anyhow};
+use serenity::model::id::GuildId;
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+const DATA: &str = "data";
+
+/// The json files in a directory, `data/` unless told otherwise.
+pub struct Json {
+    dir: PathBuf,
+}
+
+impl Default for Json {
+    fn default() -> Self {
+        Json::in_dir(DATA)
+    }
+}
+
+fn read<T: GuildData>(path: &Path) -> anyhow::Result<Option<T>> {
+    match File::open(path) {
+        Ok(file) => serde_json::from_reader(io::BufReader::new(file))
+            .map_err(anyhow::Error::from)
+            .and_then(migrations::from_document)
+            .map(Some)
+            .with_context(|| format!("{} could not be parsed", path.display())),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).with_context(|| format!("{} could not be opened", path.display())),
+    }
+}
+
+/// Replaces the contents of `path` without ever leaving it half written.
+///
+/// The contents go to a temporary file that is synced to disk and then renamed over `path`, so
+/// a crash leaves either the old or the new file in place.
+pub(crate) fn write_atomically(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut tmp_name = path.as_os_str().to_owned();
+    tmp_name.push(".tmp");
+    let tmp = PathBuf::from(tmp_name);
+    let mut file = File::create(&tmp)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    drop(file);
+    fs::rename(&tmp, path)?;
+    if let Some(dir) = path.parent() {
+        File::open(dir)?.sync_all()?;
+    }
+    Ok(())
+}
+
+/// The state a single guild version of the bot kept directly in `data/`.
+#[derive(Default)]
+pub struct Legacy {
+    pub config: Option<Config>,
+    pub courses: Option<MiEI>,
+    pub mentor_rooms: Option<ChannelMapping>,
+}
+
+impl Legacy {
+    pub fn is_empty(&self) -> bool {
+        self.config.is_none() && self.courses.is_none() && self.mentor_rooms.is_none()
+    }
+}
+
+impl Json {
+    pub fn in_dir(dir: impl Into<PathBuf>) -> Self {
+        Json { dir: dir.into() }
+    }
+
+    fn guild_dir(&self, guild: GuildId) -> PathBuf {
+        self.dir.join(guild.to_string())
+    }
+
+    fn load<T: GuildData>(&self, guild: GuildId) -> anyhow::Result<Option<T>> {
+        read(&self.guild_dir(guild).join(T::FILE))
+    }
+
+    fn save<T: GuildData>(&self, data: &T) -> anyhow::Result<()> {
+        let dir = self.guild_dir(data.guild());
+        fs::create_dir_all(&dir)?;
+        let path = dir.join(T::FILE);
+        write_atomically(&path, &serde_json::to_vec(&migrations::to_document(data)?)?)
+            .with_context(|| format!("{} could not be written", path.display()))
+    }
+
+    /// Reads the file a single guild version of the bot kept directly in `data/`.
+    pub fn read_legacy<T: GuildData>(&self) -> anyhow::Result<Option<T>> {
+        read(&self.dir.join(T::FILE))
+    }
+
+    fn read_legacy_as<T: GuildData>(&self, guild: GuildId) -> anyhow::Result<Option<T>> {
+        let mut data = self.read_legacy::<T>()?;
+        if let Some(data) = &mut data {
+            data.set_guild(guild);
+        }
+        Ok(data)
+    }
+
+    /// Reads every legacy file as the state of `guild`.
+    pub fn legacy(&self, guild: GuildId) -> anyhow::Result<Legacy> {
+        let mut config = self.read_legacy_as::<Config>(guild)?;
+        if let Some(config) = &mut config {
+            config.keep_legacy_deprecated_category();
+        }
+        Ok(Legacy {
+            config,
+            courses: self.read_legacy_as(guild)?,
+            mentor_rooms: self.read_legacy_as(guild)?,
+        })
+    }
+
+    fn remove_legacy_file<T: GuildData>(&self) -> io::Result<()> {
+        match fs::remove_file(self.dir.join(T::FILE)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    /// Removes the legacy files, once their state is stored somewhere else.
+    pub fn remove_legacy(&self) -> io::Result<()> {
+        self.remove_legacy_file::<Config>()?;
+        self.remove_legacy_file::<MiEI>()?;
+        self.remove_legacy_file::<ChannelMapping>()
+    }
+
+    /// Moves the legacy files into the directory of `guild`.
+    ///
+    /// Refuses to if the guild already has some state, so nothing is overwritten.
+    pub fn adopt_legacy(&self, guild: GuildId) -> anyhow::Result<()> {
+        let legacy = self.legacy(guild)?;
+        if legacy.is_empty() {
+            return Ok(());
+        }
+        if self.has_state(guild)? {
+            return Err(anyhow!(
+                "Guild {} already has some state, the legacy files can't be moved into it",
+                guild
+            ));
+        }
+        if let Some(config) = &legacy.config {
+            self.save(config)?;
+        }
+        if let Some(courses) = &legacy.courses {
+            self.save(courses)?;
+        }
+        if let Some(rooms) = &legacy.mentor_rooms {
+            self.save(rooms)?;
+        }
+        self.remove_legacy()?;
+        log!("Moved the legacy files to guild {}", guild);
+        Ok(())
+    }
+
+    fn has_state(&self, guild: GuildId) -> anyhow::Result<bool> {
+        Ok(self.load_config(guild)?.is_some()
+            || self.load_courses(guild)?.is_some()
+            || self.load_mentor_rooms(guild)?.is_some())
+    }
+}
+
+impl Storage for Json {
+    fn guilds(&self) -> anyhow::Result<Vec<GuildId>> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(entries
+            .filter_map(Result::ok)
+            .filter(|e| e.path().is_dir())
+            .filter_map(|e| e.file_name().to_str()?.parse::<u64>().ok())
+            .filter(|&id| id != 0)
+            .map(GuildId::new)
+            .collect())
+    }
+
+    fn load_config(&self, guild: GuildId) -> anyhow::Result<Option<Config>> {
+        self.load(guild)
+    }
+
+    fn save_config(&self, config: &Config) -> anyhow::Result<()> {
+        self.save(config)
+    }
+
+    fn load_courses(&self, guild: GuildId) -> anyhow::Result<Option<MiEI>> {
+        self.load(guild)
+    }
+
+    fn save_courses(&self, courses: &MiEI) -> anyhow::Result<()> {
+        self.save(courses)
+    }
+
+    fn load_mentor_rooms(&self, guild: GuildId) -> anyhow::Result<Option<ChannelMapping>> {
+        self.load(guild)
+    }
+
+    fn save_mentor_rooms(&self, rooms: &ChannelMapping) -> anyhow::Result<()> {
+        self.save(rooms)
+    }
+}
@@ -0,0 +1,91 @@
+//! Versioning of the stored documents.
+//!
+//! Every document is saved with a `schema_version` field. Documents written before versioning
+//! existed have no such field and are version 0. When a document is loaded, the migrations from
+//! its version up to the current one are applied in order, the `n`th step upgrading a document
+//! from version `n` to version `n + 1`.
+
+use crate::guild::GuildData;
+use anyhow::{Context, anyhow};
+use serde_json::{Map, Value};
+
+const VERSION_KEY: &str = "schema_version";
+
+pub type Migration = fn(&mut Map<String, Value>) -> anyhow::Result<()>;
+
+/// Migrations of `courses.json`.
+pub const COURSES: &[Migration] = &[courses_v1];
+
+/// Migrations of `config.json`.
+pub const CONFIG: &[Migration] = &[stamp_version, config_v2];
+
+/// Migrations of `cesium_channels.json`.
+pub const MENTOR_ROOMS: &[Migration] = &[stamp_version];
+
+/// The version documents of this kind are currently written in.
+pub fn current_version<T: GuildData>() -> u64 {
+    T::MIGRATIONS.len() as u64
+}
+
+/// Upgrades a document to the current version and reads it.
+pub fn from_document<T: GuildData>(document: Value) -> anyhow::Result<T> {
+    let Value::Object(mut document) = document else {
+        return Err(anyhow!("{} is not a json object", T::FILE));
+    };
+    let version = match document.remove(VERSION_KEY) {
+        Some(v) => v
+            .as_u64()
+            .ok_or_else(|| anyhow!("Invalid {} in {}: {}", VERSION_KEY, T::FILE, v))?,
+        None => 0,
+    };
+    let current = current_version::<T>();
+    if version > current {
+        return Err(anyhow!(
+            "{} is version {}, but this version of the bot only understands up to version {}",
+            T::FILE,
+            version,
+            current
+        ));
+    }
+    for (from, migration) in T::MIGRATIONS.iter().enumerate().skip(version as usize) {
+        migration(&mut document)
+            .with_context(|| format!("Could not migrate {} from version {}", T::FILE, from))?;
+    }
+    Ok(serde_json::from_value(Value::Object(document))?)
+}
+
+/// Writes a document in the current version.
+pub fn to_document<T: GuildData>(data: &T) -> anyhow::Result<Value> {
+    let Value::Object(mut document) = serde_json::to_value(data)? else {
+        return Err(anyhow!("{} is not a json object", T::FILE));
+    };
+    document.insert(VERSION_KEY.into(), current_version::<T>().into());
+    Ok(Value::Object(document))
+}
+
+/// Version 1 only adds the version field, the rest of the document is unchanged.
+fn stamp_version(_: &mut Map<String, Value>) -> anyhow::Result<()> {
+    Ok(())
+}
+
+/// Version 2 makes the deprecated category configurable. Guilds start without one, only the
+/// legacy config of the single guild version keeps the category it had hardcoded.
+fn config_v2(document: &mut Map<String, Value>) -> anyhow::Result<()> {
+    document.insert(
+        "course_style".into(),
+        serde_json::json!({ "deprecated_category": null }),
+    );
+    Ok(())
+}
+
+/// Version 1 moves the years, which were keys of the document itself, into a `years` object so
+/// they can't clash with other fields.
+fn courses_v1(document: &mut Map<String, Value>) -> anyhow::Result<()> {
+    let deprecated_courses = document.remove("deprecated_courses");
+    let years = std::mem::take(document);
+    document.insert("years".into(), Value::Object(years));
+    if let Some(deprecated_courses) = deprecated_courses {
+        document.insert("deprecated_courses".into(), deprecated_courses);
+    }
+    Ok(())
+}
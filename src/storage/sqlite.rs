@@ -0,0 +1,472 @@
+//! Every guild's state in a single SQLite database.
+
+use super::{Json, Storage, migrations};
+use crate::{
+    channels::{Course, CourseInfo, Expiry, MiEI, Restrictions},
+    commands::cesium::ChannelMapping,
+    config::Config,
+    guild::GuildData,
+    log,
+};
+use anyhow::{Context, anyhow};
+use rusqlite::{Connection, OptionalExtension, params};
+use serenity::model::id::{ChannelId, GuildId, RoleId};
+use std::{
+    path::Path,
+    sync::{Mutex, MutexGuard},
+};
+
+/// Steps that create and upgrade the database, the `n`th one upgrading it from version `n` to
+/// version `n + 1`. The version of a database is kept in its `user_version`.
+pub const MIGRATIONS: &[&str] = &[
+    "
+CREATE TABLE config (
+    guild INTEGER NOT NULL,
+    key TEXT NOT NULL,
+    value TEXT NOT NULL,
+    PRIMARY KEY (guild, key)
+);
+CREATE TABLE semesters (
+    guild INTEGER NOT NULL,
+    year TEXT NOT NULL,
+    semester TEXT NOT NULL,
+    PRIMARY KEY (guild, year, semester)
+);
+-- deprecated courses have no year, semester or name
+CREATE TABLE courses (
+    id INTEGER PRIMARY KEY,
+    guild INTEGER NOT NULL,
+    year TEXT,
+    semester TEXT,
+    name TEXT,
+    role INTEGER NOT NULL
+);
+CREATE TABLE course_channels (
+    course INTEGER NOT NULL REFERENCES courses (id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    channel INTEGER NOT NULL,
+    PRIMARY KEY (course, position)
+);
+CREATE TABLE mentor_rooms (
+    guild INTEGER NOT NULL,
+    text_channel INTEGER NOT NULL PRIMARY KEY,
+    voice_channel INTEGER NOT NULL
+);
+CREATE TABLE mentor_counters (
+    guild INTEGER NOT NULL PRIMARY KEY,
+    last_number INTEGER NOT NULL
+);
+",
+    "
+ALTER TABLE courses ADD COLUMN full_name TEXT;
+",
+    "
+ALTER TABLE courses ADD COLUMN code TEXT;
+ALTER TABLE courses ADD COLUMN ects TEXT;
+ALTER TABLE courses ADD COLUMN syllabus_url TEXT;
+ALTER TABLE courses ADD COLUMN material_url TEXT;
+CREATE TABLE course_instructors (
+    course INTEGER NOT NULL REFERENCES courses (id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    PRIMARY KEY (course, position)
+);
+",
+    "
+CREATE TABLE course_aliases (
+    course INTEGER NOT NULL REFERENCES courses (id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    alias TEXT NOT NULL,
+    PRIMARY KEY (course, position)
+);
+",
+    "
+ALTER TABLE courses ADD COLUMN required_role INTEGER;
+ALTER TABLE courses ADD COLUMN max_courses INTEGER;
+ALTER TABLE courses ADD COLUMN approval INTEGER NOT NULL DEFAULT 0;
+",
+    "
+CREATE TABLE course_expiry (
+    guild INTEGER NOT NULL,
+    year TEXT NOT NULL,
+    semester TEXT,
+    date TEXT NOT NULL,
+    last_run TEXT
+);
+",
+];
+
+pub struct Sqlite {
+    conn: Mutex<Connection>,
+}
+
+impl Sqlite {
+    /// Opens the database, creating or upgrading it if needed.
+    ///
+    /// A new database is filled with the json files of the deployment, so switching storage
+    /// doesn't lose any state. The files a single guild version of the bot left in `data/` are
+    /// imported into `legacy_guild`, and removed once they are.
+    pub fn open(path: impl AsRef<Path>, legacy_guild: Option<GuildId>) -> anyhow::Result<Self> {
+        Sqlite::new(Connection::open(path)?, &Json::default(), legacy_guild)
+    }
+
+    /// Like [`Sqlite::open`], with a connection that is already open and the json files in
+    /// `json`.
+    pub fn new(
+        mut conn: Connection,
+        json: &Json,
+        legacy_guild: Option<GuildId>,
+    ) -> anyhow::Result<Self> {
+        conn.pragma_update(None, "foreign_keys", true)?;
+        let version: usize = conn.pragma_query_value(None, "user_version", |r| r.get(0))?;
+        if version > MIGRATIONS.len() {
+            return Err(anyhow!(
+                "The database is version {}, but this version of the bot only understands up to \
+                version {}",
+                version,
+                MIGRATIONS.len()
+            ));
+        }
+        let tx = conn.transaction()?;
+        for (from, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+            tx.execute_batch(migration)
+                .with_context(|| format!("Could not migrate the database from version {}", from))?;
+        }
+        tx.pragma_update(None, "user_version", MIGRATIONS.len())?;
+        if version == 0 {
+            import(&tx, json)?;
+        }
+        let legacy = match legacy_guild {
+            Some(guild) => import_legacy(&tx, json, guild)?,
+            None => false,
+        };
+        tx.commit()?;
+        if legacy {
+            json.remove_legacy()?;
+        }
+        Ok(Sqlite {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    fn conn(&self) -> anyhow::Result<MutexGuard<'_, Connection>> {
+        self.conn
+            .lock()
+            .map_err(|_| anyhow!("Database connection was poisoned"))
+    }
+
+    fn write<T>(&self, f: impl FnOnce(&Connection) -> anyhow::Result<T>) -> anyhow::Result<T> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction()?;
+        let r = f(&tx)?;
+        tx.commit()?;
+        Ok(r)
+    }
+}
+
+fn import(conn: &Connection, from: &dyn Storage) -> anyhow::Result<()> {
+    for guild in from.guilds()? {
+        if let Some(mut config) = from.load_config(guild)? {
+            config.set_guild(guild);
+            write_config(conn, &config)?;
+        }
+        if let Some(mut courses) = from.load_courses(guild)? {
+            courses.set_guild(guild);
+            write_courses(conn, &courses)?;
+        }
+        if let Some(mut rooms) = from.load_mentor_rooms(guild)? {
+            rooms.set_guild(guild);
+            write_mentor_rooms(conn, &rooms)?;
+        }
+        log!("Imported the state of guild {} into the database", guild);
+    }
+    Ok(())
+}
+
+/// Imports the legacy files into `guild`, returning whether there were any.
+///
+/// Refuses to if the guild already has some state, so nothing is overwritten.
+fn import_legacy(conn: &Connection, json: &Json, guild: GuildId) -> anyhow::Result<bool> {
+    let legacy = json.legacy(guild)?;
+    if legacy.is_empty() {
+        return Ok(false);
+    }
+    let has_state = conn
+        .query_row(
+            "SELECT 1 FROM config WHERE guild = ?1
+             UNION SELECT 1 FROM semesters WHERE guild = ?1
+             UNION SELECT 1 FROM courses WHERE guild = ?1
+             UNION SELECT 1 FROM mentor_counters WHERE guild = ?1",
+            [guild.get()],
+            |_| Ok(()),
+        )
+        .optional()?
+        .is_some();
+    if has_state {
+        return Err(anyhow!(
+            "Guild {} already has some state, the legacy files can't be imported into it",
+            guild
+        ));
+    }
+    if let Some(config) = &legacy.config {
+        write_config(conn, config)?;
+    }
+    if let Some(courses) = &legacy.courses {
+        write_courses(conn, courses)?;
+    }
+    if let Some(rooms) = &legacy.mentor_rooms {
+        write_mentor_rooms(conn, rooms)?;
+    }
+    log!("Imported the legacy files into guild {}", guild);
+    Ok(true)
+}
+
+fn write_config(conn: &Connection, config: &Config) -> anyhow::Result<()> {
+    let guild = config.guild().get();
+    conn.execute("DELETE FROM config WHERE guild = ?1", [guild])?;
+    let serde_json::Value::Object(entries) = migrations::to_document(config)? else {
+        return Err(anyhow!("Config is not a json object"));
+    };
+    let mut insert = conn.prepare("INSERT INTO config (guild, key, value) VALUES (?1, ?2, ?3)")?;
+    for (key, value) in entries {
+        insert.execute(params![guild, key, value.to_string()])?;
+    }
+    Ok(())
+}
+
+fn write_courses(conn: &Connection, courses: &MiEI) -> anyhow::Result<()> {
+    let guild = courses.guild().get();
+    conn.execute("DELETE FROM semesters WHERE guild = ?1", [guild])?;
+    conn.execute("DELETE FROM courses WHERE guild = ?1", [guild])?;
+    conn.execute("DELETE FROM course_expiry WHERE guild = ?1", [guild])?;
+    let mut insert_expiry = conn.prepare(
+        "INSERT INTO course_expiry (guild, year, semester, date, last_run) \
+        VALUES (?1, ?2, ?3, ?4, ?5)",
+    )?;
+    for e in courses.expiry() {
+        insert_expiry.execute(params![guild, e.year, e.semester, e.date, e.last_run])?;
+    }
+    let mut insert_semester =
+        conn.prepare("INSERT INTO semesters (guild, year, semester) VALUES (?1, ?2, ?3)")?;
+    for (year, semester) in courses.semesters() {
+        insert_semester.execute(params![guild, year, semester])?;
+    }
+    let mut insert_course = conn.prepare(
+        "INSERT INTO courses (guild, year, semester, name, role, full_name, code, ects, \
+        syllabus_url, material_url, required_role, max_courses, approval) \
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+    )?;
+    let mut insert_channel = conn
+        .prepare("INSERT INTO course_channels (course, position, channel) VALUES (?1, ?2, ?3)")?;
+    let mut insert_instructor = conn
+        .prepare("INSERT INTO course_instructors (course, position, name) VALUES (?1, ?2, ?3)")?;
+    let mut insert_alias =
+        conn.prepare("INSERT INTO course_aliases (course, position, alias) VALUES (?1, ?2, ?3)")?;
+    let courses = courses
+        .courses()
+        .map(|(y, s, n, c)| (Some(y), Some(s), Some(n), c))
+        .chain(
+            courses
+                .deprecated_courses()
+                .iter()
+                .map(|c| (None, None, None, c)),
+        );
+    for (year, semester, name, course) in courses {
+        insert_course.execute(params![
+            guild,
+            year,
+            semester,
+            name,
+            course.role.get(),
+            course.info.full_name,
+            course.info.code,
+            course.info.ects,
+            course.info.syllabus_url,
+            course.info.material_url,
+            course.restrictions.required_role.map(RoleId::get),
+            course.restrictions.max_courses,
+            course.restrictions.approval,
+        ])?;
+        let id = conn.last_insert_rowid();
+        for (position, channel) in course.channels.iter().enumerate() {
+            insert_channel.execute(params![id, position, channel.get()])?;
+        }
+        for (position, instructor) in course.info.instructors.iter().enumerate() {
+            insert_instructor.execute(params![id, position, instructor])?;
+        }
+        for (position, alias) in course.aliases.iter().enumerate() {
+            insert_alias.execute(params![id, position, alias])?;
+        }
+    }
+    Ok(())
+}
+
+fn write_mentor_rooms(conn: &Connection, rooms: &ChannelMapping) -> anyhow::Result<()> {
+    let guild = rooms.guild().get();
+    conn.execute("DELETE FROM mentor_rooms WHERE guild = ?1", [guild])?;
+    conn.execute(
+        "INSERT OR REPLACE INTO mentor_counters (guild, last_number) VALUES (?1, ?2)",
+        params![guild, rooms.last_number()],
+    )?;
+    let mut insert = conn.prepare(
+        "INSERT INTO mentor_rooms (guild, text_channel, voice_channel) VALUES (?1, ?2, ?3)",
+    )?;
+    for (text, voice) in rooms.rooms() {
+        insert.execute(params![guild, text.get(), voice.get()])?;
+    }
+    Ok(())
+}
+
+impl Storage for Sqlite {
+    fn guilds(&self) -> anyhow::Result<Vec<GuildId>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT guild FROM config
+             UNION SELECT guild FROM semesters
+             UNION SELECT guild FROM courses
+             UNION SELECT guild FROM mentor_counters",
+        )?;
+        stmt.query_map([], |r| r.get::<_, u64>(0))?
+            .map(|g| Ok(GuildId::new(g?)))
+            .collect()
+    }
+
+    fn load_config(&self, guild: GuildId) -> anyhow::Result<Option<Config>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare("SELECT key, value FROM config WHERE guild = ?1")?;
+        let entries = stmt
+            .query_map([guild.get()], |r| {
+                Ok((r.get::<_, String>(0)?, r.get::<_, String>(1)?))
+            })?
+            .map(|e| {
+                let (key, value) = e?;
+                Ok((key, serde_json::from_str(&value)?))
+            })
+            .collect::<anyhow::Result<serde_json::Map<_, _>>>()?;
+        if entries.is_empty() {
+            return Ok(None);
+        }
+        let mut config: Config = migrations::from_document(serde_json::Value::Object(entries))?;
+        config.set_guild(guild);
+        Ok(Some(config))
+    }
+
+    fn save_config(&self, config: &Config) -> anyhow::Result<()> {
+        self.write(|conn| write_config(conn, config))
+    }
+
+    fn load_courses(&self, guild: GuildId) -> anyhow::Result<Option<MiEI>> {
+        let conn = self.conn()?;
+        let semesters = conn
+            .prepare("SELECT year, semester FROM semesters WHERE guild = ?1")?
+            .query_map([guild.get()], |r| {
+                Ok((r.get::<_, String>(0)?, r.get::<_, String>(1)?))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        let courses = conn
+            .prepare(
+                "SELECT id, year, semester, name, role, full_name, code, ects, syllabus_url, \
+                material_url, required_role, max_courses, approval FROM courses WHERE guild = ?1 \
+                ORDER BY id",
+            )?
+            .query_map([guild.get()], |r| {
+                Ok((
+                    r.get::<_, i64>(0)?,
+                    r.get::<_, Option<String>>(1)?,
+                    r.get::<_, Option<String>>(2)?,
+                    r.get::<_, Option<String>>(3)?,
+                    Course {
+                        role: RoleId::new(r.get(4)?),
+                        channels: Vec::new(),
+                        aliases: Vec::new(),
+                        restrictions: Restrictions {
+                            required_role: r.get::<_, Option<u64>>(10)?.map(RoleId::new),
+                            max_courses: r.get(11)?,
+                            approval: r.get(12)?,
+                        },
+                        info: CourseInfo {
+                            full_name: r.get(5)?,
+                            code: r.get(6)?,
+                            ects: r.get(7)?,
+                            instructors: Vec::new(),
+                            syllabus_url: r.get(8)?,
+                            material_url: r.get(9)?,
+                        },
+                    },
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        let expiry = conn
+            .prepare(
+                "SELECT year, semester, date, last_run FROM course_expiry WHERE guild = ?1 \
+                ORDER BY rowid",
+            )?
+            .query_map([guild.get()], |r| {
+                Ok(Expiry {
+                    year: r.get(0)?,
+                    semester: r.get(1)?,
+                    date: r.get(2)?,
+                    last_run: r.get(3)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        if semesters.is_empty() && courses.is_empty() {
+            return Ok(None);
+        }
+        let mut channels = conn
+            .prepare("SELECT channel FROM course_channels WHERE course = ?1 ORDER BY position")?;
+        let mut instructors = conn
+            .prepare("SELECT name FROM course_instructors WHERE course = ?1 ORDER BY position")?;
+        let mut aliases =
+            conn.prepare("SELECT alias FROM course_aliases WHERE course = ?1 ORDER BY position")?;
+        let mut active = Vec::new();
+        let mut deprecated = Vec::new();
+        for (id, year, semester, name, mut course) in courses {
+            course.channels = channels
+                .query_map([id], |r| Ok(ChannelId::new(r.get(0)?)))?
+                .collect::<Result<_, _>>()?;
+            course.info.instructors = instructors
+                .query_map([id], |r| r.get(0))?
+                .collect::<Result<_, _>>()?;
+            course.aliases = aliases
+                .query_map([id], |r| r.get(0))?
+                .collect::<Result<_, _>>()?;
+            match (year, semester, name) {
+                (Some(y), Some(s), Some(n)) => active.push((y, s, n, course)),
+                _ => deprecated.push(course),
+            }
+        }
+        Ok(Some(MiEI::from_parts(
+            guild, semesters, active, deprecated, expiry,
+        )))
+    }
+
+    fn save_courses(&self, courses: &MiEI) -> anyhow::Result<()> {
+        self.write(|conn| write_courses(conn, courses))
+    }
+
+    fn load_mentor_rooms(&self, guild: GuildId) -> anyhow::Result<Option<ChannelMapping>> {
+        let conn = self.conn()?;
+        let Some(last_number) = conn
+            .query_row(
+                "SELECT last_number FROM mentor_counters WHERE guild = ?1",
+                [guild.get()],
+                |r| r.get::<_, u32>(0),
+            )
+            .optional()?
+        else {
+            return Ok(None);
+        };
+        let rooms = conn
+            .prepare("SELECT text_channel, voice_channel FROM mentor_rooms WHERE guild = ?1")?
+            .query_map([guild.get()], |r| {
+                Ok((ChannelId::new(r.get(0)?), ChannelId::new(r.get(1)?)))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Some(ChannelMapping::from_parts(guild, last_number, rooms)))
+    }
+
+    fn save_mentor_rooms(&self, rooms: &ChannelMapping) -> anyhow::Result<()> {
+        self.write(|conn| write_mentor_rooms(conn, rooms))
+    }
+}
@@ -0,0 +1,342 @@
+//! The arguments of `$study` and `$unstudy`: the courses a student wants, as a list of courses,
+//! prefixes and years, each of which can instead take courses out of the ones before it.
+//!
+//! ```text
+//! args     = item { [","] item }
+//! item     = ["-"] selector
+//! selector = years [semester] | PREFIX "*" | COURSE
+//! years    = N ["-" N] ("ano" | "anos")
+//! semester = N ["sem" | "semestre"] | ("sem" | "semestre") N
+//! ```
+//!
+//! Spaces are optional around the parts of years and semesters, so `2ano1sem`, `2ano 1sem`,
+//! `2 ano 1 semestre` and `2ano 1` are all the first semester of the second year, and a
+//! number can be written as an ordinal, as in `1º ano`.
+
+use std::{fmt, ops::Range};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Token<'a> {
+    Comma,
+    Minus,
+    /// A number on its own, like the 1 in `1 ano`.
+    Number(u32),
+    /// A range of years on its own, like `1-2` in `1-2 anos`.
+    Range(u32, u32),
+    /// `ano` or `anos` on their own.
+    Ano,
+    /// `sem` or `semestre` on their own.
+    Sem,
+    /// Years written together, like `1ano` or `1-2anos`.
+    Years(u32, u32),
+    /// A semester written together, like `1sem` or the `1sem` of `2ano1sem`.
+    Semester(u32),
+    /// The prefix of the names of courses, like `LI` in `LI*`.
+    Wildcard(&'a str),
+    /// Anything else, which is the name of a course.
+    Word(&'a str),
+}
+
+/// A token and where it is in the arguments.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Spanned<'a> {
+    pub token: Token<'a>,
+    pub span: Range<usize>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Selector<'a> {
+    /// The courses of the years `from` to `to`, or only of one of their semesters.
+    Years {
+        from: u32,
+        to: u32,
+        semester: Option<u32>,
+    },
+    /// The courses whose names start with a prefix.
+    Wildcard(&'a str),
+    /// A course, by its name or alias.
+    Course(&'a str),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Item<'a> {
+    /// Whether the courses are taken out of the ones selected before, like `-SD` in
+    /// `3ano -SD`.
+    pub exclude: bool,
+    pub selector: Selector<'a>,
+    /// The item as it was written.
+    pub text: &'a str,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseError {
+    /// A `-` with nothing after it.
+    DanglingMinus,
+    /// A range of years whose end comes before its start, like `2-1ano`.
+    BackwardsRange(u32, u32),
+    /// A semester that doesn't follow a year, like `1sem` on its own.
+    SemesterWithoutYear(String),
+    /// Something that can't start an item, like `ano` on its own.
+    Unexpected(String),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::DanglingMinus => write!(f, "Falta o que tirar depois do `-`."),
+            ParseError::BackwardsRange(from, to) => {
+                write!(f, "Os anos `{}-{}` estão ao contrário.", from, to)
+            }
+            ParseError::SemesterWithoutYear(s) => {
+                write!(f, "Falta o ano do semestre `{}`, como em `2ano1sem`.", s)
+            }
+            ParseError::Unexpected(s) => write!(f, "Não percebi o `{}`.", s),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// `s` without `prefix`, ignoring case.
+fn strip_prefix_ci<'a>(s: &'a str, prefix: &str) -> Option<&'a str> {
+    let head = s.get(..prefix.len())?;
+    head.eq_ignore_ascii_case(prefix)
+        .then(|| &s[prefix.len()..])
+}
+
+/// The number at the start of `s`, possibly an ordinal, and what follows it.
+fn number(s: &str) -> Option<(u32, &str)> {
+    let end = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let n = s[..end].parse().ok()?;
+    let rest = &s[end..];
+    Some((n, rest.strip_prefix(['º', 'ª']).unwrap_or(rest)))
+}
+
+fn is_sem(s: &str) -> bool {
+    s.eq_ignore_ascii_case("sem") || s.eq_ignore_ascii_case("semestre")
+}
+
+fn is_ano(s: &str) -> bool {
+    s.eq_ignore_ascii_case("ano") || s.eq_ignore_ascii_case("anos")
+}
+
+/// The semester written after `ano` in the same word, like the `1sem` of `2ano1sem`.
+fn semester_suffix(s: &str) -> Option<Option<u32>> {
+    if s.is_empty() {
+        return Some(None);
+    }
+    let (n, rest) = number(s)?;
+    (rest.is_empty() || is_sem(rest)).then_some(Some(n))
+}
+
+/// Years written together, like `1ano`, `1-2anos` or `2ano1sem`.
+fn years(from: u32, rest: &str) -> Option<(u32, u32, Option<u32>)> {
+    let (to, rest) = match rest.strip_prefix('-').and_then(number) {
+        Some((to, rest)) => (to, rest),
+        None => (from, rest),
+    };
+    let rest = strip_prefix_ci(rest, "anos").or_else(|| strip_prefix_ci(rest, "ano"))?;
+    Some((from, to, semester_suffix(rest)?))
+}
+
+/// The tokens of a word, which has no spaces or commas.
+fn word_tokens<'a>(word: &'a str, start: usize, tokens: &mut Vec<Spanned<'a>>) {
+    let span = start..start + word.len();
+    let mut push = |token| {
+        tokens.push(Spanned {
+            token,
+            span: span.clone(),
+        })
+    };
+    if let Some(prefix) = word.strip_suffix('*')
+        && !prefix.is_empty()
+        && !prefix.contains('*')
+    {
+        return push(Token::Wildcard(prefix));
+    }
+    if is_ano(word) {
+        return push(Token::Ano);
+    }
+    if is_sem(word) {
+        return push(Token::Sem);
+    }
+    let Some((n, rest)) = number(word) else {
+        return push(Token::Word(word));
+    };
+    if rest.is_empty() {
+        return push(Token::Number(n));
+    }
+    if let Some(range) = rest.strip_prefix('-').and_then(number)
+        && range.1.is_empty()
+    {
+        return push(Token::Range(n, range.0));
+    }
+    if is_sem(rest) {
+        return push(Token::Semester(n));
+    }
+    match years(n, rest) {
+        Some((from, to, semester)) => {
+            push(Token::Years(from, to));
+            if let Some(semester) = semester {
+                push(Token::Semester(semester));
+            }
+        }
+        None => push(Token::Word(word)),
+    }
+}
+
+/// Splits the arguments into tokens.
+pub fn tokenize(input: &str) -> Vec<Spanned<'_>> {
+    let mut tokens = Vec::new();
+    let words = input
+        .char_indices()
+        .filter(|(_, c)| c.is_whitespace() || *c == ',')
+        .chain([(input.len(), ' ')]);
+    let mut start = 0;
+    for (end, c) in words {
+        let word = &input[start..end];
+        if let Some(rest) = word.strip_prefix('-') {
+            tokens.push(Spanned {
+                token: Token::Minus,
+                span: start..start + 1,
+            });
+            if !rest.is_empty() {
+                word_tokens(rest, start + 1, &mut tokens);
+            }
+        } else if !word.is_empty() {
+            word_tokens(word, start, &mut tokens);
+        }
+        if c == ',' {
+            tokens.push(Spanned {
+                token: Token::Comma,
+                span: end..end + 1,
+            });
+        }
+        start = end + c.len_utf8();
+    }
+    tokens
+}
+
+struct Parser<'a, 't> {
+    input: &'a str,
+    tokens: &'t [Spanned<'a>],
+    next: usize,
+}
+
+impl<'a> Parser<'a, '_> {
+    fn peek(&self) -> Option<Token<'a>> {
+        self.tokens.get(self.next).map(|t| t.token)
+    }
+
+    fn bump(&mut self) -> Option<Token<'a>> {
+        let token = self.peek();
+        self.next += 1;
+        token
+    }
+
+    fn text(&self, token: usize) -> &'a str {
+        &self.input[self.tokens[token].span.clone()]
+    }
+
+    /// The semester after some years, if there is one.
+    fn semester(&mut self) -> Result<Option<u32>, ParseError> {
+        match self.peek() {
+            Some(Token::Semester(n)) => {
+                self.bump();
+                Ok(Some(n))
+            }
+            Some(Token::Number(n)) => {
+                self.bump();
+                if self.peek() == Some(Token::Sem) {
+                    self.bump();
+                }
+                Ok(Some(n))
+            }
+            Some(Token::Sem) => {
+                self.bump();
+                match self.bump() {
+                    Some(Token::Number(n)) => Ok(Some(n)),
+                    _ => Err(ParseError::Unexpected(self.text(self.next - 2).to_string())),
+                }
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn years(&mut self, from: u32, to: u32) -> Result<Selector<'a>, ParseError> {
+        if from > to {
+            return Err(ParseError::BackwardsRange(from, to));
+        }
+        Ok(Selector::Years {
+            from,
+            to,
+            semester: self.semester()?,
+        })
+    }
+
+    /// Years written apart from `ano`, or else a course with a strange name.
+    fn years_or_course(
+        &mut self,
+        from: u32,
+        to: u32,
+        start: usize,
+    ) -> Result<Selector<'a>, ParseError> {
+        if self.peek() == Some(Token::Ano) {
+            self.bump();
+            self.years(from, to)
+        } else {
+            Ok(Selector::Course(self.text(start)))
+        }
+    }
+
+    fn item(&mut self) -> Result<Item<'a>, ParseError> {
+        let first = self.next;
+        let exclude = self.peek() == Some(Token::Minus);
+        if exclude {
+            self.bump();
+        }
+        let start = self.next;
+        let selector = match self.bump() {
+            None | Some(Token::Comma) => return Err(ParseError::DanglingMinus),
+            Some(Token::Minus) => return Err(ParseError::Unexpected("-".to_string())),
+            Some(Token::Years(from, to)) => self.years(from, to)?,
+            Some(Token::Number(n)) => self.years_or_course(n, n, start)?,
+            Some(Token::Range(from, to)) => self.years_or_course(from, to, start)?,
+            Some(Token::Semester(_)) => {
+                return Err(ParseError::SemesterWithoutYear(
+                    self.text(start).to_string(),
+                ));
+            }
+            Some(Token::Ano | Token::Sem) => {
+                return Err(ParseError::Unexpected(self.text(start).to_string()));
+            }
+            Some(Token::Wildcard(prefix)) => Selector::Wildcard(prefix),
+            Some(Token::Word(word)) => Selector::Course(word),
+        };
+        let span = self.tokens[first].span.start..self.tokens[self.next - 1].span.end;
+        Ok(Item {
+            exclude,
+            selector,
+            text: &self.input[span],
+        })
+    }
+}
+
+/// Parses the arguments into the items the student asked for, in order.
+pub fn parse(input: &str) -> Result<Vec<Item<'_>>, ParseError> {
+    let tokens = tokenize(input);
+    let mut parser = Parser {
+        input,
+        tokens: &tokens,
+        next: 0,
+    };
+    let mut items = Vec::new();
+    while let Some(token) = parser.peek() {
+        if token == Token::Comma {
+            parser.bump();
+        } else {
+            items.push(parser.item()?);
+        }
+    }
+    Ok(items)
+}
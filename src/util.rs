@@ -1,5 +1,21 @@
+use serenity::{
+    http::StatusCode,
+    model::{
+        channel::{Message, ReactionType},
+        id::{ChannelId, UserId},
+    },
+    prelude::Context,
+};
+use std::time::Duration;
+
 pub type SendSyncError = Box<dyn std::error::Error + Send + Sync>;
 
+/// Whether a request failed because what it was about no longer exists, like a channel deleted
+/// by hand.
+pub fn is_not_found(e: &serenity::Error) -> bool {
+    matches!(e, serenity::Error::Http(e) if e.status_code() == Some(StatusCode::NOT_FOUND))
+}
+
 #[macro_export]
 macro_rules! get {
     ($ctx:ident, $t:ty) => {
@@ -59,3 +75,57 @@ macro_rules! get {
             .await
     };
 }
+
+const CONFIRM: &str = "✅";
+const CANCEL: &str = "❌";
+
+/// Asks `user` to confirm what is described in `prompt`, by reacting to it.
+///
+/// Returns `false` if the user cancels or doesn't answer within a minute.
+pub async fn confirm(ctx: &Context, prompt: &Message, user: UserId) -> serenity::Result<bool> {
+    prompt
+        .react(ctx, ReactionType::Unicode(CONFIRM.into()))
+        .await?;
+    prompt
+        .react(ctx, ReactionType::Unicode(CANCEL.into()))
+        .await?;
+    let reaction = prompt
+        .await_reaction(ctx)
+        .author_id(user)
+        .filter(|r| r.emoji.unicode_eq(CONFIRM) || r.emoji.unicode_eq(CANCEL))
+        .timeout(Duration::from_secs(60))
+        .await;
+    Ok(reaction.is_some_and(|r| r.emoji.unicode_eq(CONFIRM)))
+}
+
+/// Sends `lines` as code blocks, split over as many messages as needed to stay under Discord's
+/// message size limit. Returns the last message sent.
+pub async fn say_code_blocks(
+    ctx: &Context,
+    channel: ChannelId,
+    lang: &str,
+    lines: impl IntoIterator<Item = String>,
+) -> serenity::Result<Option<Message>> {
+    const LIMIT: usize = 1900;
+    let mut blocks = vec![String::new()];
+    for line in lines {
+        if blocks
+            .last()
+            .is_some_and(|b| !b.is_empty() && b.len() + line.len() >= LIMIT)
+        {
+            blocks.push(String::new());
+        }
+        let block = blocks.last_mut().unwrap();
+        block.push_str(&line);
+        block.push('\n');
+    }
+    let mut last = None;
+    for block in blocks.into_iter().filter(|b| !b.is_empty()) {
+        last = Some(
+            channel
+                .say(ctx, format!("```{}\n{}```", lang, block))
+                .await?,
+        );
+    }
+    Ok(last)
+}
@@ -0,0 +1,132 @@
+use rusteze::archive::{
+    Archive, ArchivedAttachment, ArchivedChannel, ArchivedEmbed, ArchivedMessage,
+};
+use serenity::model::id::{ChannelId, GuildId};
+use std::{env, fs, path::PathBuf};
+
+fn message(author: &str, content: &str) -> ArchivedMessage {
+    ArchivedMessage {
+        author: author.to_string(),
+        author_id: 1,
+        timestamp: "2024-03-01T10:00:00Z".to_string(),
+        content: content.to_string(),
+        attachments: Vec::new(),
+        embeds: Vec::new(),
+    }
+}
+
+fn channel(id: u64, name: &str, messages: Vec<ArchivedMessage>) -> ArchivedChannel {
+    ArchivedChannel {
+        id: ChannelId::new(id),
+        name: name.to_string(),
+        topic: None,
+        messages,
+        threads: Vec::new(),
+    }
+}
+
+fn archive() -> Archive {
+    let mut doubts = channel(
+        1,
+        "duvidas-li3",
+        vec![message("ana", "a < b && c > d"), message("rui", "\"sim\"")],
+    );
+    doubts.topic = Some("Dúvidas de <LI3>".to_string());
+    doubts.messages[0].attachments.push(ArchivedAttachment {
+        filename: "enunciado.pdf".to_string(),
+        url: "https://cdn.example/enunciado.pdf?a=1&b=2".to_string(),
+    });
+    doubts.messages[1].embeds.push(ArchivedEmbed {
+        title: Some("<script>".to_string()),
+        ..Default::default()
+    });
+    doubts
+        .threads
+        .push(channel(2, "guião 1", vec![message("ana", "linha\noutra")]));
+    Archive {
+        course: "LI3".to_string(),
+        guild: GuildId::new(42),
+        archived_at: "2024-03-02T12:30:15+00:00".to_string(),
+        channels: vec![doubts, channel(3, "anexos-li3", Vec::new())],
+    }
+}
+
+/// An empty directory of its own for a test.
+fn scratch(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("rusteze-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+#[test]
+fn counts_the_messages_of_threads_too() {
+    assert_eq!(archive().message_count(), 3);
+    assert_eq!(
+        Archive {
+            channels: Vec::new(),
+            ..archive()
+        }
+        .message_count(),
+        0
+    );
+}
+
+#[test]
+fn saved_archives_load_back() {
+    let dir = scratch("archive-load");
+    let archive = archive();
+    let path = archive.save_in(&dir).unwrap();
+    assert_eq!(path, dir.join("LI3-2024-03-02-123015.json"));
+    assert!(dir.join("LI3-2024-03-02-123015.html").exists());
+    assert_eq!(Archive::load(&path).unwrap(), archive);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn archives_made_in_the_same_second_get_a_counter() {
+    let dir = scratch("archive-counter");
+    let archive = archive();
+    let first = archive.save_in(&dir).unwrap();
+    let second = archive.save_in(&dir).unwrap();
+    assert_ne!(first, second);
+    assert_eq!(second, dir.join("LI3-2024-03-02-123015-2.json"));
+    assert_eq!(
+        Archive::load(&first).unwrap(),
+        Archive::load(&second).unwrap()
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn loading_something_else_fails() {
+    let dir = scratch("archive-invalid");
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("x.json");
+    fs::write(&path, "{}").unwrap();
+    assert!(Archive::load(&path).is_err());
+    assert!(Archive::load(&dir.join("missing.json")).is_err());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn html_escapes_everything_written_by_users() {
+    let html = archive().to_html();
+    assert!(html.contains("a &lt; b &amp;&amp; c &gt; d"));
+    assert!(html.contains("&quot;sim&quot;"));
+    assert!(html.contains("Dúvidas de &lt;LI3&gt;"));
+    assert!(html.contains("href=\"https://cdn.example/enunciado.pdf?a=1&amp;b=2\""));
+    assert!(html.contains("<b>&lt;script&gt;</b>"));
+    assert!(!html.contains("<script>"));
+    assert!(html.contains("linha<br>outra"));
+}
+
+#[test]
+fn html_has_every_channel_and_thread() {
+    let html = archive().to_html();
+    assert!(html.starts_with("<!DOCTYPE html>"));
+    assert!(html.contains("<h1>LI3</h1>"));
+    for name in ["#duvidas-li3", "#guião 1", "#anexos-li3"] {
+        assert!(html.contains(name), "{} is missing", name);
+    }
+    assert!(html.ends_with("</body></html>\n"));
+}
@@ -0,0 +1,86 @@
+use rusteze::{
+    catalogue::{Catalogue, Format},
+    channels::MiEI,
+    config::{ChannelTemplate, CourseStyle, TemplateKind},
+    storage::migrations::from_document,
+};
+use serde_json::json;
+use serenity::model::id::ChannelId;
+use std::collections::HashMap;
+
+#[test]
+fn codes_are_uppercased() {
+    let catalogue = Catalogue::parse("year,semester,code\n1,1,pi\n", Format::Csv).unwrap();
+    assert_eq!(catalogue.courses[0].code, "PI");
+}
+
+#[test]
+fn duplicate_codes_are_rejected() {
+    let toml = r#"
+        [[course]]
+        year = 1
+        semester = 1
+        code = "PI"
+
+        [[course]]
+        year = 2
+        semester = 1
+        code = "pi"
+    "#;
+    let error = Catalogue::parse(toml, Format::Toml).unwrap_err();
+    assert!(error.to_string().contains("PI"), "{}", error);
+}
+
+#[test]
+fn exports_the_extra_channels_of_the_templates_of_each_course() {
+    let miei: MiEI = from_document(json!({
+        "schema_version": 1,
+        "years": {
+            "1": {
+                "1": {
+                    "PI": { "role": "1", "channels": ["10", "11", "12", "13", "14"] },
+                    "LI3": { "role": "2", "channels": ["20", "21", "22", "23", "24"] }
+                }
+            }
+        }
+    }))
+    .unwrap();
+    let mut style = CourseStyle::default();
+    let template = |name: &str| ChannelTemplate {
+        name: name.to_string(),
+        kind: TemplateKind::Text,
+        topic: None,
+        overwrites: Vec::new(),
+        tags: Vec::new(),
+    };
+    style.channel_templates.insert(
+        "lab".to_string(),
+        vec![template("{name}-lab"), template("forum-{name}")],
+    );
+    style
+        .course_templates
+        .insert("LI3".to_string(), "lab".to_string());
+    let channel_names = [
+        (10, "PI"),
+        (11, "duvidas-pi"),
+        (12, "anexos-pi"),
+        (13, "duvidas-exames"),
+        (14, "anexos-exames"),
+        (20, "LI3"),
+        (21, "li3-lab"),
+        (22, "forum-li3"),
+        (23, "projeto-lab"),
+        (24, "forum-projeto"),
+    ]
+    .into_iter()
+    .map(|(id, name)| (ChannelId::new(id), name.to_string()))
+    .collect();
+    let catalogue = Catalogue::from_courses(&miei, &style, &channel_names, &HashMap::new());
+    let channels = catalogue
+        .courses
+        .iter()
+        .map(|e| (e.code.as_str(), e.channels.clone()))
+        .collect::<HashMap<_, _>>();
+    assert_eq!(channels["PI"], ["exames"]);
+    assert_eq!(channels["LI3"], ["projeto"]);
+}
@@ -0,0 +1,35 @@
+use rusteze::{channels::MiEI, storage::migrations::from_document};
+use serde_json::json;
+
+fn courses() -> MiEI {
+    from_document(json!({
+        "schema_version": 1,
+        "years": {
+            "1": {
+                "1": {
+                    "ALGEBRA-LINEAR": { "role": "1", "channels": [], "aliases": ["ALC"] }
+                }
+            }
+        },
+        "deprecated_courses": [
+            { "role": "2", "channels": [], "aliases": ["CALC"] }
+        ]
+    }))
+    .unwrap()
+}
+
+#[test]
+fn names_and_aliases_are_taken_in_any_case() {
+    let courses = courses();
+    assert!(courses.name_taken("algebra-linear"));
+    assert!(courses.name_taken("alc"));
+    assert!(courses.name_taken("Calc"));
+    assert!(!courses.name_taken("PI"));
+}
+
+#[test]
+fn aliases_resolve_to_their_course() {
+    let courses = courses();
+    assert_eq!(courses.resolve("alc"), Some("ALGEBRA-LINEAR"));
+    assert_eq!(courses.resolve("CALC"), None);
+}
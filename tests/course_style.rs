@@ -0,0 +1,50 @@
+use rusteze::config::{ChannelTemplate, CourseStyle, TemplateKind};
+
+fn style() -> CourseStyle {
+    let mut style = CourseStyle::default();
+    let template = |name: &str| ChannelTemplate {
+        name: name.to_string(),
+        kind: TemplateKind::Voice,
+        topic: None,
+        overwrites: Vec::new(),
+        tags: Vec::new(),
+    };
+    style.channel_templates.insert(
+        "projetos".to_string(),
+        vec![template("{name}-voz"), template("projeto-final-{name}")],
+    );
+    style
+}
+
+#[test]
+fn renamed_channels_follow_their_template() {
+    let style = style();
+    assert_eq!(
+        style
+            .renamed_channel("duvidas-li3", "LI3", "LI4")
+            .as_deref(),
+        Some("duvidas-LI4")
+    );
+    assert_eq!(
+        style.renamed_channel("LI3-voz", "LI3", "LI4").as_deref(),
+        Some("LI4-voz")
+    );
+    assert_eq!(
+        style
+            .renamed_channel("projeto-final-li3", "LI3", "LI4")
+            .as_deref(),
+        Some("projeto-final-LI4")
+    );
+}
+
+#[test]
+fn renamed_channels_without_a_template_replace_the_name() {
+    let style = style();
+    assert_eq!(
+        style
+            .renamed_channel("grupos-li3-2024", "LI3", "LI4")
+            .as_deref(),
+        Some("grupos-LI4-2024")
+    );
+    assert_eq!(style.renamed_channel("geral", "LI3", "LI4"), None);
+}
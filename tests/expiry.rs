@@ -0,0 +1,64 @@
+use chrono::NaiveDate;
+use rusteze::channels::Expiry;
+
+fn expiry(date: &str, last_run: Option<&str>) -> Expiry {
+    Expiry {
+        year: "1".to_string(),
+        semester: Some("1".to_string()),
+        date: date.to_string(),
+        last_run: last_run.map(str::to_string),
+    }
+}
+
+fn day(s: &str) -> NaiveDate {
+    NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+}
+
+#[test]
+fn due_on_the_date() {
+    let e = expiry("02-01", Some("2024-09-15"));
+    assert!(!e.is_due(day("2025-01-31")));
+    assert!(e.is_due(day("2025-02-01")));
+}
+
+#[test]
+fn not_due_again_after_running() {
+    let e = expiry("02-01", Some("2025-02-01"));
+    assert!(!e.is_due(day("2025-02-01")));
+    assert!(!e.is_due(day("2025-12-31")));
+    assert!(e.is_due(day("2026-02-01")));
+}
+
+#[test]
+fn missed_dates_are_caught_up() {
+    let e = expiry("02-01", Some("2024-09-15"));
+    assert!(e.is_due(day("2025-02-03")));
+    assert!(e.is_due(day("2025-08-30")));
+}
+
+#[test]
+fn missed_dates_without_a_last_run_are_caught_up_for_a_while() {
+    let e = expiry("02-01", None);
+    assert!(e.is_due(day("2025-02-01")));
+    assert!(e.is_due(day("2025-02-04")));
+    assert!(e.is_due(day("2025-02-08")));
+    assert!(!e.is_due(day("2025-02-09")));
+    assert!(!e.is_due(day("2025-01-31")));
+}
+
+#[test]
+fn leap_day_falls_on_the_28th_in_other_years() {
+    let e = expiry("02-29", Some("2024-02-29"));
+    assert!(!e.is_due(day("2025-02-27")));
+    assert!(e.is_due(day("2025-02-28")));
+    let e = expiry("02-29", Some("2027-02-28"));
+    assert!(!e.is_due(day("2027-12-31")));
+    assert!(!e.is_due(day("2028-02-28")));
+    assert!(e.is_due(day("2028-02-29")));
+}
+
+#[test]
+fn invalid_dates_are_never_due() {
+    let e = expiry("13-01", None);
+    assert!(!e.is_due(day("2025-01-13")));
+}
@@ -0,0 +1,99 @@
+use rusteze::{
+    channels::MiEI,
+    commands::cesium::ChannelMapping,
+    config::Config,
+    guild::GuildData,
+    storage::migrations::{current_version, from_document, to_document},
+};
+use serde_json::{Value, json};
+use std::{fmt::Debug, fs, path::Path};
+
+fn fixture(version: u32, file: &str) -> Value {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(format!("v{}", version))
+        .join(file);
+    serde_json::from_str(&fs::read_to_string(&path).unwrap())
+        .unwrap_or_else(|e| panic!("{} is not valid json: {}", path.display(), e))
+}
+
+/// Every older fixture must load to the same state as the fixture in the current format.
+fn upgrades_every_version<T: GuildData + PartialEq + Debug>() {
+    let current = current_version::<T>() as u32;
+    let expected = from_document::<T>(fixture(current, T::FILE)).unwrap();
+    for version in 0..current {
+        let upgraded = from_document::<T>(fixture(version, T::FILE))
+            .unwrap_or_else(|e| panic!("v{} {} failed to migrate: {:#}", version, T::FILE, e));
+        assert_eq!(upgraded, expected, "v{} {}", version, T::FILE);
+    }
+}
+
+fn round_trips<T: GuildData + PartialEq + Debug>() {
+    let data = from_document::<T>(fixture(current_version::<T>() as u32, T::FILE)).unwrap();
+    let document = to_document(&data).unwrap();
+    assert_eq!(document["schema_version"], json!(current_version::<T>()));
+    assert_eq!(from_document::<T>(document).unwrap(), data);
+}
+
+fn rejects_newer_versions<T: GuildData + Debug>() {
+    let mut document = fixture(current_version::<T>() as u32, T::FILE);
+    document["schema_version"] = json!(current_version::<T>() + 1);
+    assert!(from_document::<T>(document).is_err());
+}
+
+#[test]
+fn courses_upgrade_every_version() {
+    upgrades_every_version::<MiEI>();
+}
+
+#[test]
+fn courses_v0_years_move_into_years() {
+    let document = to_document(&from_document::<MiEI>(fixture(0, MiEI::FILE)).unwrap()).unwrap();
+    assert_eq!(
+        document["years"]["3"]["2"]["SD"]["role"],
+        "618540301819543552"
+    );
+    assert_eq!(document["years"]["1"]["2"], json!({}));
+    assert!(document.get("1").is_none());
+    assert_eq!(document["deprecated_courses"].as_array().unwrap().len(), 1);
+}
+
+#[test]
+fn courses_round_trip() {
+    round_trips::<MiEI>();
+}
+
+#[test]
+fn courses_reject_newer_versions() {
+    rejects_newer_versions::<MiEI>();
+}
+
+#[test]
+fn config_upgrades_every_version() {
+    upgrades_every_version::<Config>();
+}
+
+#[test]
+fn config_round_trips() {
+    round_trips::<Config>();
+}
+
+#[test]
+fn config_rejects_newer_versions() {
+    rejects_newer_versions::<Config>();
+}
+
+#[test]
+fn mentor_rooms_upgrade_every_version() {
+    upgrades_every_version::<ChannelMapping>();
+}
+
+#[test]
+fn mentor_rooms_round_trip() {
+    round_trips::<ChannelMapping>();
+}
+
+#[test]
+fn mentor_rooms_reject_newer_versions() {
+    rejects_newer_versions::<ChannelMapping>();
+}
@@ -0,0 +1,55 @@
+use rusteze::commands::admin::parse_duration;
+use std::time::Duration;
+
+fn secs(s: &str) -> Option<u64> {
+    parse_duration(s).map(|d| d.as_secs())
+}
+
+#[test]
+fn every_unit() {
+    assert_eq!(secs("45s"), Some(45));
+    assert_eq!(secs("30m"), Some(30 * 60));
+    assert_eq!(secs("2h"), Some(2 * 60 * 60));
+    assert_eq!(secs("3d"), Some(3 * 24 * 60 * 60));
+    assert_eq!(secs("1w"), Some(7 * 24 * 60 * 60));
+    assert_eq!(secs("2H"), Some(2 * 60 * 60));
+}
+
+#[test]
+fn units_add_up() {
+    assert_eq!(
+        parse_duration("1d12h"),
+        Some(Duration::from_secs(36 * 60 * 60))
+    );
+    assert_eq!(secs("1h30m15s"), Some(60 * 60 + 30 * 60 + 15));
+    assert_eq!(secs("10m10m"), Some(20 * 60));
+}
+
+#[test]
+fn numbers_need_a_unit() {
+    assert_eq!(secs("30"), None);
+    assert_eq!(secs("1h30"), None);
+    assert_eq!(secs("h"), None);
+    assert_eq!(secs("1hm"), None);
+    assert_eq!(secs(""), None);
+}
+
+#[test]
+fn unknown_units_are_refused() {
+    assert_eq!(secs("2y"), None);
+    assert_eq!(secs("-2h"), None);
+    assert_eq!(secs("2 h"), None);
+}
+
+#[test]
+fn nothing_is_not_a_duration() {
+    assert_eq!(secs("0s"), None);
+    assert_eq!(secs("0d0h"), None);
+}
+
+#[test]
+fn overflowing_durations_are_refused() {
+    assert_eq!(secs("99999999999999999999s"), None);
+    assert_eq!(secs(&format!("{}w", u64::MAX / 60)), None);
+    assert_eq!(secs(&format!("{}s1s", u64::MAX)), None);
+}
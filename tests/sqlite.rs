@@ -0,0 +1,296 @@
+use rusqlite::{Connection, params};
+use rusteze::{
+    channels::MiEI,
+    commands::cesium::ChannelMapping,
+    config::Config,
+    guild::GuildData,
+    storage::{
+        Json, Storage,
+        migrations::{current_version, from_document, to_document},
+        sqlite::{MIGRATIONS, Sqlite},
+    },
+};
+use serde_json::{Value, json};
+use serenity::model::id::{ChannelId, GuildId};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+const GUILD: GuildId = GuildId::new(618540281000000000);
+
+fn fixture_path(version: u32, file: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(format!("v{}", version))
+        .join(file)
+}
+
+fn fixture<T: GuildData>(version: u32) -> T {
+    let path = fixture_path(version, T::FILE);
+    let document: Value = serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap();
+    let mut data = from_document::<T>(document).unwrap();
+    data.set_guild(GUILD);
+    data
+}
+
+/// The fixture in the current format.
+fn current<T: GuildData>() -> T {
+    fixture(current_version::<T>() as u32)
+}
+
+/// An empty directory of its own for a test.
+fn scratch(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("rusteze-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn empty_database() -> Sqlite {
+    Sqlite::new(
+        Connection::open_in_memory().unwrap(),
+        &Json::in_dir(scratch("sqlite-no-json")),
+        None,
+    )
+    .unwrap()
+}
+
+#[test]
+fn a_new_database_is_empty() {
+    let db = empty_database();
+    assert!(db.guilds().unwrap().is_empty());
+    assert_eq!(db.load_config(GUILD).unwrap(), None);
+    assert_eq!(db.load_courses(GUILD).unwrap(), None);
+    assert_eq!(db.load_mentor_rooms(GUILD).unwrap(), None);
+}
+
+#[test]
+fn config_round_trips() {
+    let db = empty_database();
+    let config = current::<Config>();
+    db.save_config(&config).unwrap();
+    assert_eq!(db.load_config(GUILD).unwrap(), Some(config));
+    assert_eq!(db.guilds().unwrap(), vec![GUILD]);
+}
+
+#[test]
+fn courses_round_trip() {
+    let db = empty_database();
+    let courses = current::<MiEI>();
+    db.save_courses(&courses).unwrap();
+    assert_eq!(db.load_courses(GUILD).unwrap(), Some(courses.clone()));
+    // Saving again replaces the courses instead of adding to them.
+    db.save_courses(&courses).unwrap();
+    assert_eq!(db.load_courses(GUILD).unwrap(), Some(courses));
+}
+
+#[test]
+fn mentor_rooms_round_trip() {
+    let db = empty_database();
+    let rooms = current::<ChannelMapping>();
+    db.save_mentor_rooms(&rooms).unwrap();
+    assert_eq!(db.load_mentor_rooms(GUILD).unwrap(), Some(rooms.clone()));
+    db.save_mentor_rooms(&rooms).unwrap();
+    assert_eq!(db.load_mentor_rooms(GUILD).unwrap(), Some(rooms));
+}
+
+#[test]
+fn guilds_are_kept_apart() {
+    let db = empty_database();
+    let mut other = current::<MiEI>();
+    other.set_guild(GuildId::new(1));
+    db.save_courses(&current::<MiEI>()).unwrap();
+    db.save_courses(&other).unwrap();
+    db.save_courses(&MiEI::new(GuildId::new(1))).unwrap();
+    assert_eq!(db.load_courses(GUILD).unwrap(), Some(current::<MiEI>()));
+    assert_eq!(db.load_courses(GuildId::new(1)).unwrap(), None);
+}
+
+/// A database left at `version`, with state written the way the first version of the schema
+/// stores it.
+fn database_at(version: usize) -> Connection {
+    let conn = Connection::open_in_memory().unwrap();
+    for migration in &MIGRATIONS[..version] {
+        conn.execute_batch(migration).unwrap();
+    }
+    conn.pragma_update(None, "user_version", version).unwrap();
+    let guild = GUILD.get();
+    let Value::Object(config) = to_document(&current::<Config>()).unwrap() else {
+        panic!("config is not an object");
+    };
+    for (key, value) in config {
+        conn.execute(
+            "INSERT INTO config (guild, key, value) VALUES (?1, ?2, ?3)",
+            params![guild, key, value.to_string()],
+        )
+        .unwrap();
+    }
+    conn.execute(
+        "INSERT INTO semesters (guild, year, semester) VALUES (?1, '1', '1')",
+        [guild],
+    )
+    .unwrap();
+    conn.execute(
+        "INSERT INTO courses (guild, year, semester, name, role) VALUES (?1, '1', '1', 'PI', 10)",
+        [guild],
+    )
+    .unwrap();
+    let course = conn.last_insert_rowid();
+    conn.execute(
+        "INSERT INTO course_channels (course, position, channel) VALUES (?1, 0, 20), (?1, 1, 21)",
+        [course],
+    )
+    .unwrap();
+    conn.execute(
+        "INSERT INTO mentor_rooms (guild, text_channel, voice_channel) VALUES (?1, 30, 31)",
+        [guild],
+    )
+    .unwrap();
+    conn.execute(
+        "INSERT INTO mentor_counters (guild, last_number) VALUES (?1, 3)",
+        [guild],
+    )
+    .unwrap();
+    conn
+}
+
+#[test]
+fn upgrades_from_every_version() {
+    let mut courses = from_document::<MiEI>(json!({
+        "schema_version": 1,
+        "years": { "1": { "1": { "PI": { "role": "10", "channels": ["20", "21"] } } } }
+    }))
+    .unwrap();
+    courses.set_guild(GUILD);
+    let mut rooms = from_document::<ChannelMapping>(json!({
+        "schema_version": 1,
+        "last_number": 3,
+        "channels": { "30": "31" }
+    }))
+    .unwrap();
+    rooms.set_guild(GUILD);
+    let json = Json::in_dir(scratch("sqlite-upgrade"));
+    for version in 1..MIGRATIONS.len() {
+        let db = Sqlite::new(database_at(version), &json, None)
+            .unwrap_or_else(|e| panic!("v{} failed to upgrade: {:#}", version, e));
+        assert_eq!(
+            db.load_config(GUILD).unwrap(),
+            Some(current::<Config>()),
+            "v{}",
+            version
+        );
+        assert_eq!(
+            db.load_courses(GUILD).unwrap(),
+            Some(courses.clone()),
+            "v{}",
+            version
+        );
+        assert_eq!(
+            db.load_mentor_rooms(GUILD).unwrap(),
+            Some(rooms.clone()),
+            "v{}",
+            version
+        );
+    }
+}
+
+#[test]
+fn newer_databases_are_refused() {
+    let conn = Connection::open_in_memory().unwrap();
+    conn.pragma_update(None, "user_version", MIGRATIONS.len() + 1)
+        .unwrap();
+    assert!(Sqlite::new(conn, &Json::in_dir(scratch("sqlite-newer")), None).is_err());
+}
+
+/// Copies the fixtures of a version into `dir`, for `guild` or, without one, as legacy files.
+fn copy_fixtures(version: u32, dir: &Path, guild: Option<GuildId>) {
+    let dir = match guild {
+        Some(guild) => dir.join(guild.to_string()),
+        None => dir.to_path_buf(),
+    };
+    fs::create_dir_all(&dir).unwrap();
+    for file in [Config::FILE, MiEI::FILE, ChannelMapping::FILE] {
+        let from = fixture_path(version, file);
+        if from.exists() {
+            fs::copy(from, dir.join(file)).unwrap();
+        }
+    }
+}
+
+#[test]
+fn new_databases_import_the_json_files() {
+    for version in [0, 1] {
+        let dir = scratch(&format!("sqlite-import-v{}", version));
+        copy_fixtures(version, &dir, Some(GUILD));
+        let db = Sqlite::new(
+            Connection::open_in_memory().unwrap(),
+            &Json::in_dir(&dir),
+            None,
+        )
+        .unwrap();
+        assert_eq!(db.guilds().unwrap(), vec![GUILD], "v{}", version);
+        assert_eq!(db.load_config(GUILD).unwrap(), Some(current::<Config>()));
+        assert_eq!(db.load_courses(GUILD).unwrap(), Some(current::<MiEI>()));
+        assert_eq!(
+            db.load_mentor_rooms(GUILD).unwrap(),
+            Some(current::<ChannelMapping>())
+        );
+        // The json files are kept, in case the database has to be thrown away.
+        assert!(dir.join(GUILD.to_string()).join(MiEI::FILE).exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
+
+#[test]
+fn legacy_files_are_imported_into_the_legacy_guild() {
+    let dir = scratch("sqlite-legacy");
+    copy_fixtures(0, &dir, None);
+    let db = Sqlite::new(
+        Connection::open_in_memory().unwrap(),
+        &Json::in_dir(&dir),
+        Some(GUILD),
+    )
+    .unwrap();
+    assert_eq!(db.guilds().unwrap(), vec![GUILD]);
+    let config = db.load_config(GUILD).unwrap().unwrap();
+    assert_eq!(
+        config.course_style().deprecated_category,
+        Some(ChannelId::new(618553779192856577))
+    );
+    assert_eq!(db.load_courses(GUILD).unwrap(), Some(current::<MiEI>()));
+    assert_eq!(
+        db.load_mentor_rooms(GUILD).unwrap(),
+        Some(current::<ChannelMapping>())
+    );
+    for file in [Config::FILE, MiEI::FILE, ChannelMapping::FILE] {
+        assert!(!dir.join(file).exists(), "{} was not removed", file);
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn legacy_files_never_overwrite_a_guild() {
+    let dir = scratch("sqlite-legacy-taken");
+    copy_fixtures(0, &dir, Some(GUILD));
+    copy_fixtures(0, &dir, None);
+    let json = Json::in_dir(&dir);
+    assert!(Sqlite::new(Connection::open_in_memory().unwrap(), &json, Some(GUILD)).is_err());
+    assert!(dir.join(MiEI::FILE).exists());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn without_a_legacy_guild_legacy_files_are_left_alone() {
+    let dir = scratch("sqlite-legacy-none");
+    copy_fixtures(0, &dir, None);
+    let db = Sqlite::new(
+        Connection::open_in_memory().unwrap(),
+        &Json::in_dir(&dir),
+        None,
+    )
+    .unwrap();
+    assert!(db.guilds().unwrap().is_empty());
+    assert!(dir.join(MiEI::FILE).exists());
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,290 @@
+use rusteze::study_args::{Item, ParseError, Selector, Token, parse, tokenize};
+
+fn tokens(input: &str) -> Vec<Token<'_>> {
+    tokenize(input).into_iter().map(|t| t.token).collect()
+}
+
+fn selectors(input: &str) -> Vec<(bool, Selector<'_>)> {
+    parse(input)
+        .unwrap_or_else(|e| panic!("{:?} failed to parse: {}", input, e))
+        .into_iter()
+        .map(|i| (i.exclude, i.selector))
+        .collect()
+}
+
+fn years(from: u32, to: u32, semester: Option<u32>) -> Selector<'static> {
+    Selector::Years { from, to, semester }
+}
+
+#[test]
+fn empty_arguments_select_nothing() {
+    assert_eq!(parse("").unwrap(), vec![]);
+    assert_eq!(parse("   ").unwrap(), vec![]);
+    assert_eq!(parse(" , ,, ").unwrap(), vec![]);
+}
+
+#[test]
+fn tokenizes_words_numbers_and_punctuation() {
+    assert_eq!(
+        tokens("LI3, 1 ano -SD PI*"),
+        vec![
+            Token::Word("LI3"),
+            Token::Comma,
+            Token::Number(1),
+            Token::Ano,
+            Token::Minus,
+            Token::Word("SD"),
+            Token::Wildcard("PI"),
+        ]
+    );
+}
+
+#[test]
+fn tokenizes_years_written_together() {
+    assert_eq!(tokens("1ano"), vec![Token::Years(1, 1)]);
+    assert_eq!(tokens("1-2anos"), vec![Token::Years(1, 2)]);
+    assert_eq!(
+        tokens("2ano1sem"),
+        vec![Token::Years(2, 2), Token::Semester(1)]
+    );
+    assert_eq!(tokens("1-2"), vec![Token::Range(1, 2)]);
+    assert_eq!(tokens("1sem"), vec![Token::Semester(1)]);
+}
+
+#[test]
+fn spans_point_into_the_input() {
+    let input = "LI3  -SD";
+    let spans = tokenize(input)
+        .into_iter()
+        .map(|t| &input[t.span])
+        .collect::<Vec<_>>();
+    assert_eq!(spans, vec!["LI3", "-", "SD"]);
+}
+
+#[test]
+fn courses_are_kept_as_written() {
+    assert_eq!(
+        selectors("LI3 algebra-linear Pi"),
+        vec![
+            (false, Selector::Course("LI3")),
+            (false, Selector::Course("algebra-linear")),
+            (false, Selector::Course("Pi")),
+        ]
+    );
+}
+
+#[test]
+fn comma_lists_with_or_without_spaces() {
+    let expected = vec![
+        (false, Selector::Course("LI3")),
+        (false, Selector::Course("PI")),
+        (false, Selector::Course("SD")),
+    ];
+    assert_eq!(selectors("LI3,PI,SD"), expected);
+    assert_eq!(selectors("LI3, PI ,SD"), expected);
+    assert_eq!(selectors("LI3 PI SD"), expected);
+    assert_eq!(selectors(",LI3,,PI, SD,"), expected);
+}
+
+#[test]
+fn years_with_and_without_spaces() {
+    for input in ["3ano", "3 ano", "3ANO", "3 Anos", "3º ano", "3ºano"] {
+        assert_eq!(
+            selectors(input),
+            vec![(false, years(3, 3, None))],
+            "{}",
+            input
+        );
+    }
+}
+
+#[test]
+fn semesters_in_every_spelling() {
+    for input in [
+        "2ano1sem",
+        "2ano1semestre",
+        "2ano 1sem",
+        "2ano 1 sem",
+        "2ano 1",
+        "2 ano 1 semestre",
+        "2ano sem 1",
+        "2 anos 1sem",
+        "2ºano 1ºsem",
+    ] {
+        assert_eq!(
+            selectors(input),
+            vec![(false, years(2, 2, Some(1)))],
+            "{}",
+            input
+        );
+    }
+}
+
+#[test]
+fn year_ranges() {
+    assert_eq!(selectors("1-2ano"), vec![(false, years(1, 2, None))]);
+    assert_eq!(selectors("1-2 anos"), vec![(false, years(1, 2, None))]);
+    assert_eq!(selectors("1-3ano2sem"), vec![(false, years(1, 3, Some(2)))]);
+    assert_eq!(selectors("2-2ano"), vec![(false, years(2, 2, None))]);
+}
+
+#[test]
+fn backwards_ranges_are_errors() {
+    assert_eq!(parse("2-1ano"), Err(ParseError::BackwardsRange(2, 1)));
+    assert_eq!(parse("3-1 anos"), Err(ParseError::BackwardsRange(3, 1)));
+}
+
+#[test]
+fn a_semester_after_a_year_is_not_a_new_year() {
+    assert_eq!(
+        selectors("1ano 2 LI3"),
+        vec![
+            (false, years(1, 1, Some(2))),
+            (false, Selector::Course("LI3"))
+        ]
+    );
+    assert_eq!(
+        selectors("1ano 2ano"),
+        vec![(false, years(1, 1, None)), (false, years(2, 2, None))]
+    );
+    assert_eq!(
+        selectors("1ano, 2 ano"),
+        vec![(false, years(1, 1, None)), (false, years(2, 2, None))]
+    );
+}
+
+#[test]
+fn wildcards() {
+    assert_eq!(
+        selectors("LI* -LI3"),
+        vec![
+            (false, Selector::Wildcard("LI")),
+            (true, Selector::Course("LI3"))
+        ]
+    );
+    assert_eq!(selectors("1*"), vec![(false, Selector::Wildcard("1"))]);
+}
+
+#[test]
+fn a_lone_star_is_not_a_wildcard() {
+    assert_eq!(selectors("*"), vec![(false, Selector::Course("*"))]);
+    assert_eq!(selectors("L**"), vec![(false, Selector::Course("L**"))]);
+}
+
+#[test]
+fn exclusions() {
+    assert_eq!(
+        selectors("3ano -SD"),
+        vec![(false, years(3, 3, None)), (true, Selector::Course("SD"))]
+    );
+    assert_eq!(
+        selectors("3ano - SD"),
+        vec![(false, years(3, 3, None)), (true, Selector::Course("SD"))]
+    );
+    assert_eq!(
+        selectors("1-3ano -2ano1sem"),
+        vec![(false, years(1, 3, None)), (true, years(2, 2, Some(1)))]
+    );
+    assert_eq!(
+        selectors("3ano,-SD,-LI*"),
+        vec![
+            (false, years(3, 3, None)),
+            (true, Selector::Course("SD")),
+            (true, Selector::Wildcard("LI")),
+        ]
+    );
+}
+
+#[test]
+fn hyphens_inside_names_are_not_exclusions() {
+    assert_eq!(
+        selectors("ALGEBRA-LINEAR -ALGEBRA-LINEAR"),
+        vec![
+            (false, Selector::Course("ALGEBRA-LINEAR")),
+            (true, Selector::Course("ALGEBRA-LINEAR")),
+        ]
+    );
+}
+
+#[test]
+fn dangling_minus_is_an_error() {
+    assert_eq!(parse("3ano -"), Err(ParseError::DanglingMinus));
+    assert_eq!(parse("3ano -, SD"), Err(ParseError::DanglingMinus));
+    assert!(matches!(parse("- - SD"), Err(ParseError::Unexpected(_))));
+}
+
+#[test]
+fn semesters_need_a_year() {
+    assert_eq!(
+        parse("1sem"),
+        Err(ParseError::SemesterWithoutYear("1sem".to_string()))
+    );
+    assert_eq!(
+        parse("LI3 2semestre"),
+        Err(ParseError::SemesterWithoutYear("2semestre".to_string()))
+    );
+}
+
+#[test]
+fn keywords_on_their_own_are_errors() {
+    assert_eq!(parse("ano"), Err(ParseError::Unexpected("ano".to_string())));
+    assert_eq!(parse("sem"), Err(ParseError::Unexpected("sem".to_string())));
+    assert_eq!(
+        parse("2ano sem"),
+        Err(ParseError::Unexpected("sem".to_string()))
+    );
+}
+
+#[test]
+fn numbers_that_are_not_years_are_courses() {
+    assert_eq!(selectors("3"), vec![(false, Selector::Course("3"))]);
+    assert_eq!(selectors("1-2"), vec![(false, Selector::Course("1-2"))]);
+    assert_eq!(selectors("3D"), vec![(false, Selector::Course("3D"))]);
+    assert_eq!(selectors("2anox"), vec![(false, Selector::Course("2anox"))]);
+}
+
+#[test]
+fn words_that_start_like_keywords_are_courses() {
+    assert_eq!(
+        selectors("SEMINARIO ANOTACOES"),
+        vec![
+            (false, Selector::Course("SEMINARIO")),
+            (false, Selector::Course("ANOTACOES")),
+        ]
+    );
+}
+
+#[test]
+fn huge_numbers_are_courses() {
+    assert_eq!(
+        selectors("99999999999ano"),
+        vec![(false, Selector::Course("99999999999ano"))]
+    );
+}
+
+#[test]
+fn items_keep_their_text() {
+    let items = parse("2 ano 1 sem, -SD  LI*").unwrap();
+    let texts = items.iter().map(|i| i.text).collect::<Vec<_>>();
+    assert_eq!(texts, vec!["2 ano 1 sem", "-SD", "LI*"]);
+    assert_eq!(
+        items[1],
+        Item {
+            exclude: true,
+            selector: Selector::Course("SD"),
+            text: "-SD",
+        }
+    );
+}
+
+#[test]
+fn unicode_and_other_whitespace() {
+    assert_eq!(
+        selectors("ÁLGEBRA\tPI\n2ano"),
+        vec![
+            (false, Selector::Course("ÁLGEBRA")),
+            (false, Selector::Course("PI")),
+            (false, years(2, 2, None)),
+        ]
+    );
+}
@@ -0,0 +1,74 @@
+use rusteze::{
+    channels::{MiEI, Refusal},
+    commands::study::{Decision, decide},
+    storage::migrations::from_document,
+};
+use serde_json::json;
+use serenity::model::id::RoleId;
+
+const PI: RoleId = RoleId::new(1);
+const LA: RoleId = RoleId::new(2);
+const SD: RoleId = RoleId::new(3);
+const MEI: RoleId = RoleId::new(4);
+
+fn courses() -> MiEI {
+    from_document(json!({
+        "schema_version": 1,
+        "years": {
+            "1": {
+                "1": {
+                    "PI": { "role": "1", "channels": [] },
+                    "LA": { "role": "2", "channels": [] }
+                }
+            },
+            "3": {
+                "2": {
+                    "SD": { "role": "3", "channels": [], "restrictions": { "approval": true } }
+                }
+            }
+        }
+    }))
+    .unwrap()
+}
+
+fn decisions(member_roles: &[RoleId], joining: bool) -> Vec<Decision> {
+    decide(
+        &courses(),
+        member_roles,
+        &[("PI", PI), ("LA", LA), ("SD", SD)],
+        joining,
+    )
+}
+
+#[test]
+fn joining_adds_only_the_courses_the_student_is_not_in() {
+    assert_eq!(
+        decisions(&[PI, MEI], true),
+        vec![
+            Decision::Unchanged,
+            Decision::Change,
+            Decision::Refused(Refusal::NeedsApproval),
+        ]
+    );
+}
+
+#[test]
+fn leaving_removes_only_the_courses_the_student_is_in() {
+    assert_eq!(
+        decisions(&[PI, SD, MEI], false),
+        vec![Decision::Change, Decision::Unchanged, Decision::Change]
+    );
+}
+
+#[test]
+fn a_student_in_no_courses() {
+    assert_eq!(
+        decisions(&[], true),
+        vec![
+            Decision::Change,
+            Decision::Change,
+            Decision::Refused(Refusal::NeedsApproval),
+        ]
+    );
+    assert_eq!(decisions(&[], false), vec![Decision::Unchanged; 3]);
+}